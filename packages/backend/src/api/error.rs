@@ -3,7 +3,10 @@ use std::{fmt::Display, result};
 use axum::{
     Json,
     body::Body,
-    extract::rejection::PathRejection,
+    extract::{
+        multipart::{MultipartError, MultipartRejection},
+        rejection::PathRejection,
+    },
     http::{Response, StatusCode},
     response::IntoResponse,
 };
@@ -28,10 +31,36 @@ pub enum ApiError {
     #[serde(serialize_with = "a_message", rename = "query_error")]
     QueryRejection(#[from] serde_path_to_error::Error<serde_html_form::de::Error>),
 
+    #[error(transparent)]
+    #[serde(serialize_with = "a_message", rename = "multipart_error")]
+    MultipartRejection(#[from] MultipartRejection),
+
+    #[error(transparent)]
+    #[serde(serialize_with = "a_message", rename = "multipart_error")]
+    Multipart(#[from] MultipartError),
+
     #[error("Cannot parse pagination cursor: {0}")]
     #[serde(serialize_with = "a_message", rename = "query_error")]
     InvalidPaginationCursor(String),
 
+    #[error("Invalid search query: {0}")]
+    #[serde(rename = "search_query_error")]
+    InvalidSearchQuery(#[from] crate::services::ygo::query::ParseError),
+
+    #[error("Validation failed: {0}")]
+    #[serde(serialize_with = "a_message", rename = "validation_error")]
+    Validation(String),
+
+    #[error("Conflict: {0}")]
+    #[serde(serialize_with = "a_message", rename = "conflict")]
+    Conflict(String),
+
+    #[error("Deck breaks {} construction rule(s)", .violations.len())]
+    #[serde(rename = "invalid_deck")]
+    InvalidDeck {
+        violations: Vec<crate::services::ygo::deck::DeckViolation>,
+    },
+
     #[error(transparent)]
     #[serde(serialize_with = "no_content", rename = "database_error")]
     Postgres(#[from] tokio_postgres::Error),
@@ -56,7 +85,13 @@ impl ApiError {
             ApiError::NotFound { .. } => StatusCode::NOT_FOUND,
             ApiError::PathRejection(_) => StatusCode::BAD_REQUEST,
             ApiError::QueryRejection(_) => StatusCode::BAD_REQUEST,
+            ApiError::MultipartRejection(rejection) => rejection.status(),
+            ApiError::Multipart(err) => err.status(),
             ApiError::InvalidPaginationCursor(_) => StatusCode::BAD_REQUEST,
+            ApiError::InvalidSearchQuery(_) => StatusCode::BAD_REQUEST,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::InvalidDeck { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -242,4 +277,77 @@ mod tests {
         })
         .await;
     }
+
+    #[tokio::test]
+    async fn test_validation_error() {
+        with_app_state(async move |state| {
+            let router = Router::new()
+                .route(
+                    "/test",
+                    get(async || -> ApiResult<()> {
+                        Err(ApiError::Validation(
+                            "quantity must be positive".to_string(),
+                        ))
+                    }),
+                )
+                .with_state(state.as_ref().clone());
+            let request = Request::builder().uri("/test").body(Body::empty()).unwrap();
+
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                r#"{"error":"validation_error","message":"quantity must be positive"}"#
+            );
+        })
+        .await;
+    }
+
+    #[test]
+    fn test_serialize_invalid_deck_error() {
+        let error = ApiError::InvalidDeck {
+            violations: vec![crate::services::ygo::deck::DeckViolation::UnknownCard {
+                card_id: 144,
+            }],
+        };
+        assert_eq!(error.to_string(), "Deck breaks 1 construction rule(s)");
+
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(
+            json,
+            r#"{"error":"invalid_deck","violations":[{"rule":"unknown_card","cardId":144}]}"#
+        );
+    }
+
+    #[test]
+    fn test_serialize_invalid_search_query_error() {
+        let error: ApiError = crate::services::ygo::query::parse("atk>=high")
+            .unwrap_err()
+            .into();
+        assert_eq!(
+            error.to_string(),
+            "Invalid search query: invalid value 'high' for 'atk' at position 5"
+        );
+        assert_eq!(error.status_code(), StatusCode::BAD_REQUEST);
+
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(
+            json,
+            r#"{"error":"search_query_error","message":"invalid value 'high' for 'atk'","position":5}"#
+        );
+    }
+
+    #[test]
+    fn test_serialize_conflict_error() {
+        let error = ApiError::Conflict("Image prefetch is already running".to_string());
+        assert_eq!(error.status_code(), StatusCode::CONFLICT);
+
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(
+            json,
+            r#"{"error":"conflict","message":"Image prefetch is already running"}"#
+        );
+    }
 }
@@ -0,0 +1,67 @@
+use axum::{Json, extract::State, response::IntoResponse};
+
+use crate::api::{ApiResult, Query};
+use crate::prelude::AppState;
+use crate::services::ygo as service;
+
+/// Lists banlists, most recent first
+pub async fn get_banlists(
+    State(state): State<AppState>,
+    Query(filter): Query<service::banlist::Filter>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let banlists = service::banlist::get_all(&client, Some(filter)).await?;
+
+    Ok(Json(banlists).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::NaiveDate;
+
+    use crate::models::ygo;
+    use crate::test_utils::*;
+
+    use super::*;
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode},
+        routing::get,
+    };
+
+    #[tokio::test]
+    async fn test_get_banlists() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                for format in [ygo::BanlistFormat::Tcg, ygo::BanlistFormat::Goat] {
+                    let date = NaiveDate::from_ymd_opt(2005, 4, 1).unwrap();
+                    service::banlist::save(&client, format, date, &HashMap::new())
+                        .await
+                        .expect("banlist");
+                }
+            }
+
+            let router = Router::new()
+                .route("/ygo/banlists", get(get_banlists))
+                .with_state(state.as_ref().clone());
+            let request = Request::builder()
+                .uri("/ygo/banlists?format=goat")
+                .body(Body::empty())
+                .unwrap();
+
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let banlists: Vec<ygo::Banlist> = serde_json::from_slice(&body).expect("json");
+            assert_eq!(banlists.len(), 1);
+            assert_eq!(banlists[0].format, ygo::BanlistFormat::Goat);
+        })
+        .await
+    }
+}
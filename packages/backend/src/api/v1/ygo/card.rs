@@ -1,6 +1,9 @@
-use std::path::PathBuf;
-
-use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum::{
+    Json,
+    extract::{Multipart, State, multipart::Field},
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+};
 
 use crate::api::utils::{decode_pagination_cursor, encode_pagination_cursor};
 use crate::api::{ApiError, ApiResult, Path, Query};
@@ -8,7 +11,10 @@ use crate::importers;
 use crate::importers::ygoprodeck::CardImageSize;
 use crate::models::ygo;
 use crate::prelude::AppState;
+use crate::services::image::{self as service_image, ImageFormat};
+use crate::services::image_cache::CachedImage;
 use crate::services::ygo as service;
+use crate::services::ygo::image::{get_card_image_key, get_card_upload_key};
 
 use serde::{Deserialize, Serialize};
 
@@ -18,21 +24,59 @@ pub struct Pagination {
     pub cursor: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct TotalQuery {
+    /// Whether to count the cards matching the filter
+    #[serde(default)]
+    pub include_total: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Page {
     pub cards: Vec<crate::models::ygo::Card>,
     pub next: Option<String>,
+    pub prev: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<service::card::Total>,
+}
+
+/// Search query, see `service::query::parse` for its syntax
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: Option<String>,
+}
+
+impl SearchQuery {
+    /// Parses the search query, if not blank
+    fn parse(self) -> ApiResult<Option<service::query::Expr>> {
+        let expr = self
+            .q
+            .filter(|q| !q.trim().is_empty())
+            .map(|q| service::query::parse(&q))
+            .transpose()?;
+
+        Ok(expr)
+    }
 }
 
 /// Lists yugioh cards (paginated)
 pub async fn get_cards(
     State(state): State<AppState>,
     Query(pagination): Query<Pagination>,
-    Query(filter): Query<service::card::Filter>,
+    Query(mut filter): Query<service::card::Filter>,
     Query(sort): Query<service::card::Sort>,
+    Query(search): Query<SearchQuery>,
+    Query(total_query): Query<TotalQuery>,
 ) -> ApiResult<impl IntoResponse> {
     let client = state.db.get().await?;
 
+    filter.q = search.parse()?;
+
+    let total = match total_query.include_total {
+        true => Some(service::card::count(&client, Some(filter.clone())).await?),
+        false => None,
+    };
+
     let limit = pagination.limit.unwrap_or(100).min(100);
     let cursor = pagination
         .cursor
@@ -40,7 +84,7 @@ pub async fn get_cards(
         .map(|c| decode_pagination_cursor(c))
         .transpose()?;
 
-    let (cards, next_cursor) =
+    let (cards, next_cursor, prev_cursor) =
         service::card::get_page(&client, Some(filter), Some(sort), limit, cursor).await?;
 
     let as_page = Page {
@@ -49,11 +93,55 @@ pub async fn get_cards(
             .as_ref()
             .map(encode_pagination_cursor)
             .transpose()?,
+        prev: prev_cursor
+            .as_ref()
+            .map(encode_pagination_cursor)
+            .transpose()?,
+        total,
     };
 
     Ok(Json(as_page).into_response())
 }
 
+/// Counts the cards matching the filter for each value of the card list filters
+pub async fn get_facets(
+    State(state): State<AppState>,
+    Query(mut filter): Query<service::card::Filter>,
+    Query(search): Query<SearchQuery>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    filter.q = search.parse()?;
+    let facets = service::card::get_facets(&client, filter).await?;
+
+    Ok(Json(facets).into_response())
+}
+
+/// Card name lookup query
+#[derive(Debug, Deserialize)]
+pub struct LookupQuery {
+    pub name: String,
+    pub limit: Option<u32>,
+}
+
+/// Looks up cards by name, tolerating typos. Best matches come first, with their similarity.
+pub async fn lookup(
+    State(state): State<AppState>,
+    Query(query): Query<LookupQuery>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let name = query.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::Validation("name cannot be empty".to_string()));
+    }
+    let limit = query.limit.unwrap_or(10).min(50);
+
+    let matches = service::card::lookup(&client, name, limit).await?;
+
+    Ok(Json(matches).into_response())
+}
+
 /// Get card by ID
 pub async fn get_by_id(
     State(state): State<AppState>,
@@ -70,76 +158,292 @@ pub async fn get_by_id(
     Ok(Json(card).into_response())
 }
 
-/// Card art size query
+/// Lists the artworks of a card
+pub async fn get_artworks(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    // Make sure the card exists
+    service::card::get_by_id(&client, id)
+        .await?
+        .ok_or(ApiError::NotFound {
+            resource: id.into(),
+        })?;
+
+    let artworks = service::artwork::get_by_card_id(&client, id).await?;
+
+    Ok(Json(artworks).into_response())
+}
+
+/// Card art size, artwork and resizing query
 #[derive(Debug, Deserialize)]
 pub struct CardImageOptionsQuery {
     pub size: Option<CardImageSize>,
+    /// Index of the artwork, defaults to the card's default artwork
+    pub art: Option<i16>,
+    /// Width to resize the image down to, in pixels
+    pub width: Option<u32>,
 }
 
-/// Get card image by ID
+/// Widths images can be resized to. Requested widths are rounded up to the next one, so that
+/// only a few variants of each image are transcoded and cached.
+const IMAGE_WIDTHS: [u32; 6] = [64, 128, 256, 512, 1024, 2048];
+
+/// Range of widths and heights of uploaded images
+const UPLOAD_DIMENSIONS: std::ops::RangeInclusive<u32> = 64..=4096;
+
+/// Maximum size of uploaded images
+const MAX_UPLOAD_SIZE: usize = 8 * 1024 * 1024; // 8 MB
+
+/// Maximum size of image upload requests, leaving room for the multipart encoding
+pub const MAX_UPLOAD_REQUEST_SIZE: usize = MAX_UPLOAD_SIZE + 64 * 1024;
+
+/// Get card image by ID. The image is resized when a width is given, and transcoded to the
+/// smallest format accepted by the client.
 pub async fn get_image_by_id(
     State(state): State<AppState>,
     Path(id): Path<i32>,
     Query(options): Query<CardImageOptionsQuery>,
+    headers: HeaderMap,
 ) -> ApiResult<impl IntoResponse> {
-    let client = state.db.get().await?;
     let size = options.size.unwrap_or(CardImageSize::Small);
-    let card_image_path = get_card_image_path(&state, id, &size).await;
+    let art = options.art.unwrap_or(0);
+    let width = options
+        .width
+        .map(|width| {
+            IMAGE_WIDTHS
+                .into_iter()
+                .find(|&breakpoint| width > 0 && breakpoint >= width)
+                .ok_or_else(|| {
+                    ApiError::Validation(format!(
+                        "width must be between 1 and {}",
+                        IMAGE_WIDTHS[IMAGE_WIDTHS.len() - 1]
+                    ))
+                })
+        })
+        .transpose()?;
+    let not_found = || ApiError::NotFound {
+        resource: serde_json::json!({ "card": id, "art": art }),
+    };
 
-    let image_data;
-    if card_image_path.exists() {
-        tracing::debug!("Serving cached image for card ID {id}");
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .unwrap_or_default();
+    let format = ImageFormat::negotiate(accept);
 
-        image_data = tokio::fs::read(card_image_path).await?;
+    // Uploaded images replace every size of the default artwork
+    let uploaded = art == 0
+        && state
+            .images
+            .storage()
+            .exists(&get_card_upload_key(id))
+            .await?;
+
+    let card_image_key = get_card_image_key(id, art, &size, width, format);
+
+    let image = if width.is_none() && format == ImageFormat::Jpeg {
+        get_original_card_image(&state, id, art, &size, uploaded).await?
     } else {
-        tracing::debug!("Fetching image from ygoprodeck for card ID {id}");
+        state
+            .images
+            .get_or_create(&card_image_key, async || {
+                let Some(original) =
+                    get_original_card_image(&state, id, art, &size, uploaded).await?
+                else {
+                    return Ok(None);
+                };
+
+                tracing::debug!("Transcoding image for card ID {id} (artwork {art}) to {format:?}");
+
+                // Images are never upscaled: widths from the original's on keep its size
+                let (original_width, _) = service_image::dimensions(&original.data)?;
+                let width = width.filter(|&width| width < original_width);
+                let image_data = state
+                    .transcoder
+                    .transcode(original.data, width, format)
+                    .await?;
+
+                Ok::<_, ApiError>(Some(image_data))
+            })
+            .await?
+    };
+    let image = image.ok_or_else(not_found)?;
+
+    // Other artworks never change, so clients can keep them as long as they want. The default
+    // artwork may be replaced by an upload at the same URL, so clients check it with its ETag
+    // first. The image served depends on the formats accepted by the client.
+    let etag = format!("\"{}\"", image.hash);
+    let cache_control = match art {
+        0 => REVALIDATE_CACHE_CONTROL,
+        _ => IMMUTABLE_CACHE_CONTROL,
+    };
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, cache_control.to_string()),
+        (header::VARY, "accept".to_string()),
+    ];
+
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH)
+        && etag_matches(if_none_match.to_str().unwrap_or_default(), &etag)
+    {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+
+    let content_type = service_image::content_type(&image.data);
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, content_type)],
+        cache_headers,
+        image.data,
+    )
+        .into_response())
+}
+
+/// Cache-Control header of images that never change
+pub const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// Cache-Control header of images that may change, to be revalidated before each use
+const REVALIDATE_CACHE_CONTROL: &str = "public, no-cache";
 
-        let card = service::card::get_by_id(&client, id)
+/// Whether an If-None-Match header value matches an ETag. Uses the weak comparison, as
+/// required for If-None-Match.
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+/// Utility to get the original card image, as uploaded or as served by ygoprodeck, from the
+/// local cache. The image is retrieved from ygoprodeck and cached when missing, unless ygoprodeck
+/// has none.
+async fn get_original_card_image(
+    state: &AppState,
+    id: i32,
+    art: i16,
+    size: &CardImageSize,
+    uploaded: bool,
+) -> ApiResult<Option<CachedImage>> {
+    if uploaded {
+        return Ok(state.images.read(&get_card_upload_key(id)).await?);
+    }
+
+    let card_image_key = get_card_image_key(id, art, size, None, ImageFormat::Jpeg);
+
+    state
+        .images
+        .get_or_create(&card_image_key, async || {
+            tracing::debug!("Fetching image from ygoprodeck for card ID {id} (artwork {art})");
+
+            let client = state.db.get().await?;
+            let card = service::card::get_by_id(&client, id)
+                .await?
+                .ok_or(ApiError::NotFound {
+                    resource: id.into(),
+                })?;
+
+            // Cards without known artworks can still serve their default one
+            let ygoprodeck_id = match service::artwork::get_by_index(&client, id, art).await? {
+                Some(artwork) => artwork.ygoprodeck_id,
+                None if art == 0 => {
+                    // Custom cards only have the images uploaded for them
+                    card.data.ygoprodeck_id.ok_or(ApiError::NotFound {
+                        resource: serde_json::json!({ "card": id, "art": art }),
+                    })?
+                }
+                None => {
+                    return Err(ApiError::NotFound {
+                        resource: serde_json::json!({ "card": id, "art": art }),
+                    });
+                }
+            };
+
+            // Retrieve card art from ygopro deck
+            Ok(importers::ygoprodeck::get_card_image(ygoprodeck_id, size).await?)
+        })
+        .await
+}
+
+/// Upload an image for a card, as a scan or a proxy, in the `image` field of a multipart form.
+/// The image replaces the one from ygoprodeck, in every size.
+pub async fn upload_image(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    mut multipart: Multipart,
+) -> ApiResult<impl IntoResponse> {
+    {
+        let client = state.db.get().await?;
+        service::card::get_by_id(&client, id)
             .await?
             .ok_or(ApiError::NotFound {
                 resource: id.into(),
             })?;
+    }
 
-        let ygoprodeck_id = card
-            .data
-            .ygoprodeck_id
-            .ok_or(anyhow::anyhow!("Card {id} has no ygoprodeck ID"))?;
+    let mut image = None;
+    while let Some(field) = multipart.next_field().await? {
+        if field.name() == Some("image") {
+            image = Some(read_uploaded_image(field).await?);
+        }
+    }
 
-        // Retrieve card art from ygopro deck
-        image_data = importers::ygoprodeck::get_card_image(ygoprodeck_id, &size).await?;
+    let image = image.ok_or(ApiError::Validation("image is missing".to_string()))?;
+    let (image, _) = check_uploaded_image(image).await?;
 
-        // Make sure the parent directory exists
-        if let Some(parent) = card_image_path.parent() {
-            tokio::fs::create_dir_all(parent).await?;
-        }
+    let image = state.images.save(&get_card_upload_key(id), image).await?;
 
-        // Save the image to local cache
-        tokio::fs::write(card_image_path, &image_data).await?;
-    }
+    // Images cached from ygoprodeck or from a previous upload are outdated
+    service::image::delete_cached_card_images(&state.images, id).await?;
 
-    // Serve it as an image
-    Ok((StatusCode::OK, [("content-type", "image/jpeg")], image_data).into_response())
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(header::ETAG, format!("\"{}\"", image.hash))],
+    )
+        .into_response())
 }
 
-/// Utility to get the card's image path
-pub async fn get_card_image_path(state: &AppState, card_id: i32, size: &CardImageSize) -> PathBuf {
-    let size_suffix = match size {
-        CardImageSize::Small => "",
-        CardImageSize::Full => "_full",
-        CardImageSize::ArtOnly => "_cropped",
-    };
+/// Reads an image uploaded in a multipart field, up to the maximum upload size
+pub async fn read_uploaded_image(mut field: Field<'_>) -> ApiResult<Vec<u8>> {
+    let mut data = Vec::new();
+    while let Some(chunk) = field.chunk().await? {
+        if data.len() + chunk.len() > MAX_UPLOAD_SIZE {
+            return Err(ApiError::Validation(format!(
+                "image must be at most {} MB",
+                MAX_UPLOAD_SIZE / 1024 / 1024
+            )));
+        }
+        data.extend_from_slice(&chunk);
+    }
 
-    state
-        .config
-        .get_content_path()
-        .join(format!("card/{card_id}{size_suffix}.jpg"))
+    Ok(data)
+}
+
+/// Checks that an uploaded image is a valid image of acceptable dimensions.
+/// Returns the image along with its width and height.
+pub async fn check_uploaded_image(image: Vec<u8>) -> ApiResult<(Vec<u8>, (u32, u32))> {
+    let checked = tokio::task::spawn_blocking(move || {
+        service_image::validate_upload(&image, UPLOAD_DIMENSIONS)
+            .map(|dimensions| (image, dimensions))
+    })
+    .await
+    .map_err(anyhow::Error::from)?
+    .map_err(ApiError::Validation)?;
+
+    Ok(checked)
 }
 
 /// Import yugioh cards
 pub async fn import(State(state): State<AppState>) -> ApiResult<impl IntoResponse> {
-    let client = state.db.get().await?;
-
-    let result = importers::ygoprodeck::import(&client).await?;
+    // The import goes on if the client disconnects, rather than being dropped in the middle of
+    // its transaction
+    let result = tokio::spawn(async move {
+        let client = state.db.get().await?;
+        importers::ygoprodeck::import(&client, &state.images).await
+    })
+    .await
+    .map_err(anyhow::Error::from)??;
 
     Ok(Json(result).into_response())
 }
@@ -163,6 +467,11 @@ pub async fn delete_by_id(
 
     let deleted = service::card::delete_by_id(&client, id).await?;
     if deleted {
+        // Images left behind are deleted by the next orphan sweep
+        if let Err(err) = service::image::delete_card_images(&state.images, id).await {
+            tracing::warn!("Could not delete images of card ID {id}: {err}");
+        }
+
         Ok(StatusCode::NO_CONTENT)
     } else {
         Err(ApiError::NotFound {
@@ -184,6 +493,7 @@ pub async fn update(
         id,
         updated_at: chrono::Utc::now(),
         data,
+        banlist: Default::default(),
     };
 
     let updated = service::card::save(&client, &to_update)
@@ -197,17 +507,265 @@ pub async fn update(
 
 #[cfg(test)]
 mod tests {
+    use std::net::SocketAddr;
+
     use crate::models::ygo;
+    use crate::services::image_cache;
     use crate::test_utils::*;
 
     use super::*;
     use axum::{
         Router,
         body::Body,
+        extract::ConnectInfo,
         http::{Request, StatusCode},
         routing::{delete, get, post, put},
     };
 
+    #[tokio::test]
+    async fn test_get_facets() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                for (name, attribute) in [
+                    ("Dark Magician", ygo::MonsterAttribute::Dark),
+                    ("Blue-Eyes White Dragon", ygo::MonsterAttribute::Light),
+                    ("Red-Eyes Black Dragon", ygo::MonsterAttribute::Dark),
+                ] {
+                    let new = ygo::NewCard {
+                        data: ygo::CardData {
+                            name: name.to_string(),
+                            monster_attribute: Some(attribute),
+                            ..Default::default()
+                        },
+                    };
+                    service::card::save_new(&client, &new).await.expect("card");
+                }
+            }
+
+            let router = Router::new()
+                .route("/ygo/cards/facets", get(get_facets))
+                .with_state(state.as_ref().clone());
+            let request = Request::builder()
+                .uri("/ygo/cards/facets?q=name%3Adragon")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let facets: serde_json::Value = serde_json::from_slice(&body).expect("json");
+            assert_eq!(
+                facets["attribute"],
+                serde_json::json!({ "dark": 1, "light": 1 })
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_lookup() {
+        with_app_state(async move |state| {
+            let card = {
+                let client = state.db.get().await.expect("db");
+                let new = ygo::NewCard {
+                    data: ygo::CardData {
+                        name: "Blue-Eyes White Dragon".to_string(),
+                        ..Default::default()
+                    },
+                };
+                service::card::save_new(&client, &new).await.expect("card")
+            };
+
+            let router = Router::new()
+                .route("/ygo/cards/lookup", get(lookup))
+                .with_state(state.as_ref().clone());
+
+            let request = Request::builder()
+                .uri("/ygo/cards/lookup?name=blue%20eyes%20white%20dragn")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let matches: Vec<service::card::CardMatch> =
+                serde_json::from_slice(&body).expect("Unable to parse response body");
+            assert_eq!(matches.len(), 1);
+            assert_eq!(matches[0].card, card);
+            assert!(matches[0].similarity > 0.5);
+
+            let request = Request::builder()
+                .uri("/ygo/cards/lookup?name=%20")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_get_cards_with_search_query() {
+        with_app_state(async move |state| {
+            let ids = {
+                let client = state.db.get().await.expect("db");
+                let cards = [
+                    ("Blue-Eyes White Dragon", ygo::MonsterAttribute::Light, 3000),
+                    ("Red-Eyes Black Dragon", ygo::MonsterAttribute::Dark, 2400),
+                    ("Dark Magician", ygo::MonsterAttribute::Dark, 2500),
+                ];
+                let mut ids = Vec::new();
+                for (name, attribute, atk) in cards {
+                    let new = ygo::NewCard {
+                        data: ygo::CardData {
+                            name: name.to_string(),
+                            description: "A monster.".to_string(),
+                            monster_attribute: Some(attribute),
+                            monster_atk: Some(atk),
+                            ..Default::default()
+                        },
+                    };
+                    ids.push(
+                        service::card::save_new(&client, &new)
+                            .await
+                            .expect("card")
+                            .id,
+                    );
+                }
+                ids
+            };
+
+            let router = Router::new()
+                .route("/ygo/cards", get(get_cards))
+                .with_state(state.as_ref().clone());
+
+            // atk>=2500 (attr:light OR dragon)
+            let request = Request::builder()
+                .uri("/ygo/cards?q=atk%3E%3D2500%20(attr%3Alight%20OR%20dragon)")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let paginated: super::Page =
+                serde_json::from_slice(&body).expect("Unable to parse response body");
+            let found: Vec<_> = paginated.cards.iter().map(|c| c.id).collect();
+            assert_eq!(found, vec![ids[0]]);
+
+            // -attr:light
+            let request = Request::builder()
+                .uri("/ygo/cards?q=-attr%3Alight")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let paginated: super::Page =
+                serde_json::from_slice(&body).expect("Unable to parse response body");
+            let found: Vec<_> = paginated.cards.iter().map(|c| c.id).collect();
+            assert_eq!(found, vec![ids[1], ids[2]]);
+
+            // dragon (atk>2000
+            let request = Request::builder()
+                .uri("/ygo/cards?q=dragon%20(atk%3E2000")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                r#"{"error":"search_query_error","message":"expected ')'","position":16}"#
+            );
+
+            // Deeply nested queries are rejected rather than overflowing the stack
+            let request = Request::builder()
+                .uri(format!("/ygo/cards?q={}", "(".repeat(1000)))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_get_cards_by_link_arrows() {
+        with_app_state(async move |state| {
+            let ids = {
+                let client = state.db.get().await.expect("db");
+                let cards = [
+                    (
+                        "Decode Talker",
+                        ygo::LinkArrows::Top | ygo::LinkArrows::BottomLeft,
+                    ),
+                    (
+                        "Firewall Dragon",
+                        ygo::LinkArrows::Top | ygo::LinkArrows::Left | ygo::LinkArrows::Right,
+                    ),
+                    ("Link Spider", ygo::LinkArrows::Bottom),
+                ];
+                let mut ids = Vec::new();
+                for (name, arrows) in cards {
+                    let new = ygo::NewCard {
+                        data: ygo::CardData {
+                            name: name.to_string(),
+                            description: "A link monster.".to_string(),
+                            monster_kind: Some(ygo::MonsterKind::Link),
+                            monster_link_arrows: Some(arrows),
+                            ..Default::default()
+                        },
+                    };
+                    ids.push(
+                        service::card::save_new(&client, &new)
+                            .await
+                            .expect("card")
+                            .id,
+                    );
+                }
+                ids
+            };
+
+            let router = Router::new()
+                .route("/ygo/cards", get(get_cards))
+                .with_state(state.as_ref().clone());
+
+            let cases = [
+                ("linkArrowsAll=top", vec![ids[0], ids[1]]),
+                ("linkArrowsAll=top&linkArrowsAll=right", vec![ids[1]]),
+                (
+                    "linkArrowsAny=bottom_left&linkArrowsAny=bottom",
+                    vec![ids[0], ids[2]],
+                ),
+            ];
+            for (query, expected) in cases {
+                let request = Request::builder()
+                    .uri(format!("/ygo/cards?{query}"))
+                    .body(Body::empty())
+                    .unwrap();
+                let response = router.clone().oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                let paginated: super::Page =
+                    serde_json::from_slice(&body).expect("Unable to parse response body");
+                let found: Vec<_> = paginated.cards.iter().map(|c| c.id).collect();
+                assert_eq!(found, expected, "{query}");
+            }
+
+            // Unknown arrows are rejected
+            let request = Request::builder()
+                .uri("/ygo/cards?linkArrowsAny=middle")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn test_get_cards() {
         with_app_state(async move |state| {
@@ -236,6 +794,50 @@ mod tests {
         .await
     }
 
+    #[tokio::test]
+    async fn test_get_cards_with_total_and_prev() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 3).await.expect("seed");
+            }
+
+            let router = Router::new()
+                .route("/ygo/cards", get(get_cards))
+                .with_state(state.as_ref().clone());
+            let get_page = async |uri: String| -> super::Page {
+                let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+                let response = router.clone().oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                serde_json::from_slice(&body).expect("Unable to parse response body")
+            };
+
+            let page1 = get_page("/ygo/cards?limit=2&include_total=true".to_string()).await;
+            assert_eq!(
+                page1.total,
+                Some(service::card::Total {
+                    count: 3,
+                    estimated: false
+                })
+            );
+            assert!(page1.prev.is_none());
+
+            let next = page1.next.clone().expect("next");
+            let page2 = get_page(format!("/ygo/cards?limit=2&cursor={next}")).await;
+            assert_eq!(page2.cards.len(), 1);
+            assert!(page2.next.is_none());
+            assert!(page2.total.is_none());
+
+            let prev = page2.prev.expect("prev");
+            let back = get_page(format!("/ygo/cards?limit=2&cursor={prev}")).await;
+            assert_eq!(back.cards, page1.cards);
+            assert!(back.prev.is_none());
+            assert_eq!(back.next, page1.next);
+        })
+        .await
+    }
+
     #[tokio::test]
     async fn test_get_by_id() {
         with_app_state(async move |state| {
@@ -265,6 +867,393 @@ mod tests {
         .await
     }
 
+    #[tokio::test]
+    async fn test_get_cards_by_banlist_status() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 2).await.expect("seed");
+                let date = chrono::NaiveDate::from_ymd_opt(2005, 4, 1).unwrap();
+                let limits = std::collections::HashMap::from([(2, 1)]);
+                service::banlist::save(&client, ygo::BanlistFormat::Goat, date, &limits)
+                    .await
+                    .expect("banlist");
+            }
+
+            let router = Router::new()
+                .route("/ygo/cards", get(get_cards))
+                .with_state(state.as_ref().clone());
+            let request = Request::builder()
+                .uri("/ygo/cards?banlist=limited&banlistFormat=goat")
+                .body(Body::empty())
+                .unwrap();
+
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let paginated: serde_json::Value = serde_json::from_slice(&body).expect("json");
+            let cards = paginated["cards"].as_array().expect("cards");
+            assert_eq!(cards.len(), 1);
+            assert_eq!(cards[0]["id"], 2);
+            assert_eq!(
+                cards[0]["banlist"],
+                serde_json::json!({ "goat": "limited" })
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_get_artworks() {
+        with_app_state(async move |state| {
+            let id = {
+                let client = state.db.get().await.expect("db");
+                let new = ygo::NewCard {
+                    data: ygo::CardData {
+                        name: "Dark Magician".to_string(),
+                        ygoprodeck_id: Some(46986414),
+                        ..Default::default()
+                    },
+                };
+                let card = service::card::save_new(&client, &new).await.expect("card");
+                service::artwork::save_all_by_card(&client, &[(card.id, vec![46986414, 36996508])])
+                    .await
+                    .expect("artworks");
+                card.id
+            };
+
+            let router = Router::new()
+                .route("/ygo/cards/{id}/images", get(get_artworks))
+                .route("/ygo/cards/{id}/image", get(get_image_by_id))
+                .with_state(state.as_ref().clone());
+
+            let request = Request::builder()
+                .uri(format!("/ygo/cards/{id}/images"))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let artworks: Vec<ygo::CardArtwork> =
+                serde_json::from_slice(&body).expect("Unable to parse response body");
+            assert_eq!(
+                artworks
+                    .iter()
+                    .map(|a| (a.index, a.ygoprodeck_id))
+                    .collect::<Vec<_>>(),
+                vec![(0, 46986414), (1, 36996508)]
+            );
+
+            // Unknown card
+            let request = Request::builder()
+                .uri(format!("/ygo/cards/{}/images", id + 1))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            // Unknown artwork
+            let request = Request::builder()
+                .uri(format!("/ygo/cards/{id}/image?art=2"))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            // Each artwork is cached on its own
+            let small = get_card_image_key(id, 0, &CardImageSize::Small, None, ImageFormat::Jpeg);
+            let alternate =
+                get_card_image_key(id, 1, &CardImageSize::Small, None, ImageFormat::Jpeg);
+            assert_eq!(small, format!("card/{id}.jpg"));
+            assert_eq!(alternate, format!("card/{id}_art1.jpg"));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_get_image_resized_and_transcoded() {
+        with_app_state(async move |state| {
+            let id = {
+                let client = state.db.get().await.expect("db");
+                let new = ygo::NewCard {
+                    data: ygo::CardData {
+                        name: "Dark Magician".to_string(),
+                        ..Default::default()
+                    },
+                };
+                service::card::save_new(&client, &new)
+                    .await
+                    .expect("card")
+                    .id
+            };
+
+            // Cache the original image, so that it's not retrieved from ygoprodeck
+            let original_key =
+                get_card_image_key(id, 0, &CardImageSize::Small, None, ImageFormat::Jpeg);
+            let mut original = std::io::Cursor::new(Vec::new());
+            image::RgbImage::from_pixel(200, 290, image::Rgb([64, 32, 128]))
+                .write_to(&mut original, image::ImageFormat::Jpeg)
+                .expect("jpeg");
+            state
+                .images
+                .storage()
+                .write(&original_key, original.get_ref())
+                .await
+                .unwrap();
+
+            let router = Router::new()
+                .route("/ygo/cards/{id}/image", get(get_image_by_id))
+                .with_state(state.as_ref().clone());
+
+            let request = Request::builder()
+                .uri(format!("/ygo/cards/{id}/image?width=100"))
+                .header("accept", "image/webp,*/*;q=0.8")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["content-type"], "image/webp");
+            assert_eq!(response.headers()["vary"], "accept");
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let image = webp::Decoder::new(&body).decode().expect("webp");
+            assert_eq!((image.width(), image.height()), (128, 185));
+
+            // The variant is cached by format and width, rounded up to the next breakpoint
+            let variant_key =
+                get_card_image_key(id, 0, &CardImageSize::Small, Some(128), ImageFormat::Webp);
+            assert_eq!(variant_key, format!("card/{id}_w128.webp"));
+            assert!(state.images.storage().exists(&variant_key).await.unwrap());
+
+            // Widths from the original's on are served at the original's size
+            let request = Request::builder()
+                .uri(format!("/ygo/cards/{id}/image?width=1000"))
+                .header("accept", "image/webp")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let image = webp::Decoder::new(&body).decode().expect("webp");
+            assert_eq!((image.width(), image.height()), (200, 290));
+            let full_width_key =
+                get_card_image_key(id, 0, &CardImageSize::Small, Some(1024), ImageFormat::Webp);
+            assert!(
+                state
+                    .images
+                    .storage()
+                    .exists(&full_width_key)
+                    .await
+                    .unwrap()
+            );
+
+            // Cached variants are served without reading the original
+            state.images.delete(&original_key).await.unwrap();
+            let request = Request::builder()
+                .uri(format!("/ygo/cards/{id}/image?width=100"))
+                .header("accept", "image/webp")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            state
+                .images
+                .storage()
+                .write(&original_key, original.get_ref())
+                .await
+                .unwrap();
+
+            // Clients without WebP or AVIF support get the original JPEG
+            let request = Request::builder()
+                .uri(format!("/ygo/cards/{id}/image"))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["content-type"], "image/jpeg");
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, original.get_ref()[..]);
+
+            // Widths out of range are rejected
+            for width in [0, 4096] {
+                let request = Request::builder()
+                    .uri(format!("/ygo/cards/{id}/image?width={width}"))
+                    .body(Body::empty())
+                    .unwrap();
+                let response = router.clone().oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+            }
+
+            for key in [original_key, variant_key, full_width_key] {
+                state.images.delete(&key).await.unwrap();
+            }
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_get_image_conditional_requests() {
+        with_app_state(async move |state| {
+            let id = {
+                let client = state.db.get().await.expect("db");
+                let new = ygo::NewCard {
+                    data: ygo::CardData {
+                        name: "Dark Magician".to_string(),
+                        ..Default::default()
+                    },
+                };
+                service::card::save_new(&client, &new)
+                    .await
+                    .expect("card")
+                    .id
+            };
+
+            // Cache an original image that's not a JPEG
+            let original_key =
+                get_card_image_key(id, 0, &CardImageSize::Small, None, ImageFormat::Jpeg);
+            let mut original = std::io::Cursor::new(Vec::new());
+            image::RgbImage::new(4, 4)
+                .write_to(&mut original, image::ImageFormat::Png)
+                .expect("png");
+            state
+                .images
+                .storage()
+                .write(&original_key, original.get_ref())
+                .await
+                .unwrap();
+
+            let router = Router::new()
+                .route("/ygo/cards/{id}/image", get(get_image_by_id))
+                .with_state(state.as_ref().clone());
+            let request = |if_none_match: Option<&str>| {
+                let mut request = Request::builder().uri(format!("/ygo/cards/{id}/image"));
+                if let Some(if_none_match) = if_none_match {
+                    request = request.header("if-none-match", if_none_match);
+                }
+                request.body(Body::empty()).unwrap()
+            };
+
+            let response = router.clone().oneshot(request(None)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["content-type"], "image/png");
+            assert_eq!(response.headers()["cache-control"], "public, no-cache");
+            let etag = response.headers()["etag"].to_str().unwrap().to_string();
+            assert_eq!(
+                etag,
+                format!("\"{}\"", service_image::content_hash(original.get_ref()))
+            );
+
+            // The hash is stored next to the cached image
+            let hash_key = image_cache::get_hash_key(&original_key);
+            assert_eq!(
+                state.images.storage().read(&hash_key).await.unwrap(),
+                Some(etag.trim_matches('"').as_bytes().to_vec())
+            );
+
+            for if_none_match in [etag.clone(), format!("W/{etag}"), format!("\"a\", {etag}")] {
+                let response = router
+                    .clone()
+                    .oneshot(request(Some(&if_none_match)))
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+                assert_eq!(response.headers()["etag"], etag.as_str());
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                assert!(body.is_empty());
+            }
+
+            let response = router
+                .clone()
+                .oneshot(request(Some("\"other\"")))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            // Other artworks can't be replaced by uploads, so they're cached for good
+            let artwork_key =
+                get_card_image_key(id, 1, &CardImageSize::Small, None, ImageFormat::Jpeg);
+            state
+                .images
+                .storage()
+                .write(&artwork_key, original.get_ref())
+                .await
+                .unwrap();
+            let request = Request::builder()
+                .uri(format!("/ygo/cards/{id}/image?art=1"))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers()["cache-control"],
+                "public, max-age=31536000, immutable"
+            );
+
+            for key in [original_key, artwork_key] {
+                state.images.delete(&key).await.unwrap();
+            }
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_get_image_missing_upstream() {
+        with_app_state(async move |state| {
+            let id = {
+                let client = state.db.get().await.expect("db");
+                let new = ygo::NewCard {
+                    data: ygo::CardData {
+                        name: "Dark Magician".to_string(),
+                        ygoprodeck_id: Some(46986414),
+                        ..Default::default()
+                    },
+                };
+                service::card::save_new(&client, &new)
+                    .await
+                    .expect("card")
+                    .id
+            };
+
+            // Remember that ygoprodeck has no image for the card
+            let original_key =
+                get_card_image_key(id, 0, &CardImageSize::Small, None, ImageFormat::Jpeg);
+            let now = chrono::Utc::now().timestamp().to_string();
+            state
+                .images
+                .storage()
+                .write(&image_cache::get_missing_key(&original_key), now.as_bytes())
+                .await
+                .unwrap();
+
+            let router = Router::new()
+                .route("/ygo/cards/{id}/image", get(get_image_by_id))
+                .with_state(state.as_ref().clone());
+
+            // Neither the original nor its variants are retrieved from ygoprodeck
+            for (uri, accept) in [
+                (format!("/ygo/cards/{id}/image"), "image/jpeg"),
+                (format!("/ygo/cards/{id}/image?width=100"), "image/webp"),
+            ] {
+                let request = Request::builder()
+                    .uri(uri)
+                    .header("accept", accept)
+                    .body(Body::empty())
+                    .unwrap();
+                let response = router.clone().oneshot(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            }
+
+            let variant_key =
+                get_card_image_key(id, 0, &CardImageSize::Small, Some(128), ImageFormat::Webp);
+            for key in [original_key, variant_key] {
+                state.images.delete(&key).await.unwrap();
+            }
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn test_get_by_id_not_found() {
         with_app_state(async move |state| {
@@ -371,6 +1360,15 @@ mod tests {
                 .route("/ygo/cards/{id}", delete(delete_by_id).get(get_by_id))
                 .with_state(state.as_ref().clone());
 
+            // Cached images of the card
+            let keys = [
+                get_card_image_key(1, 0, &CardImageSize::Small, None, ImageFormat::Jpeg),
+                get_card_image_key(1, 0, &CardImageSize::Full, Some(20), ImageFormat::Webp),
+            ];
+            for key in &keys {
+                state.images.save(key, b"image".to_vec()).await.unwrap();
+            }
+
             // Delete
             let del = Request::builder()
                 .method("DELETE")
@@ -380,6 +1378,13 @@ mod tests {
             let resp = router.clone().oneshot(del).await.unwrap();
             assert_eq!(resp.status(), StatusCode::NO_CONTENT);
 
+            // Its images are deleted along with it
+            for key in &keys {
+                assert!(!state.images.storage().exists(key).await.unwrap());
+                let hash_key = image_cache::get_hash_key(key);
+                assert!(!state.images.storage().exists(&hash_key).await.unwrap());
+            }
+
             // Verify gone
             let get = Request::builder()
                 .uri("/ygo/cards/1")
@@ -518,4 +1523,170 @@ mod tests {
         })
         .await
     }
+
+    /// Utility to encode an image as PNG, with noise so that it doesn't compress
+    fn png(width: u32, height: u32, noise: bool) -> Vec<u8> {
+        let mut state: u32 = 2463534242;
+        let image = image::RgbImage::from_fn(width, height, |_, _| {
+            if !noise {
+                return image::Rgb([64, 32, 128]);
+            }
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let [r, g, b, _] = state.to_le_bytes();
+            image::Rgb([r, g, b])
+        });
+
+        let mut data = std::io::Cursor::new(Vec::new());
+        image
+            .write_to(&mut data, image::ImageFormat::Png)
+            .expect("png");
+        data.into_inner()
+    }
+
+    fn upload_request(id: i32, image: &[u8]) -> Request<Body> {
+        let mut body = b"--BOUNDARY\r\nContent-Disposition: form-data; name=\"image\"; filename=\"scan.png\"\r\nContent-Type: image/png\r\n\r\n".to_vec();
+        body.extend_from_slice(image);
+        body.extend_from_slice(b"\r\n--BOUNDARY--\r\n");
+
+        Request::builder()
+            .method("PUT")
+            .uri(format!("/api/v1/ygo/cards/{id}/image"))
+            .header("content-type", "multipart/form-data; boundary=BOUNDARY")
+            .header("content-length", body.len())
+            // Uploads are rate limited by IP address
+            .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 8000))))
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upload_image() {
+        with_app_state(async move |state| {
+            let id = {
+                let client = state.db.get().await.expect("db");
+                let new = ygo::NewCard {
+                    data: ygo::CardData {
+                        name: "Custom Dragon".to_string(),
+                        ..Default::default()
+                    },
+                };
+                service::card::save_new(&client, &new)
+                    .await
+                    .expect("card")
+                    .id
+            };
+
+            // Through the app, to go through its request size limits
+            let router = crate::app(state.as_ref().clone());
+            let get_image = |query: &str, accept: &str| {
+                Request::builder()
+                    .uri(format!("/api/v1/ygo/cards/{id}/image{query}"))
+                    .header("accept", accept)
+                    .body(Body::empty())
+                    .unwrap()
+            };
+
+            // Custom cards have no image until one is uploaded
+            let response = router
+                .clone()
+                .oneshot(get_image("", "image/jpeg"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            // Uploads must be images, of reasonable dimensions, for existing cards
+            for (id, image, status) in [
+                (
+                    id,
+                    b"not an image".to_vec(),
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                ),
+                (id, png(32, 32, false), StatusCode::UNPROCESSABLE_ENTITY),
+                (i32::MAX, png(100, 145, false), StatusCode::NOT_FOUND),
+            ] {
+                let response = router
+                    .clone()
+                    .oneshot(upload_request(id, &image))
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), status);
+            }
+
+            // Images over the 1 MB limit of other requests can be uploaded
+            let image = png(700, 700, true);
+            assert!(image.len() > 1024 * 1024);
+            let response = router
+                .clone()
+                .oneshot(upload_request(id, &image))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+            let etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+            let response = router
+                .clone()
+                .oneshot(get_image("", "image/jpeg"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["content-type"], "image/png");
+            assert_eq!(response.headers()["etag"], etag.as_str());
+            assert_eq!(response.headers()["cache-control"], "public, no-cache");
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, image);
+
+            let response = router
+                .clone()
+                .oneshot(get_image("?width=100", "image/webp"))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let decoded = webp::Decoder::new(&body).decode().expect("webp");
+            assert_eq!((decoded.width(), decoded.height()), (128, 128));
+
+            // Uploading another image replaces its cached variants
+            let response = router
+                .clone()
+                .oneshot(upload_request(id, &png(100, 145, false)))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+            let response = router
+                .clone()
+                .oneshot(get_image("?width=100", "image/webp"))
+                .await
+                .unwrap();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let decoded = webp::Decoder::new(&body).decode().expect("webp");
+            assert_eq!((decoded.width(), decoded.height()), (100, 145));
+
+            // Uploads have their own size limit
+            let response = router
+                .clone()
+                .oneshot(upload_request(id, &vec![0; MAX_UPLOAD_REQUEST_SIZE]))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+            // Other requests keep the size limit of the app
+            let request = Request::builder()
+                .method("PUT")
+                .uri(format!("/api/v1/ygo/cards/{id}"))
+                .header("content-type", "application/json")
+                .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 8000))))
+                .body(Body::from(vec![b' '; 2 * 1024 * 1024]))
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+            service::image::delete_card_images(&state.images, id)
+                .await
+                .unwrap();
+        })
+        .await;
+    }
 }
@@ -0,0 +1,825 @@
+use axum::{
+    Json,
+    extract::{Multipart, State},
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use serde::de::{IntoDeserializer, value::Error as ValueError};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Client;
+
+use super::card::{
+    IMMUTABLE_CACHE_CONTROL, Pagination, check_uploaded_image, etag_matches, read_uploaded_image,
+};
+use crate::api::utils::{decode_pagination_cursor, encode_pagination_cursor};
+use crate::api::{ApiError, ApiResult, Path, Query};
+use crate::models::ygo;
+use crate::prelude::AppState;
+use crate::services::image as service_image;
+use crate::services::ygo as service;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Page {
+    pub items: Vec<ygo::CollectionItem>,
+    pub next: Option<String>,
+}
+
+/// Lists owned card copies (paginated)
+pub async fn get_items(
+    State(state): State<AppState>,
+    Query(pagination): Query<Pagination>,
+    Query(filter): Query<service::collection::Filter>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let limit = pagination.limit.unwrap_or(100).min(100);
+    let cursor = pagination
+        .cursor
+        .as_ref()
+        .map(|c| decode_pagination_cursor(c))
+        .transpose()?;
+
+    let (items, next_cursor) =
+        service::collection::get_page(&client, Some(filter), limit, cursor).await?;
+
+    let as_page = Page {
+        items,
+        next: next_cursor
+            .as_ref()
+            .map(encode_pagination_cursor)
+            .transpose()?,
+    };
+
+    Ok(Json(as_page).into_response())
+}
+
+/// Get collection item by ID
+pub async fn get_by_id(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let item = service::collection::get_by_id(&client, id)
+        .await?
+        .ok_or(ApiError::NotFound {
+            resource: id.into(),
+        })?;
+
+    Ok(Json(item).into_response())
+}
+
+/// Add a card copy to the collection
+pub async fn create(
+    State(state): State<AppState>,
+    Json(new_item): Json<ygo::NewCollectionItem>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    validate(&client, &new_item.data).await?;
+    let created = service::collection::save_new(&client, &new_item).await?;
+
+    Ok((StatusCode::CREATED, Json(created)).into_response())
+}
+
+/// Update a collection item by ID
+pub async fn update(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(data): Json<ygo::CollectionItemData>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    validate(&client, &data).await?;
+
+    // The value assigned to updated_at here is meaningless and will be ignored by the database.
+    let to_update = ygo::CollectionItem {
+        id,
+        updated_at: chrono::Utc::now(),
+        data,
+    };
+
+    let updated = service::collection::save(&client, &to_update)
+        .await?
+        .ok_or(ApiError::NotFound {
+            resource: id.into(),
+        })?;
+
+    Ok(Json(updated).into_response())
+}
+
+/// Remove a collection item by ID
+pub async fn delete_by_id(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let deleted = service::collection::delete_by_id(&client, id).await?;
+    if deleted {
+        // Scans left behind are deleted by the next orphan sweep
+        if let Err(err) = service::scan::delete_item_scan_images(&state.images, id).await {
+            tracing::warn!("Could not delete scans of collection item ID {id}: {err}");
+        }
+
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound {
+            resource: id.into(),
+        })
+    }
+}
+
+/// Values the collection by price source, with a breakdown by set, rarity and kind
+pub async fn get_value(
+    State(state): State<AppState>,
+    Query(filter): Query<service::collection::ValueFilter>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let value = service::collection::get_value(&client, filter).await?;
+
+    Ok(Json(value).into_response())
+}
+
+/// Lists the scans of a collection item
+pub async fn get_scans(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    service::collection::get_by_id(&client, id)
+        .await?
+        .ok_or(ApiError::NotFound {
+            resource: id.into(),
+        })?;
+    let scans = service::scan::get_by_item_id(&client, id).await?;
+
+    Ok(Json(scans).into_response())
+}
+
+/// Upload a photo of a collection item, in the `image` field of a multipart form. The `side`
+/// field tells what the photo shows (`front`, `back` or `slab`), and the optional `captureDate`
+/// field when it was taken.
+pub async fn upload_scan(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    mut multipart: Multipart,
+) -> ApiResult<impl IntoResponse> {
+    {
+        let client = state.db.get().await?;
+        service::collection::get_by_id(&client, id)
+            .await?
+            .ok_or(ApiError::NotFound {
+                resource: id.into(),
+            })?;
+    }
+
+    let mut image = None;
+    let mut side = None;
+    let mut capture_date = None;
+    while let Some(field) = multipart.next_field().await? {
+        match field.name() {
+            Some("image") => image = Some(read_uploaded_image(field).await?),
+            Some("side") => {
+                let text = field.text().await?;
+                let parsed = ygo::ScanSide::deserialize(text.as_str().into_deserializer())
+                    .map_err(|_: ValueError| {
+                        ApiError::Validation(format!("unknown scan side '{text}'"))
+                    })?;
+                side = Some(parsed);
+            }
+            Some("captureDate") => {
+                let text = field.text().await?;
+                if !text.trim().is_empty() {
+                    let parsed = text.trim().parse::<NaiveDate>().map_err(|_| {
+                        ApiError::Validation(format!("captureDate '{text}' is not a valid date"))
+                    })?;
+                    capture_date = Some(parsed);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let image = image.ok_or(ApiError::Validation("image is missing".to_string()))?;
+    let side = side.ok_or(ApiError::Validation("side is missing".to_string()))?;
+    let (image, (width, height)) = check_uploaded_image(image).await?;
+
+    let new_scan = ygo::NewCollectionScan {
+        data: ygo::CollectionScanData {
+            item_id: id,
+            side,
+            capture_date,
+            width: width as i32,
+            height: height as i32,
+            size: image.len() as i32,
+        },
+    };
+    let client = state.db.get().await?;
+    let created = service::scan::save_new(&client, &state.images, &new_scan, image).await?;
+
+    Ok((StatusCode::CREATED, Json(created)).into_response())
+}
+
+/// Get the image of a collection item's scan
+pub async fn get_scan_image(
+    State(state): State<AppState>,
+    Path((id, scan_id)): Path<(i32, i32)>,
+    headers: HeaderMap,
+) -> ApiResult<impl IntoResponse> {
+    let not_found = || ApiError::NotFound {
+        resource: serde_json::json!({ "item": id, "scan": scan_id }),
+    };
+
+    {
+        let client = state.db.get().await?;
+        service::scan::get_by_id(&client, id, scan_id)
+            .await?
+            .ok_or_else(not_found)?;
+    }
+    let image = state
+        .images
+        .read(&service::scan::get_scan_key(id, scan_id))
+        .await?
+        .ok_or_else(not_found)?;
+
+    // Scans are never replaced, a new upload gets a new ID
+    let etag = format!("\"{}\"", image.hash);
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, IMMUTABLE_CACHE_CONTROL.to_string()),
+    ];
+
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH)
+        && etag_matches(if_none_match.to_str().unwrap_or_default(), &etag)
+    {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+
+    let content_type = service_image::content_type(&image.data);
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, content_type)],
+        cache_headers,
+        image.data,
+    )
+        .into_response())
+}
+
+/// Delete a scan of a collection item, along with its image
+pub async fn delete_scan(
+    State(state): State<AppState>,
+    Path((id, scan_id)): Path<(i32, i32)>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let deleted = service::scan::delete_by_id(&client, &state.images, id, scan_id).await?;
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound {
+            resource: serde_json::json!({ "item": id, "scan": scan_id }),
+        })
+    }
+}
+
+/// Checks a collection item before it's saved
+async fn validate(client: &Client, data: &ygo::CollectionItemData) -> ApiResult<()> {
+    if data.quantity <= 0 {
+        return Err(ApiError::Validation(
+            "quantity must be a positive number".to_string(),
+        ));
+    }
+
+    if data
+        .acquisition_price
+        .is_some_and(|price| price.is_sign_negative())
+    {
+        return Err(ApiError::Validation(
+            "acquisitionPrice cannot be negative".to_string(),
+        ));
+    }
+
+    if service::card::get_by_id(client, data.card_id)
+        .await?
+        .is_none()
+    {
+        return Err(ApiError::Validation(format!(
+            "card {} does not exist",
+            data.card_id
+        )));
+    }
+
+    if let Some(print_id) = data.print_id {
+        let print = service::set::get_print_by_id(client, print_id).await?;
+        if print.is_none_or(|print| print.card_id != data.card_id) {
+            return Err(ApiError::Validation(format!(
+                "print {print_id} is not a printing of card {}",
+                data.card_id
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::services::image_cache::ImageCache;
+    use crate::services::image_storage::FilesystemStorage;
+    use crate::test_utils::*;
+
+    use super::*;
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode},
+        routing::{delete, get},
+    };
+
+    fn router(state: &AppState) -> Router {
+        Router::new()
+            .route("/ygo/collection", get(get_items).post(create))
+            .route("/ygo/collection/value", get(get_value))
+            .route(
+                "/ygo/collection/{id}",
+                get(get_by_id).put(update).delete(delete_by_id),
+            )
+            .route(
+                "/ygo/collection/{id}/scans",
+                get(get_scans).post(upload_scan),
+            )
+            .route("/ygo/collection/{id}/scans/{scan_id}", delete(delete_scan))
+            .route(
+                "/ygo/collection/{id}/scans/{scan_id}/image",
+                get(get_scan_image),
+            )
+            .with_state(state.clone())
+    }
+
+    fn json_request(method: &str, uri: &str, body: &impl Serialize) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_vec(body).unwrap()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_create_and_get_item() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 1).await.expect("seed");
+            }
+            let router = router(&state);
+
+            let new = serde_json::json!({
+                "cardId": 1,
+                "quantity": 2,
+                "condition": "lightly_played",
+                "language": "german",
+                "firstEdition": true,
+                "acquisitionDate": "2024-05-01",
+                "acquisitionPrice": "4.99",
+            });
+            let response = router
+                .clone()
+                .oneshot(json_request("POST", "/ygo/collection", &new))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let created: ygo::CollectionItem = serde_json::from_slice(&body).expect("json");
+            assert_eq!(created.data.card_id, 1);
+            assert_eq!(created.data.quantity, 2);
+            assert_eq!(created.data.condition, ygo::CardCondition::LightlyPlayed);
+            assert_eq!(created.data.language, ygo::CardLanguage::German);
+            assert_eq!(
+                created.data.acquisition_price.map(|p| p.to_string()),
+                Some("4.99".to_string())
+            );
+
+            let request = Request::builder()
+                .uri(format!("/ygo/collection/{}", created.id))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let fetched: ygo::CollectionItem = serde_json::from_slice(&body).expect("json");
+            assert_eq!(fetched, created);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_create_item_with_invalid_quantity() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 1).await.expect("seed");
+            }
+
+            let new = serde_json::json!({ "cardId": 1, "quantity": 0 });
+            let response = router(&state)
+                .oneshot(json_request("POST", "/ygo/collection", &new))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                r#"{"error":"validation_error","message":"quantity must be a positive number"}"#
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_create_item_with_unknown_card() {
+        with_app_state(async move |state| {
+            let new = serde_json::json!({ "cardId": 144, "quantity": 1 });
+            let response = router(&state)
+                .oneshot(json_request("POST", "/ygo/collection", &new))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                r#"{"error":"validation_error","message":"card 144 does not exist"}"#
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_list_items() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 2).await.expect("seed");
+                for card_id in [1, 2, 2] {
+                    let new = ygo::NewCollectionItem {
+                        data: ygo::CollectionItemData {
+                            card_id,
+                            quantity: 1,
+                            ..Default::default()
+                        },
+                    };
+                    service::collection::save_new(&client, &new)
+                        .await
+                        .expect("insert");
+                }
+            }
+
+            let request = Request::builder()
+                .uri("/ygo/collection?cardId=2")
+                .body(Body::empty())
+                .unwrap();
+            let response = router(&state).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let page: Page = serde_json::from_slice(&body).expect("json");
+            assert_eq!(page.items.len(), 2);
+            assert!(page.items.iter().all(|item| item.data.card_id == 2));
+            assert!(page.next.is_none());
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_update_and_delete_item() {
+        with_app_state(async move |state| {
+            let created = {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 1).await.expect("seed");
+                let new = ygo::NewCollectionItem {
+                    data: ygo::CollectionItemData {
+                        card_id: 1,
+                        quantity: 1,
+                        ..Default::default()
+                    },
+                };
+                service::collection::save_new(&client, &new)
+                    .await
+                    .expect("insert")
+            };
+            let router = router(&state);
+            let uri = format!("/ygo/collection/{}", created.id);
+
+            let mut data = created.data.clone();
+            data.quantity = 4;
+            let response = router
+                .clone()
+                .oneshot(json_request("PUT", &uri, &data))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let updated: ygo::CollectionItem = serde_json::from_slice(&body).expect("json");
+            assert_eq!(updated.data.quantity, 4);
+
+            let request = Request::builder()
+                .method("DELETE")
+                .uri(&uri)
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+            let request = Request::builder()
+                .method("DELETE")
+                .uri(&uri)
+                .body(Body::empty())
+                .unwrap();
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_create_item_with_print_of_another_card() {
+        with_app_state(async move |state| {
+            let print_id = {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 2).await.expect("seed");
+                let set = service::set::upsert(
+                    &client,
+                    &ygo::CardSetData {
+                        name: "Legend of Blue Eyes".to_string(),
+                        code: "LOB".to_string(),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .expect("set");
+                let print = ygo::NewCardPrint {
+                    card_id: 2,
+                    set_id: set.id,
+                    code: "LOB-005".to_string(),
+                    rarity: "Secret Rare".to_string(),
+                    rarity_code: None,
+                };
+                service::set::upsert_prints(&client, &[print])
+                    .await
+                    .expect("print");
+                service::set::get_prints_by_card_id(&client, 2)
+                    .await
+                    .expect("prints")[0]
+                    .id
+            };
+            let router = router(&state);
+
+            let new = serde_json::json!({ "cardId": 1, "printId": print_id, "quantity": 1 });
+            let response = router
+                .clone()
+                .oneshot(json_request("POST", "/ygo/collection", &new))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                format!(
+                    r#"{{"error":"validation_error","message":"print {print_id} is not a printing of card 1"}}"#
+                )
+            );
+
+            let new = serde_json::json!({ "cardId": 2, "printId": print_id, "quantity": 1 });
+            let response = router
+                .oneshot(json_request("POST", "/ygo/collection", &new))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let created: ygo::CollectionItem = serde_json::from_slice(&body).expect("json");
+            assert_eq!(created.data.print_id, Some(print_id));
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_get_value() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 1).await.expect("seed");
+                let new = ygo::NewCollectionItem {
+                    data: ygo::CollectionItemData {
+                        card_id: 1,
+                        quantity: 3,
+                        ..Default::default()
+                    },
+                };
+                service::collection::save_new(&client, &new)
+                    .await
+                    .expect("insert");
+                let date = chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+                let prices = [(1, ygo::PriceSource::Cardmarket, "1.25".parse().unwrap())];
+                service::price::save_snapshots(&client, date, &prices)
+                    .await
+                    .expect("snapshot");
+            }
+
+            let request = Request::builder()
+                .uri("/ygo/collection/value?date=2025-01-31&compareTo=2024-12-31&source=cardmarket")
+                .body(Body::empty())
+                .unwrap();
+            let response = router(&state).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let value: serde_json::Value = serde_json::from_slice(&body).expect("json");
+            assert_eq!(
+                value,
+                serde_json::json!({
+                    "date": "2025-01-31",
+                    "previousDate": "2024-12-31",
+                    "sources": [{
+                        "source": "cardmarket",
+                        "total": "3.75",
+                        "previousTotal": "0",
+                        "change": "3.75",
+                        "bySet": [{ "name": null, "total": "3.75", "previousTotal": "0", "change": "3.75" }],
+                        "byRarity": [{ "name": null, "total": "3.75", "previousTotal": "0", "change": "3.75" }],
+                        "byKind": [{ "name": "monster", "total": "3.75", "previousTotal": "0", "change": "3.75" }],
+                    }],
+                })
+            );
+        })
+        .await
+    }
+
+    fn scan_request(id: i32, fields: &[(&str, &[u8])]) -> Request<Body> {
+        let mut body = Vec::new();
+        for (name, value) in fields {
+            body.extend_from_slice(
+                format!("--BOUNDARY\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n")
+                    .as_bytes(),
+            );
+            body.extend_from_slice(value);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"--BOUNDARY--\r\n");
+
+        Request::builder()
+            .method("POST")
+            .uri(format!("/ygo/collection/{id}/scans"))
+            .header("content-type", "multipart/form-data; boundary=BOUNDARY")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upload_list_and_delete_scans() {
+        with_app_state(async move |state| {
+            // Keep the scans out of the shared content directory
+            let dir = test_dir("api-scans");
+            let state = AppState {
+                images: ImageCache::new(Arc::new(FilesystemStorage::new(dir.path())), None),
+                ..state.as_ref().clone()
+            };
+            let item = {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 1).await.expect("seed");
+                let new = ygo::NewCollectionItem {
+                    data: ygo::CollectionItemData {
+                        card_id: 1,
+                        quantity: 1,
+                        ..Default::default()
+                    },
+                };
+                service::collection::save_new(&client, &new)
+                    .await
+                    .expect("insert")
+            };
+            let router = router(&state);
+
+            let mut png = std::io::Cursor::new(Vec::new());
+            image::RgbImage::from_pixel(64, 96, image::Rgb([64, 32, 128]))
+                .write_to(&mut png, image::ImageFormat::Png)
+                .expect("png");
+            let png = png.into_inner();
+
+            let request = scan_request(
+                item.id,
+                &[
+                    ("side", b"front"),
+                    ("captureDate", b"2026-10-01"),
+                    ("image", &png),
+                ],
+            );
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let scan: ygo::CollectionScan = serde_json::from_slice(&body).expect("json");
+            assert_eq!(
+                scan.data,
+                ygo::CollectionScanData {
+                    item_id: item.id,
+                    side: ygo::ScanSide::Front,
+                    capture_date: chrono::NaiveDate::from_ymd_opt(2026, 10, 1),
+                    width: 64,
+                    height: 96,
+                    size: png.len() as i32,
+                }
+            );
+
+            // Invalid sides, dates and images are rejected
+            let invalid: [&[(&str, &[u8])]; 4] = [
+                &[("side", b"edge"), ("image", &png)],
+                &[
+                    ("side", b"back"),
+                    ("captureDate", b"yesterday"),
+                    ("image", &png),
+                ],
+                &[("side", b"back"), ("image", b"not an image")],
+                &[("image", &png)],
+            ];
+            for fields in invalid {
+                let response = router
+                    .clone()
+                    .oneshot(scan_request(item.id, fields))
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+            }
+
+            let response = router
+                .clone()
+                .oneshot(scan_request(
+                    item.id + 1,
+                    &[("side", b"back"), ("image", &png)],
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let request = Request::builder()
+                .uri(format!("/ygo/collection/{}/scans", item.id))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let scans: Vec<ygo::CollectionScan> = serde_json::from_slice(&body).expect("json");
+            assert_eq!(scans, vec![scan.clone()]);
+
+            let uri = format!("/ygo/collection/{}/scans/{}", item.id, scan.id);
+            let request = Request::builder()
+                .uri(format!("{uri}/image"))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+            let etag = response.headers()[header::ETAG].clone();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, png);
+
+            let request = Request::builder()
+                .uri(format!("{uri}/image"))
+                .header(header::IF_NONE_MATCH, etag)
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+            for status in [StatusCode::NO_CONTENT, StatusCode::NOT_FOUND] {
+                let request = Request::builder()
+                    .method("DELETE")
+                    .uri(&uri)
+                    .body(Body::empty())
+                    .unwrap();
+                let response = router.clone().oneshot(request).await.unwrap();
+                assert_eq!(response.status(), status);
+            }
+
+            let request = Request::builder()
+                .uri(format!("{uri}/image"))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            assert!(state.images.storage().list("").await.unwrap().is_empty());
+        })
+        .await
+    }
+}
@@ -0,0 +1,803 @@
+use std::collections::HashMap;
+
+use axum::{
+    Json,
+    extract::{Multipart, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Client;
+
+use crate::api::{ApiError, ApiResult, Path, Query};
+use crate::models::ygo;
+use crate::prelude::AppState;
+use crate::services::ygo as service;
+
+/// Result of a deck import (YDK file or ydke URL)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeckImport {
+    #[serde(flatten)]
+    pub resolved: service::ydk::ResolvedYdk,
+    pub violations: Vec<service::deck::DeckViolation>,
+}
+
+/// A ydke URL, with the name to give the deck when importing it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct YdkeUrl {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// A deck's cards resolved by zone
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeckPreview {
+    pub main: Vec<ygo::Card>,
+    pub extra: Vec<ygo::Card>,
+    pub side: Vec<ygo::Card>,
+    pub unknown_passwords: Vec<String>,
+}
+
+/// Banlist format (and date) to check a deck against
+#[derive(Debug, Deserialize)]
+pub struct FormatQuery {
+    pub format: Option<ygo::BanlistFormat>,
+    pub date: Option<chrono::NaiveDate>,
+}
+
+/// Lists decks
+pub async fn get_decks(State(state): State<AppState>) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let decks = service::deck::get_all(&client).await?;
+
+    Ok(Json(decks).into_response())
+}
+
+/// Get deck by ID
+pub async fn get_by_id(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let deck = service::deck::get_by_id(&client, id)
+        .await?
+        .ok_or(ApiError::NotFound {
+            resource: id.into(),
+        })?;
+
+    Ok(Json(deck).into_response())
+}
+
+/// Create a new deck
+pub async fn create(
+    State(state): State<AppState>,
+    Json(new_deck): Json<ygo::NewDeck>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    check(&client, &new_deck.data, None).await?;
+    let created = service::deck::save_new(&client, &new_deck).await?;
+
+    Ok((StatusCode::CREATED, Json(created)).into_response())
+}
+
+/// Update a deck by ID
+pub async fn update(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(data): Json<ygo::DeckData>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    check(&client, &data, None).await?;
+
+    // The value assigned to updated_at here is meaningless and will be ignored by the database.
+    let to_update = ygo::Deck {
+        id,
+        updated_at: chrono::Utc::now(),
+        data,
+    };
+
+    let updated = service::deck::save(&client, &to_update)
+        .await?
+        .ok_or(ApiError::NotFound {
+            resource: id.into(),
+        })?;
+
+    Ok(Json(updated).into_response())
+}
+
+/// Delete a deck by ID
+pub async fn delete_by_id(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let deleted = service::deck::delete_by_id(&client, id).await?;
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound {
+            resource: id.into(),
+        })
+    }
+}
+
+/// Checks a deck against the construction rules without saving it.
+/// When a format is given, the deck is also checked against its banlist in effect on the date (today by default).
+pub async fn validate(
+    State(state): State<AppState>,
+    Query(format): Query<FormatQuery>,
+    Json(data): Json<ygo::DeckData>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let banlist = match format.format {
+        Some(format_name) => {
+            let date = format
+                .date
+                .unwrap_or_else(|| chrono::Utc::now().date_naive());
+            let banlist = service::banlist::get_effective(&client, format_name, date)
+                .await?
+                .ok_or(ApiError::Validation(format!(
+                    "no banlist in effect on {date} for this format"
+                )))?;
+            Some(banlist)
+        }
+        None => None,
+    };
+
+    check(&client, &data, banlist.as_ref()).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Parses an uploaded YDK file (`file` field, with an optional `name` field) into a deck.
+/// The deck is not saved: it's returned along with unknown passwords and broken rules.
+pub async fn import_ydk(
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let mut content = None;
+    let mut name = None;
+    while let Some(field) = multipart.next_field().await? {
+        match field.name() {
+            Some("file") => {
+                // Default to the file name, without its extension
+                if name.is_none() {
+                    name = field
+                        .file_name()
+                        .map(|file_name| file_name.trim_end_matches(".ydk").to_string());
+                }
+                content = Some(field.text().await?);
+            }
+            Some("name") => name = Some(field.text().await?),
+            _ => {}
+        }
+    }
+
+    let content = content.ok_or(ApiError::Validation("file is missing".to_string()))?;
+    let ydk = service::ydk::parse(&content).map_err(ApiError::Validation)?;
+    let name = name
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| "Imported deck".to_string());
+
+    let resolved = service::ydk::resolve(&client, &ydk, name).await?;
+    let violations = service::deck::check(&client, &resolved.deck, None).await?;
+
+    Ok(Json(DeckImport {
+        resolved,
+        violations,
+    })
+    .into_response())
+}
+
+/// Download a deck as a YDK file
+pub async fn export_ydk(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let deck = service::deck::get_by_id(&client, id)
+        .await?
+        .ok_or(ApiError::NotFound {
+            resource: id.into(),
+        })?;
+
+    let ydk = service::ydk::export(&client, &deck.data).await?;
+
+    // Keep the file name safe to use in the header
+    let file_name: String = deck
+        .data
+        .name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .collect();
+    let disposition = format!("attachment; filename=\"{}.ydk\"", file_name.trim());
+
+    Ok((
+        StatusCode::OK,
+        [
+            ("content-type", "text/plain; charset=utf-8".to_string()),
+            ("content-disposition", disposition),
+        ],
+        service::ydk::write(&ydk),
+    )
+        .into_response())
+}
+
+/// Get a deck as a ydke URL
+pub async fn export_ydke(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let deck = service::deck::get_by_id(&client, id)
+        .await?
+        .ok_or(ApiError::NotFound {
+            resource: id.into(),
+        })?;
+
+    let ydk = service::ydk::export(&client, &deck.data).await?;
+
+    Ok(Json(YdkeUrl {
+        url: service::ydke::encode(&ydk),
+        name: Some(deck.data.name),
+    })
+    .into_response())
+}
+
+/// Converts a ydke URL into a deck.
+/// The deck is not saved: it's returned along with unknown passwords and broken rules.
+pub async fn import_ydke(
+    State(state): State<AppState>,
+    Json(ydke): Json<YdkeUrl>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let ydk = service::ydke::decode(&ydke.url).map_err(ApiError::Validation)?;
+    let name = ydke
+        .name
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| "Imported deck".to_string());
+
+    let resolved = service::ydk::resolve(&client, &ydk, name).await?;
+    let violations = service::deck::check(&client, &resolved.deck, None).await?;
+
+    Ok(Json(DeckImport {
+        resolved,
+        violations,
+    })
+    .into_response())
+}
+
+/// Previews the cards of a ydke URL
+pub async fn preview_ydke(
+    State(state): State<AppState>,
+    Query(ydke): Query<YdkeUrl>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let ydk = service::ydke::decode(&ydke.url).map_err(ApiError::Validation)?;
+    let resolved = service::ydk::resolve(&client, &ydk, String::new()).await?;
+
+    let card_ids: Vec<i32> = resolved.deck.card_ids().collect();
+    let cards: HashMap<i32, ygo::Card> = service::card::get_by_ids(&client, &card_ids)
+        .await?
+        .into_iter()
+        .map(|card| (card.id, card))
+        .collect();
+    let zone_cards = |zone: &ygo::DeckZone| -> Vec<ygo::Card> {
+        resolved
+            .deck
+            .zone(zone)
+            .iter()
+            .filter_map(|card_id| cards.get(card_id).cloned())
+            .collect()
+    };
+
+    Ok(Json(DeckPreview {
+        main: zone_cards(&ygo::DeckZone::Main),
+        extra: zone_cards(&ygo::DeckZone::Extra),
+        side: zone_cards(&ygo::DeckZone::Side),
+        unknown_passwords: resolved.unknown_passwords,
+    })
+    .into_response())
+}
+
+/// Checks a deck before it's saved
+async fn check(
+    client: &Client,
+    data: &ygo::DeckData,
+    banlist: Option<&ygo::Banlist>,
+) -> ApiResult<()> {
+    if data.name.trim().is_empty() {
+        return Err(ApiError::Validation("name cannot be empty".to_string()));
+    }
+
+    let violations = service::deck::check(client, data, banlist).await?;
+    if !violations.is_empty() {
+        return Err(ApiError::InvalidDeck { violations });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+
+    use super::*;
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode},
+        routing::{get, post},
+    };
+
+    fn router(state: &AppState) -> Router {
+        Router::new()
+            .route("/ygo/decks", get(get_decks).post(create))
+            .route("/ygo/decks/import", post(import_ydk))
+            .route("/ygo/decks/import/ydke", post(import_ydke))
+            .route("/ygo/decks/preview", get(preview_ydke))
+            .route("/ygo/decks/validate", post(validate))
+            .route(
+                "/ygo/decks/{id}",
+                get(get_by_id).put(update).delete(delete_by_id),
+            )
+            .route("/ygo/decks/{id}/ydk", get(export_ydk))
+            .route("/ygo/decks/{id}/ydke", get(export_ydke))
+            .with_state(state.clone())
+    }
+
+    fn json_request(method: &str, uri: &str, body: &impl Serialize) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_vec(body).unwrap()))
+            .unwrap()
+    }
+
+    /// A legal deck made of 3 copies of the seeded cards 1 to 14
+    fn make_deck() -> ygo::DeckData {
+        ygo::DeckData {
+            name: "Kaiba".to_string(),
+            main: (1..=14).flat_map(|id| [id; 3]).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_and_get_deck() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 14).await.expect("seed");
+            }
+            let router = router(&state);
+
+            let response = router
+                .clone()
+                .oneshot(json_request("POST", "/ygo/decks", &make_deck()))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let created: ygo::Deck = serde_json::from_slice(&body).expect("json");
+            assert_eq!(created.data, make_deck());
+
+            let request = Request::builder()
+                .uri(format!("/ygo/decks/{}", created.id))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let fetched: ygo::Deck = serde_json::from_slice(&body).expect("json");
+            assert_eq!(fetched, created);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_create_illegal_deck() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 14).await.expect("seed");
+            }
+
+            let mut deck = make_deck();
+            deck.main.push(1);
+            let response = router(&state)
+                .oneshot(json_request("POST", "/ygo/decks", &deck))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                r#"{"error":"invalid_deck","violations":[{"rule":"too_many_copies","cardId":1,"count":4,"max":3}]}"#
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_create_deck_without_name() {
+        with_app_state(async move |state| {
+            let deck = ygo::DeckData {
+                name: " ".to_string(),
+                ..make_deck()
+            };
+            let response = router(&state)
+                .oneshot(json_request("POST", "/ygo/decks", &deck))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                r#"{"error":"validation_error","message":"name cannot be empty"}"#
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_validate_deck() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 14).await.expect("seed");
+            }
+            let router = router(&state);
+
+            let response = router
+                .clone()
+                .oneshot(json_request("POST", "/ygo/decks/validate", &make_deck()))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+            let deck = ygo::DeckData {
+                main: vec![1, 2, 3],
+                ..make_deck()
+            };
+            let response = router
+                .oneshot(json_request("POST", "/ygo/decks/validate", &deck))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                r#"{"error":"invalid_deck","violations":[{"rule":"zone_size","zone":"main","count":3,"min":40,"max":60}]}"#
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_validate_deck_against_banlist() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 14).await.expect("seed");
+                let date = chrono::NaiveDate::from_ymd_opt(2005, 4, 1).unwrap();
+                let limits = HashMap::from([(1, 1)]);
+                service::banlist::save(&client, ygo::BanlistFormat::Goat, date, &limits)
+                    .await
+                    .expect("banlist");
+            }
+            let router = router(&state);
+
+            let response = router
+                .clone()
+                .oneshot(json_request(
+                    "POST",
+                    "/ygo/decks/validate?format=goat&date=2005-10-01",
+                    &make_deck(),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                r#"{"error":"invalid_deck","violations":[{"rule":"too_many_copies","cardId":1,"count":3,"max":1}]}"#
+            );
+
+            // No banlist was in effect yet
+            let response = router
+                .oneshot(json_request(
+                    "POST",
+                    "/ygo/decks/validate?format=goat&date=2004-10-01",
+                    &make_deck(),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                r#"{"error":"validation_error","message":"no banlist in effect on 2004-10-01 for this format"}"#
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_update_and_delete_deck() {
+        with_app_state(async move |state| {
+            let created = {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 15).await.expect("seed");
+                let new = ygo::NewDeck { data: make_deck() };
+                service::deck::save_new(&client, &new)
+                    .await
+                    .expect("insert")
+            };
+            let router = router(&state);
+            let uri = format!("/ygo/decks/{}", created.id);
+
+            let mut data = created.data.clone();
+            data.side = vec![15, 15];
+            let response = router
+                .clone()
+                .oneshot(json_request("PUT", &uri, &data))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let updated: ygo::Deck = serde_json::from_slice(&body).expect("json");
+            assert_eq!(updated.data.side, vec![15, 15]);
+
+            let request = Request::builder()
+                .method("DELETE")
+                .uri(&uri)
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+            let request = Request::builder().uri(&uri).body(Body::empty()).unwrap();
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        })
+        .await
+    }
+
+    fn multipart_request(uri: &str, fields: &[(&str, Option<&str>, &str)]) -> Request<Body> {
+        let mut body = String::new();
+        for (name, file_name, value) in fields {
+            body.push_str("--BOUNDARY\r\n");
+            body.push_str(&format!("Content-Disposition: form-data; name=\"{name}\""));
+            if let Some(file_name) = file_name {
+                body.push_str(&format!("; filename=\"{file_name}\""));
+            }
+            body.push_str(&format!("\r\n\r\n{value}\r\n"));
+        }
+        body.push_str("--BOUNDARY--\r\n");
+
+        Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "multipart/form-data; boundary=BOUNDARY")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn create_card(client: &Client, password: &str) -> i32 {
+        let new = ygo::NewCard {
+            data: ygo::CardData {
+                name: format!("Card {password}"),
+                password: Some(password.to_string()),
+                ..Default::default()
+            },
+        };
+        service::card::save_new(client, &new)
+            .await
+            .expect("card")
+            .id
+    }
+
+    #[tokio::test]
+    async fn test_import_ydk() {
+        with_app_state(async move |state| {
+            let card_id = {
+                let client = state.db.get().await.expect("db");
+                create_card(&client, "89631139").await
+            };
+
+            let ydk = "#created by Player\n#main\n89631139\n89631139\n00000144\n#extra\n!side\n";
+            let request = multipart_request("/ygo/decks/import", &[("file", Some("Kaiba.ydk"), ydk)]);
+            let response = router(&state).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let import: serde_json::Value = serde_json::from_slice(&body).expect("json");
+            assert_eq!(
+                import,
+                serde_json::json!({
+                    "deck": { "name": "Kaiba", "main": [card_id, card_id], "extra": [], "side": [] },
+                    "unknownPasswords": ["144"],
+                    "violations": [{ "rule": "zone_size", "zone": "main", "count": 2, "min": 40, "max": 60 }],
+                })
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_import_invalid_ydk() {
+        with_app_state(async move |state| {
+            let request = multipart_request(
+                "/ygo/decks/import",
+                &[
+                    ("name", None, "Kaiba"),
+                    ("file", None, "#main\nBlue-Eyes\n"),
+                ],
+            );
+            let response = router(&state).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                r#"{"error":"validation_error","message":"line 2: invalid password 'Blue-Eyes'"}"#
+            );
+
+            let request = multipart_request("/ygo/decks/import", &[("name", None, "Kaiba")]);
+            let response = router(&state).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_export_ydk() {
+        with_app_state(async move |state| {
+            let deck = {
+                let client = state.db.get().await.expect("db");
+                let card_id = create_card(&client, "89631139").await;
+                let new = ygo::NewDeck {
+                    data: ygo::DeckData {
+                        name: "Kaiba's \"Deck\"".to_string(),
+                        main: vec![card_id; 3],
+                        side: vec![card_id],
+                        ..Default::default()
+                    },
+                };
+                service::deck::save_new(&client, &new).await.expect("deck")
+            };
+
+            let request = Request::builder()
+                .uri(format!("/ygo/decks/{}/ydk", deck.id))
+                .body(Body::empty())
+                .unwrap();
+            let response = router(&state).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers()["content-disposition"],
+                r#"attachment; filename="Kaibas Deck.ydk""#
+            );
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                "#created by cardfolio\n#main\n89631139\n89631139\n89631139\n#extra\n!side\n89631139\n"
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_export_ydke_then_import() {
+        with_app_state(async move |state| {
+            let deck = {
+                let client = state.db.get().await.expect("db");
+                let card_id = create_card(&client, "89631139").await;
+                let new = ygo::NewDeck {
+                    data: ygo::DeckData {
+                        name: "Kaiba".to_string(),
+                        main: vec![card_id; 3],
+                        ..Default::default()
+                    },
+                };
+                service::deck::save_new(&client, &new).await.expect("deck")
+            };
+            let router = router(&state);
+
+            let request = Request::builder()
+                .uri(format!("/ygo/decks/{}/ydke", deck.id))
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let ydke: YdkeUrl = serde_json::from_slice(&body).expect("json");
+            assert_eq!(ydke.url, "ydke://o6lXBaOpVwWjqVcF!!!");
+
+            let response = router
+                .oneshot(json_request("POST", "/ygo/decks/import/ydke", &ydke))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let import: serde_json::Value = serde_json::from_slice(&body).expect("json");
+            assert_eq!(import["deck"]["name"], "Kaiba");
+            assert_eq!(import["deck"]["main"], serde_json::json!(deck.data.main));
+            assert_eq!(import["unknownPasswords"], serde_json::json!([]));
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_preview_ydke() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                create_card(&client, "89631139").await;
+            }
+
+            // Main: 89631139 twice, side: 144
+            let request = Request::builder()
+                .uri("/ygo/decks/preview?url=ydke%3A%2F%2Fo6lXBaOpVwU%3D!!kAAAAA%3D%3D!")
+                .body(Body::empty())
+                .unwrap();
+            let response = router(&state).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let preview: DeckPreview = serde_json::from_slice(&body).expect("json");
+            assert_eq!(preview.main.len(), 2);
+            assert_eq!(preview.main[0].data.password.as_deref(), Some("89631139"));
+            assert!(preview.extra.is_empty());
+            assert!(preview.side.is_empty());
+            assert_eq!(preview.unknown_passwords, vec!["144".to_string()]);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_preview_invalid_ydke() {
+        with_app_state(async move |state| {
+            let request = Request::builder()
+                .uri("/ygo/decks/preview?url=https%3A%2F%2Fexample.com")
+                .body(Body::empty())
+                .unwrap();
+            let response = router(&state).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                r#"{"error":"validation_error","message":"URL must start with ydke://"}"#
+            );
+        })
+        .await
+    }
+}
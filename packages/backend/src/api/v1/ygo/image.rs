@@ -0,0 +1,188 @@
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+
+use crate::api::{ApiError, ApiResult, Query};
+use crate::prelude::AppState;
+use crate::services::ygo as service;
+
+/// Starts downloading the images of all cards into the local cache, in the background
+pub async fn start_prefetch(
+    State(state): State<AppState>,
+    Query(options): Query<service::image::PrefetchOptions>,
+) -> ApiResult<impl IntoResponse> {
+    let started = state
+        .prefetch
+        .start(state.db.clone(), state.images.clone(), options);
+    if !started {
+        return Err(ApiError::Conflict(
+            "Image prefetch is already running".to_string(),
+        ));
+    }
+
+    Ok((StatusCode::ACCEPTED, Json(state.prefetch.progress())).into_response())
+}
+
+/// Get the progress of the image prefetch job
+pub async fn get_prefetch_progress(State(state): State<AppState>) -> ApiResult<impl IntoResponse> {
+    Ok(Json(state.prefetch.progress()).into_response())
+}
+
+/// Get statistics about the image cache
+pub async fn get_cache_stats(State(state): State<AppState>) -> ApiResult<impl IntoResponse> {
+    Ok(Json(state.images.stats().await?).into_response())
+}
+
+/// Deletes the cached images of cards that no longer exist
+pub async fn sweep_orphans(State(state): State<AppState>) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let result = service::image::sweep_orphans(&client, &state.images).await?;
+    Ok(Json(result).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::models::ygo;
+    use crate::services::image_cache::ImageCache;
+    use crate::services::image_storage::FilesystemStorage;
+    use crate::services::ygo::image::{PrefetchProgress, PrefetchStatus, SweepResult};
+    use crate::test_utils::*;
+
+    use super::*;
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode},
+        routing::{get, post},
+    };
+
+    fn router(state: &AppState) -> Router {
+        Router::new()
+            .route(
+                "/ygo/images/prefetch",
+                get(get_prefetch_progress).post(start_prefetch),
+            )
+            .route("/ygo/images/stats", get(get_cache_stats))
+            .route("/ygo/images/sweep", post(sweep_orphans))
+            .with_state(state.clone())
+    }
+
+    async fn get_progress(router: &Router) -> PrefetchProgress {
+        let request = Request::builder()
+            .uri("/ygo/images/prefetch")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).expect("Unable to parse response body")
+    }
+
+    #[tokio::test]
+    async fn test_prefetch() {
+        with_app_state(async move |state| {
+            let router = router(&state);
+
+            let progress = get_progress(&router).await;
+            assert_eq!(progress.status, PrefetchStatus::Idle);
+
+            // No card has images to prefetch
+            let request = Request::builder()
+                .method("POST")
+                .uri("/ygo/images/prefetch?sizes=small&concurrency=2")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let progress: PrefetchProgress =
+                serde_json::from_slice(&body).expect("Unable to parse response body");
+            assert_eq!(progress.status, PrefetchStatus::Running);
+            assert!(progress.started_at.is_some());
+
+            let mut progress = get_progress(&router).await;
+            while progress.status == PrefetchStatus::Running {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                progress = get_progress(&router).await;
+            }
+            assert_eq!(progress.status, PrefetchStatus::Finished);
+            assert_eq!(progress.total, 0);
+
+            // Unknown sizes are rejected
+            let request = Request::builder()
+                .method("POST")
+                .uri("/ygo/images/prefetch?sizes=huge")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_sweep_orphans_and_stats() {
+        with_app_state(async move |state| {
+            // Images of cards from other tests are in the shared content directory
+            let dir = test_dir("api-sweep");
+            let state = AppState {
+                images: ImageCache::new(Arc::new(FilesystemStorage::new(dir.path())), Some(1024)),
+                ..state.as_ref().clone()
+            };
+            let router = router(&state);
+
+            let id = {
+                let client = state.db.get().await.expect("db");
+                let new = ygo::NewCard {
+                    data: ygo::CardData {
+                        name: "Dark Magician".to_string(),
+                        ..Default::default()
+                    },
+                };
+                let id = service::card::save_new(&client, &new)
+                    .await
+                    .expect("card")
+                    .id;
+                service::card::delete_by_id(&client, id)
+                    .await
+                    .expect("delete");
+                id
+            };
+            state
+                .images
+                .save(&format!("card/{id}.jpg"), b"image".to_vec())
+                .await
+                .unwrap();
+
+            let request = Request::builder()
+                .method("POST")
+                .uri("/ygo/images/sweep")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let result: SweepResult =
+                serde_json::from_slice(&body).expect("Unable to parse response body");
+            assert_eq!((result.cards, result.images, result.freed), (1, 1, 69));
+
+            let request = Request::builder()
+                .uri("/ygo/images/stats")
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let stats: serde_json::Value =
+                serde_json::from_slice(&body).expect("Unable to parse response body");
+            assert_eq!(stats["images"], 0);
+            assert_eq!(stats["size"], 0);
+            assert_eq!(stats["maxSize"], 1024);
+        })
+        .await;
+    }
+}
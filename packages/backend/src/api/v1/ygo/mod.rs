@@ -1 +1,7 @@
+pub mod banlist;
 pub mod card;
+pub mod collection;
+pub mod deck;
+pub mod image;
+pub mod price;
+pub mod set;
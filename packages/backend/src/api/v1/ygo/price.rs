@@ -0,0 +1,91 @@
+use axum::{Json, extract::State, response::IntoResponse};
+
+use crate::api::{ApiError, ApiResult, Path, Query};
+use crate::prelude::AppState;
+use crate::services::ygo as service;
+
+/// Lists the price history of a card
+pub async fn get_card_prices(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Query(filter): Query<service::price::Filter>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    // Make sure the card exists
+    service::card::get_by_id(&client, id)
+        .await?
+        .ok_or(ApiError::NotFound {
+            resource: id.into(),
+        })?;
+
+    let prices = service::price::get_history(&client, id, Some(filter)).await?;
+
+    Ok(Json(prices).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::models::ygo;
+    use crate::test_utils::*;
+
+    use super::*;
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode},
+        routing::get,
+    };
+
+    fn router(state: &AppState) -> Router {
+        Router::new()
+            .route("/ygo/cards/{id}/prices", get(get_card_prices))
+            .with_state(state.clone())
+    }
+
+    #[tokio::test]
+    async fn test_get_card_prices() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 1).await.expect("seed");
+                for (day, price) in [(1, "1.50"), (2, "1.75"), (3, "2.00")] {
+                    let date = NaiveDate::from_ymd_opt(2025, 1, day).unwrap();
+                    let prices = [(1, ygo::PriceSource::Cardmarket, price.parse().unwrap())];
+                    service::price::save_snapshots(&client, date, &prices)
+                        .await
+                        .expect("snapshot");
+                }
+            }
+
+            let request = Request::builder()
+                .uri("/ygo/cards/1/prices?from=2025-01-02&to=2025-01-03")
+                .body(Body::empty())
+                .unwrap();
+            let response = router(&state).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(
+                body,
+                r#"[{"source":"cardmarket","date":"2025-01-02","price":"1.75"},{"source":"cardmarket","date":"2025-01-03","price":"2.00"}]"#
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_get_card_prices_not_found() {
+        with_app_state(async move |state| {
+            let request = Request::builder()
+                .uri("/ygo/cards/144/prices")
+                .body(Body::empty())
+                .unwrap();
+            let response = router(&state).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        })
+        .await
+    }
+}
@@ -0,0 +1,181 @@
+use axum::{Json, extract::State, response::IntoResponse};
+
+use crate::api::{ApiError, ApiResult, Path, Query};
+use crate::prelude::AppState;
+use crate::services::ygo as service;
+
+/// Lists card sets
+pub async fn get_sets(
+    State(state): State<AppState>,
+    Query(filter): Query<service::set::Filter>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let sets = service::set::get_all(&client, Some(filter)).await?;
+
+    Ok(Json(sets).into_response())
+}
+
+/// Get set by ID
+pub async fn get_set_by_id(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    let set = service::set::get_by_id(&client, id)
+        .await?
+        .ok_or(ApiError::NotFound {
+            resource: id.into(),
+        })?;
+
+    Ok(Json(set).into_response())
+}
+
+/// Lists the printings of a card
+pub async fn get_card_prints(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> ApiResult<impl IntoResponse> {
+    let client = state.db.get().await?;
+
+    // Make sure the card exists
+    service::card::get_by_id(&client, id)
+        .await?
+        .ok_or(ApiError::NotFound {
+            resource: id.into(),
+        })?;
+
+    let prints = service::set::get_prints_by_card_id(&client, id).await?;
+
+    Ok(Json(prints).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::ygo;
+    use crate::test_utils::*;
+
+    use super::*;
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode},
+        routing::get,
+    };
+
+    #[tokio::test]
+    async fn test_get_sets() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                for (name, code) in [("Legend of Blue Eyes", "LOB"), ("Metal Raiders", "MRD")] {
+                    let set = ygo::CardSetData {
+                        name: name.to_string(),
+                        code: code.to_string(),
+                        ..Default::default()
+                    };
+                    service::set::upsert(&client, &set).await.expect("set");
+                }
+            }
+
+            let router = Router::new()
+                .route("/ygo/sets", get(get_sets))
+                .with_state(state.as_ref().clone());
+            let request = Request::builder()
+                .uri("/ygo/sets?code=LOB")
+                .body(Body::empty())
+                .unwrap();
+
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let sets: Vec<ygo::CardSet> = serde_json::from_slice(&body).expect("json");
+            assert_eq!(sets.len(), 1);
+            assert_eq!(sets[0].data.name, "Legend of Blue Eyes");
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_get_set_by_id_not_found() {
+        with_app_state(async move |state| {
+            let router = Router::new()
+                .route("/ygo/sets/{id}", get(get_set_by_id))
+                .with_state(state.as_ref().clone());
+            let request = Request::builder()
+                .uri("/ygo/sets/144")
+                .body(Body::empty())
+                .unwrap();
+
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_get_card_prints() {
+        with_app_state(async move |state| {
+            {
+                let client = state.db.get().await.expect("db");
+                service::card::seed_cards(&client, 1).await.expect("seed");
+                let set = ygo::CardSetData {
+                    name: "Legend of Blue Eyes".to_string(),
+                    code: "LOB".to_string(),
+                    ..Default::default()
+                };
+                let set = service::set::upsert(&client, &set).await.expect("set");
+                let print = ygo::NewCardPrint {
+                    card_id: 1,
+                    set_id: set.id,
+                    code: "LOB-001".to_string(),
+                    rarity: "Ultra Rare".to_string(),
+                    rarity_code: None,
+                };
+                service::set::upsert_prints(&client, &[print])
+                    .await
+                    .expect("print");
+            }
+
+            let router = Router::new()
+                .route("/ygo/cards/{id}/prints", get(get_card_prints))
+                .with_state(state.as_ref().clone());
+            let request = Request::builder()
+                .uri("/ygo/cards/1/prints")
+                .body(Body::empty())
+                .unwrap();
+
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let prints: Vec<ygo::CardPrint> = serde_json::from_slice(&body).expect("json");
+            assert_eq!(prints.len(), 1);
+            assert_eq!(prints[0].code, "LOB-001");
+            assert_eq!(prints[0].set.data.code, "LOB");
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_get_card_prints_not_found() {
+        with_app_state(async move |state| {
+            let router = Router::new()
+                .route("/ygo/cards/{id}/prints", get(get_card_prints))
+                .with_state(state.as_ref().clone());
+            let request = Request::builder()
+                .uri("/ygo/cards/144/prints")
+                .body(Body::empty())
+                .unwrap();
+
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            assert_eq!(body, r#"{"error":"not_found","resource":144}"#);
+        })
+        .await
+    }
+}
@@ -1,15 +1,19 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
 use anyhow::Context;
-use serde::{Deserialize, Serialize};
+use futures_util::{Stream, StreamExt, stream};
+use rust_decimal::Decimal;
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use tokio::sync::mpsc;
 use tokio_postgres::Client;
 
-use crate::models::ygo::{Card, CardData};
+use crate::database::with_transaction;
+use crate::models::ygo::CardData;
+use crate::services::image_cache::ImageCache;
 use crate::{models::ygo, services::ygo as service};
 
-#[derive(Debug, Deserialize)]
-struct YgoProDeckList {
-    data: Vec<YgoProDeckCard>,
-}
-
 #[derive(Debug, Deserialize)]
 struct YgoProDeckCard {
     id: i32,
@@ -36,6 +40,10 @@ struct YgoProDeckCard {
     pendulum_desc: Option<String>,
     monster_desc: Option<String>, // Monster description for Pendulum cards
     misc_info: Option<(YgoProDeckMiscInfo,)>, // tcg/ocg dates, konami_id, etc
+    card_sets: Option<Vec<YgoProDeckCardSet>>,
+    banlist_info: Option<YgoProDeckBanlistInfo>,
+    card_prices: Option<Vec<YgoProDeckCardPrices>>,
+    card_images: Option<Vec<YgoProDeckCardImage>>, // Default artwork first, then alternate ones
 }
 
 #[derive(Debug, Deserialize)]
@@ -45,6 +53,100 @@ struct YgoProDeckMiscInfo {
     konami_id: Option<i32>,
 }
 
+#[derive(Debug, Deserialize)]
+struct YgoProDeckCardSet {
+    set_name: String,
+    set_code: String, // e.g. LOB-EN001
+    set_rarity: String,
+    set_rarity_code: Option<String>, // e.g. (UR)
+}
+
+#[derive(Debug, Deserialize)]
+struct YgoProDeckBanlistInfo {
+    ban_tcg: Option<String>, // e.g. Forbidden, Limited, Semi-Limited
+    ban_ocg: Option<String>,
+    ban_goat: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct YgoProDeckCardPrices {
+    tcgplayer_price: Option<String>, // e.g. "0.25"
+    cardmarket_price: Option<String>,
+    ebay_price: Option<String>,
+    amazon_price: Option<String>,
+    coolstuffinc_price: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct YgoProDeckCardImage {
+    id: i32, // Image ID, the card's own ID for its default artwork
+}
+
+#[derive(Debug, Deserialize)]
+struct YgoProDeckSet {
+    set_name: String,
+    set_code: String, // e.g. LOB
+    num_of_cards: Option<i32>,
+    tcg_date: Option<String>,
+}
+
+impl YgoProDeckCardSet {
+    /// The set code is the part of the print code before the dash (LOB-EN001 -> LOB)
+    fn get_set_code(&self) -> &str {
+        self.set_code
+            .split_once('-')
+            .map_or(self.set_code.as_str(), |(code, _)| code)
+    }
+
+    fn get_rarity_code(&self) -> Option<String> {
+        self.set_rarity_code
+            .as_ref()
+            .filter(|code| !code.is_empty())
+            .cloned()
+    }
+}
+
+impl YgoProDeckBanlistInfo {
+    /// Returns the number of copies allowed by each format that limits the card
+    fn get_limits(&self) -> Vec<(ygo::BanlistFormat, i16)> {
+        [
+            (ygo::BanlistFormat::Tcg, &self.ban_tcg),
+            (ygo::BanlistFormat::Ocg, &self.ban_ocg),
+            (ygo::BanlistFormat::Goat, &self.ban_goat),
+        ]
+        .into_iter()
+        .filter_map(|(format, status)| {
+            let limit = match status.as_ref()?.to_lowercase().as_str() {
+                "forbidden" | "banned" => 0,
+                "limited" => 1,
+                "semi-limited" => 2,
+                _ => return None,
+            };
+            Some((format, limit))
+        })
+        .collect()
+    }
+}
+
+impl YgoProDeckCardPrices {
+    /// Returns the known prices by source. Prices of 0 mean there's no offer, and are left out.
+    fn get_prices(&self) -> Vec<(ygo::PriceSource, Decimal)> {
+        [
+            (ygo::PriceSource::Tcgplayer, &self.tcgplayer_price),
+            (ygo::PriceSource::Cardmarket, &self.cardmarket_price),
+            (ygo::PriceSource::Ebay, &self.ebay_price),
+            (ygo::PriceSource::Amazon, &self.amazon_price),
+            (ygo::PriceSource::Coolstuffinc, &self.coolstuffinc_price),
+        ]
+        .into_iter()
+        .filter_map(|(source, price)| {
+            let price: Decimal = price.as_ref()?.parse().ok()?;
+            (price > Decimal::ZERO).then_some((source, price))
+        })
+        .collect()
+    }
+}
+
 impl YgoProDeckCard {
     fn get_monster_attribute(&self) -> Option<ygo::MonsterAttribute> {
         if !self.is_monster() {
@@ -360,73 +462,405 @@ impl TryFrom<YgoProDeckCard> for CardData {
     }
 }
 
-/// Parses a json list
-async fn parse_json_list(json: &str) -> anyhow::Result<impl Iterator<Item = YgoProDeckCard>> {
-    let parsed_list: YgoProDeckList =
-        serde_json::from_str(json).with_context(|| "Failed to parse ygoprodeck JSON deck file")?;
+/// Number of cards saved at once by imports
+const IMPORT_BATCH_SIZE: usize = 500;
+
+/// Number of response chunks received ahead of the parser
+const IMPORT_CHUNK_BUFFER: usize = 16;
+
+/// Parses a json list one card at a time, handing each card to `on_card` rather than collecting
+/// them. Parsing stops with an error when `on_card` returns false.
+fn parse_json_list(
+    reader: impl std::io::Read,
+    on_card: impl FnMut(YgoProDeckCard) -> bool,
+) -> anyhow::Result<()> {
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    deserializer
+        .deserialize_map(CardListVisitor(on_card))
+        .and_then(|_| deserializer.end())
+        .with_context(|| "Failed to parse ygoprodeck JSON deck file")
+}
+
+/// Visits the `data` list of a json list, see `parse_json_list`
+struct CardListVisitor<F>(F);
+
+impl<'de, F: FnMut(YgoProDeckCard) -> bool> Visitor<'de> for CardListVisitor<F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a ygoprodeck card list")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        let mut found = false;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "data" {
+                map.next_value_seed(CardSeqSeed(&mut self.0))?;
+                found = true;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+
+        match found {
+            true => Ok(()),
+            false => Err(de::Error::missing_field("data")),
+        }
+    }
+}
+
+/// Visits the cards of a json list, see `parse_json_list`
+struct CardSeqSeed<'a, F>(&'a mut F);
 
-    Ok(parsed_list.data.into_iter())
+impl<'de, F: FnMut(YgoProDeckCard) -> bool> DeserializeSeed<'de> for CardSeqSeed<'_, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
 }
 
-/// Utility to get the existing card from the database
-async fn get_existing_card(client: &Client, card_data: &CardData) -> anyhow::Result<Option<Card>> {
-    // Check by Konami ID
-    if let Some(konami_id) = card_data.konami_id {
-        return Ok(service::card::get_by_konami_id(client, konami_id).await?);
+impl<'de, F: FnMut(YgoProDeckCard) -> bool> Visitor<'de> for CardSeqSeed<'_, F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of ygoprodeck cards")
     }
 
-    if let Some(password) = &card_data.password {
-        return Ok(service::card::get_by_password(client, password).await?);
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(card) = seq.next_element::<YgoProDeckCard>()? {
+            if !(self.0)(card) {
+                return Err(de::Error::custom("import was interrupted"));
+            }
+        }
+
+        Ok(())
     }
+}
 
-    anyhow::bail!(
-        "Card '{}' has no Konami ID or password, cannot check for existing card",
-        card_data.name
-    );
+/// Reader over the chunks of a response, as they're received.
+/// Meant to be used from a blocking thread.
+struct ChunkReader {
+    chunks: mpsc::Receiver<anyhow::Result<Vec<u8>>>,
+    current: std::io::Cursor<Vec<u8>>,
 }
 
-/// Imports cards from a json string
-async fn import_from_json_str(client: &Client, json: &str) -> anyhow::Result<(usize, usize)> {
-    let mut inserted = 0;
-    let mut updated = 0;
-
-    for card in parse_json_list(json).await? {
-        let card_data: CardData = card.try_into()?;
-        let existing_card = match get_existing_card(client, &card_data).await {
-            Ok(card) => card,
-            Err(err) => {
-                tracing::warn!("{}. Skipping...", err);
+impl std::io::Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let read = self.current.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+
+            // An interrupted response looks like the end of the list, and fails to parse
+            match self.chunks.blocking_recv() {
+                Some(Ok(chunk)) => self.current = std::io::Cursor::new(chunk),
+                Some(Err(err)) => return Err(std::io::Error::other(err)),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+/// What an imported card is matched on against the existing cards
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum CardKey {
+    KonamiId(i32),
+    Password(String),
+}
+
+/// A card to import, with the data saved apart from it
+struct ImportedCard {
+    data: CardData,
+    card_sets: Vec<YgoProDeckCardSet>,
+    banlist_info: Option<YgoProDeckBanlistInfo>,
+    card_prices: Vec<YgoProDeckCardPrices>,
+    artwork_ids: Vec<i32>,
+}
+
+impl TryFrom<YgoProDeckCard> for ImportedCard {
+    type Error = anyhow::Error;
+
+    fn try_from(mut card: YgoProDeckCard) -> Result<Self, Self::Error> {
+        let card_sets = card.card_sets.take().unwrap_or_default();
+        let banlist_info = card.banlist_info.take();
+        let card_prices = card.card_prices.take().unwrap_or_default();
+        let mut artwork_ids: Vec<i32> = card
+            .card_images
+            .take()
+            .unwrap_or_default()
+            .iter()
+            .map(|image| image.id)
+            .collect();
+        if artwork_ids.is_empty() {
+            artwork_ids.push(card.id);
+        }
+
+        Ok(Self {
+            data: card.try_into()?,
+            card_sets,
+            banlist_info,
+            card_prices,
+            artwork_ids,
+        })
+    }
+}
+
+impl ImportedCard {
+    /// Utility to get what the card is matched on, if it can be
+    fn get_key(&self) -> Option<CardKey> {
+        if let Some(konami_id) = self.data.konami_id {
+            return Some(CardKey::KonamiId(konami_id));
+        }
+
+        self.data.password.clone().map(CardKey::Password)
+    }
+}
+
+/// State of a card import across batches
+#[derive(Default)]
+struct CardImport {
+    inserted: usize,
+    updated: usize,
+    set_ids: HashMap<String, i32>,
+    /// Cards whose artworks changed, so that their cached images are out of date
+    changed_artworks: HashSet<i32>,
+    banlists: HashMap<ygo::BanlistFormat, HashMap<i32, i16>>,
+}
+
+/// Imports cards from the chunks of a json list as they're received. Cards are parsed on a
+/// blocking thread and saved in batches, so that the whole list is never held in memory.
+/// Batches are saved in a single transaction, so that nothing is saved unless the whole list is.
+/// Banlists are saved once the cards are, in transactions of their own.
+async fn import_from_json_chunks(
+    client: &Client,
+    images: &ImageCache,
+    chunks: impl Stream<Item = anyhow::Result<Vec<u8>>>,
+) -> anyhow::Result<(usize, usize)> {
+    let (chunk_sender, chunk_receiver) = mpsc::channel(IMPORT_CHUNK_BUFFER);
+    let (card_sender, mut card_receiver) = mpsc::channel(IMPORT_BATCH_SIZE);
+
+    let parser = tokio::task::spawn_blocking(move || {
+        let reader = ChunkReader {
+            chunks: chunk_receiver,
+            current: Default::default(),
+        };
+        parse_json_list(reader, |card| card_sender.blocking_send(card).is_ok())
+    });
+
+    // Errors of the response are passed on to the parser, so that the transaction is rolled
+    // back rather than dropped half-way
+    let receive = async move {
+        let mut chunks = std::pin::pin!(chunks);
+        while let Some(chunk) = chunks.next().await {
+            let failed = chunk.is_err();
+            if chunk_sender.send(chunk).await.is_err() || failed {
+                break;
+            }
+        }
+    };
+
+    let today = chrono::Utc::now().date_naive();
+    let import = with_transaction(client, None, async move |client| {
+        // Printings of known sets are attached to them as they are
+        let sets = service::set::get_all(client, None).await?;
+        let mut import = CardImport {
+            set_ids: sets
+                .into_iter()
+                .map(|set| (set.data.name, set.id))
+                .collect(),
+            ..Default::default()
+        };
+
+        let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+        let mut keys = HashSet::new();
+        while let Some(card) = card_receiver.recv().await {
+            let card: ImportedCard = card.try_into()?;
+            let Some(key) = card.get_key() else {
+                tracing::warn!(
+                    "Card '{}' has no Konami ID or password, cannot check for existing card. Skipping...",
+                    card.data.name
+                );
                 continue;
+            };
+
+            // Cards of a batch are matched at once, so that copies of a card are saved in turn
+            if keys.contains(&key) || batch.len() == IMPORT_BATCH_SIZE {
+                import_batch(client, std::mem::take(&mut batch), &mut import, today).await?;
+                keys.clear();
             }
+            keys.insert(key);
+            batch.push(card);
+        }
+
+        // The import is rolled back if the list could not be parsed to the end
+        parser.await??;
+        import_batch(client, batch, &mut import, today).await?;
+
+        Ok::<_, anyhow::Error>(import)
+    });
+
+    let ((), import) = tokio::join!(receive, import);
+    let import = import?;
+
+    // Images are cached by artwork index, which may now point to another artwork
+    for card_id in import.changed_artworks {
+        if let Err(err) = service::image::delete_cached_card_images(images, card_id).await {
+            tracing::warn!("Could not delete cached images of card ID {card_id}: {err}");
+        }
+    }
+    import_banlists(client, import.banlists, today).await?;
+
+    Ok((import.inserted, import.updated))
+}
+
+/// Saves a batch of imported cards, along with their printings, artworks and prices.
+/// Their banlist statuses are kept to be saved at the end of the import.
+async fn import_batch(
+    client: &Client,
+    batch: Vec<ImportedCard>,
+    import: &mut CardImport,
+    today: chrono::NaiveDate,
+) -> anyhow::Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+
+    let cards: Vec<CardData> = batch.iter().map(|card| card.data.clone()).collect();
+    let ids = service::card::upsert_all(client, &cards).await?;
+
+    // Sets missing from the list of sets are created the first time they're seen
+    let mut new_sets = Vec::new();
+    for card_set in batch.iter().flat_map(|card| &card.card_sets) {
+        if !import.set_ids.contains_key(&card_set.set_name)
+            && !new_sets
+                .iter()
+                .any(|set: &ygo::CardSetData| set.name == card_set.set_name)
+        {
+            new_sets.push(ygo::CardSetData {
+                name: card_set.set_name.clone(),
+                code: card_set.get_set_code().to_string(),
+                ..Default::default()
+            });
+        }
+    }
+    if !new_sets.is_empty() {
+        for set in service::set::upsert_all(client, &new_sets).await? {
+            import.set_ids.insert(set.data.name, set.id);
+        }
+    }
+
+    let mut prints = HashMap::new();
+    let mut artworks = Vec::with_capacity(batch.len());
+    let mut prices = Vec::new();
+    for (card, id) in batch.into_iter().zip(ids) {
+        let Some((card_id, inserted)) = id else {
+            continue;
         };
+        match inserted {
+            true => import.inserted += 1,
+            false => import.updated += 1,
+        }
 
-        if let Some(mut card) = existing_card {
-            card.data = card_data.clone();
-            service::card::save(client, &card).await?;
-            updated += 1;
-        } else {
-            let new_card = ygo::NewCard {
-                data: card_data.clone(),
+        for card_set in card.card_sets {
+            let print = ygo::NewCardPrint {
+                card_id,
+                set_id: import.set_ids[&card_set.set_name],
+                rarity_code: card_set.get_rarity_code(),
+                code: card_set.set_code,
+                rarity: card_set.set_rarity,
             };
-            service::card::save_new(client, &new_card).await?;
-            inserted += 1;
+            let key = (print.set_id, print.code.clone(), print.rarity.clone());
+            prints.insert((card_id, key), print);
         }
+
+        artworks.push((card_id, card.artwork_ids));
+
+        for (format, limit) in card.banlist_info.iter().flat_map(|info| info.get_limits()) {
+            import
+                .banlists
+                .entry(format)
+                .or_default()
+                .insert(card_id, limit);
+        }
+
+        // Keep a daily snapshot of the prices
+        prices.extend(
+            card.card_prices
+                .iter()
+                .flat_map(|p| p.get_prices())
+                .map(|(source, price)| (card_id, source, price)),
+        );
+    }
+
+    let prints: Vec<_> = prints.into_values().collect();
+    service::set::upsert_prints(client, &prints).await?;
+    let changed_artworks = service::artwork::save_all_by_card(client, &artworks).await?;
+    import.changed_artworks.extend(changed_artworks);
+    service::price::save_snapshots(client, today, &prices).await?;
+
+    Ok(())
+}
+
+/// Saves the banlists as effective from today, unless they match the ones already in effect
+async fn import_banlists(
+    client: &Client,
+    banlists: HashMap<ygo::BanlistFormat, HashMap<i32, i16>>,
+    today: chrono::NaiveDate,
+) -> anyhow::Result<()> {
+    for (format, limits) in banlists {
+        if let Some(current) = service::banlist::get_effective(client, format, today).await?
+            && service::banlist::get_limits(client, current.id).await? == limits
+        {
+            continue;
+        }
+
+        service::banlist::save(client, format, today, &limits).await?;
+    }
+
+    Ok(())
+}
+
+/// Imports card sets from a json string
+async fn import_sets_from_json_str(client: &Client, json: &str) -> anyhow::Result<usize> {
+    let sets: Vec<YgoProDeckSet> =
+        serde_json::from_str(json).with_context(|| "Failed to parse ygoprodeck JSON set list")?;
+
+    for set in &sets {
+        let set_data = ygo::CardSetData {
+            name: set.set_name.clone(),
+            code: set.set_code.clone(),
+            release_date: YgoProDeckCard::parse_date_opt(&set.tcg_date),
+            card_count: set.num_of_cards,
+        };
+        service::set::upsert(client, &set_data).await?;
     }
 
-    Ok((inserted, updated))
+    Ok(sets.len())
 }
 
-/// Imports YgoProDeck cards into the database
-pub async fn import(client: &Client) -> anyhow::Result<(usize, usize)> {
-    const ENDPOINT: &str = "https://db.ygoprodeck.com/api/v7/cardinfo.php?misc=yes&sort=new";
+/// Imports YgoProDeck sets and cards into the database
+pub async fn import(client: &Client, images: &ImageCache) -> anyhow::Result<(usize, usize)> {
+    const SETS_ENDPOINT: &str = "https://db.ygoprodeck.com/api/v7/cardsets.php";
+    const CARDS_ENDPOINT: &str = "https://db.ygoprodeck.com/api/v7/cardinfo.php?misc=yes&sort=new";
+
+    // Import sets first, so that card printings can be attached to them
+    let json = reqwest::get(SETS_ENDPOINT).await?.text().await?;
+    import_sets_from_json_str(client, &json).await?;
 
-    let json = reqwest::get(ENDPOINT).await?.text().await?;
+    // Cards are imported while they're received, rather than after the whole list
+    let response = reqwest::get(CARDS_ENDPOINT).await?.error_for_status()?;
+    let chunks = stream::try_unfold(response, async |mut response| {
+        let chunk = response.chunk().await?;
+        Ok(chunk.map(|chunk| (chunk.to_vec(), response)))
+    });
 
-    import_from_json_str(client, &json).await
+    import_from_json_chunks(client, images, chunks).await
 }
 
 /// Card art size variant
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum CardImageSize {
     #[serde(rename = "small")]
     Small,
@@ -448,30 +882,70 @@ fn get_card_image_url(ygoprodeck_id: i32, size: &CardImageSize) -> String {
     format!("{BASE_URL}/cards{size_suffix}/{ygoprodeck_id}.jpg")
 }
 
-/// Retrieve card image from ygoprodeck
-pub async fn get_card_image(ygoprodeck_id: i32, size: &CardImageSize) -> anyhow::Result<Vec<u8>> {
+/// Retrieve card image from ygoprodeck, if it has one
+pub async fn get_card_image(
+    ygoprodeck_id: i32,
+    size: &CardImageSize,
+) -> anyhow::Result<Option<Vec<u8>>> {
     let url = get_card_image_url(ygoprodeck_id, size);
     let response = reqwest::get(&url).await?;
 
     let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
     if !status.is_success() {
         anyhow::bail!("Failed to retrieve card image from {url}: HTTP {status}")
     }
 
     let bytes = response.bytes().await?;
-    Ok(bytes.to_vec())
+    Ok(Some(bytes.to_vec()))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
     use super::*;
-    use crate::{models::ygo, test_utils::with_db_pool};
+    use crate::services::image::ImageFormat;
+    use crate::services::image_storage::FilesystemStorage;
+    use crate::services::ygo::image::get_card_image_key;
+    use crate::{
+        models::ygo,
+        test_utils::{test_dir, with_db_pool},
+    };
 
     async fn json_to_card_data(json: &str) -> anyhow::Result<Vec<CardData>> {
-        parse_json_list(json)
-            .await?
-            .map(|card| card.try_into())
-            .collect::<anyhow::Result<Vec<CardData>>>()
+        let mut cards = Vec::new();
+        parse_json_list(json.as_bytes(), |card| {
+            cards.push(card);
+            true
+        })?;
+
+        cards.into_iter().map(|card| card.try_into()).collect()
+    }
+
+    /// Imports cards from a json string, received in small chunks
+    async fn import_from_json_str(client: &Client, json: &str) -> anyhow::Result<(usize, usize)> {
+        let dir = test_dir("import");
+        let images = ImageCache::new(Arc::new(FilesystemStorage::new(dir.path())), None);
+        import_from_json_str_with_images(client, &images, json).await
+    }
+
+    /// Imports cards from a json string, received in small chunks, along with the image cache
+    async fn import_from_json_str_with_images(
+        client: &Client,
+        images: &ImageCache,
+        json: &str,
+    ) -> anyhow::Result<(usize, usize)> {
+        let chunks = json
+            .as_bytes()
+            .chunks(64)
+            .map(|chunk| Ok(chunk.to_vec()))
+            .collect::<Vec<_>>();
+
+        import_from_json_chunks(client, images, stream::iter(chunks)).await
     }
 
     #[tokio::test]
@@ -1611,6 +2085,518 @@ mod tests {
         }).await
     }
 
+    #[tokio::test]
+    async fn test_import_json_saves_card_prints() {
+        with_db_pool(async move |db_pool| {
+            let json = r#"{"data":[{
+                "id": 46533533,
+                "name": "Dipity",
+                "typeline": ["Fiend", "Normal"],
+                "frameType": "normal",
+                "desc": "''A cute little thing who lives in a glass bottle.''",
+                "race": "Fiend",
+                "atk": 0,
+                "def": 0,
+                "level": 4,
+                "attribute": "LIGHT",
+                "card_sets": [
+                    {
+                        "set_name": "Alliance Insight",
+                        "set_code": "ALIN-EN097",
+                        "set_rarity": "Common",
+                        "set_rarity_code": "(C)",
+                        "set_price": "0"
+                    },
+                    {
+                        "set_name": "Alliance Insight",
+                        "set_code": "ALIN-EN097",
+                        "set_rarity": "Quarter Century Secret Rare",
+                        "set_rarity_code": "",
+                        "set_price": "0"
+                    }
+                ],
+                "misc_info": [{ "konami_id": 20274 }]
+            }]}"#;
+
+            let client = db_pool.get().await.expect("Could not get DB client");
+
+            // Importing twice should not duplicate the printings
+            for _ in 0..2 {
+                import_from_json_str(&client, json)
+                    .await
+                    .expect("Could not import cards from JSON");
+            }
+
+            let card = service::card::get_by_konami_id(&client, 20274)
+                .await
+                .expect("Could not get card by Konami ID")
+                .expect("Card not found");
+
+            let prints = service::set::get_prints_by_card_id(&client, card.id)
+                .await
+                .expect("Could not get card prints");
+
+            assert_eq!(prints.len(), 2);
+            assert!(prints.iter().all(|p| p.code == "ALIN-EN097"));
+            assert!(prints.iter().all(|p| p.set.data.name == "Alliance Insight"));
+            assert!(prints.iter().all(|p| p.set.data.code == "ALIN"));
+            assert!(
+                prints
+                    .iter()
+                    .any(|p| p.rarity == "Common" && p.rarity_code.as_deref() == Some("(C)"))
+            );
+            assert!(
+                prints
+                    .iter()
+                    .any(|p| p.rarity == "Quarter Century Secret Rare" && p.rarity_code.is_none())
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_import_json_saves_artworks() {
+        with_db_pool(async move |db_pool| {
+            let json = r#"{"data":[{
+                "id": 46986414,
+                "name": "Dark Magician",
+                "typeline": ["Spellcaster", "Normal"],
+                "frameType": "normal",
+                "desc": "''The ultimate wizard in terms of attack and defense.''",
+                "race": "Spellcaster",
+                "atk": 2500,
+                "def": 2100,
+                "level": 7,
+                "attribute": "DARK",
+                "card_images": [
+                    {
+                        "id": 46986414,
+                        "image_url": "https://images.ygoprodeck.com/images/cards/46986414.jpg",
+                        "image_url_small": "https://images.ygoprodeck.com/images/cards_small/46986414.jpg",
+                        "image_url_cropped": "https://images.ygoprodeck.com/images/cards_cropped/46986414.jpg"
+                    },
+                    {
+                        "id": 36996508,
+                        "image_url": "https://images.ygoprodeck.com/images/cards/36996508.jpg",
+                        "image_url_small": "https://images.ygoprodeck.com/images/cards_small/36996508.jpg",
+                        "image_url_cropped": "https://images.ygoprodeck.com/images/cards_cropped/36996508.jpg"
+                    }
+                ],
+                "misc_info": [{ "konami_id": 4041 }]
+            }]}"#;
+
+            let client = db_pool.get().await.expect("Could not get DB client");
+
+            // Importing twice should not duplicate the artworks
+            for _ in 0..2 {
+                import_from_json_str(&client, json)
+                    .await
+                    .expect("Could not import cards from JSON");
+            }
+
+            let card = service::card::get_by_konami_id(&client, 4041)
+                .await
+                .expect("Could not get card by Konami ID")
+                .expect("Card not found");
+
+            let artworks = service::artwork::get_by_card_id(&client, card.id)
+                .await
+                .expect("Could not get card artworks");
+
+            assert_eq!(
+                artworks
+                    .iter()
+                    .map(|a| (a.index, a.ygoprodeck_id))
+                    .collect::<Vec<_>>(),
+                vec![(0, 46986414), (1, 36996508)]
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_import_json_deletes_cached_images_of_replaced_artworks() {
+        with_db_pool(async move |db_pool| {
+            let mut card = make_card_json(89631139, Some(4007), "Blue-Eyes White Dragon");
+            card["card_images"] = serde_json::json!([{ "id": 89631139 }, { "id": 89631140 }]);
+            let json = serde_json::json!({ "data": [card] }).to_string();
+
+            let dir = test_dir("import-artworks");
+            let images = ImageCache::new(Arc::new(FilesystemStorage::new(dir.path())), None);
+            let client = db_pool.get().await.expect("Could not get DB client");
+            import_from_json_str_with_images(&client, &images, &json)
+                .await
+                .expect("Could not import cards from JSON");
+
+            let id = service::card::get_by_konami_id(&client, 4007)
+                .await
+                .expect("Could not get card by Konami ID")
+                .expect("Card not found")
+                .id;
+            let key = get_card_image_key(id, 1, &CardImageSize::Small, None, ImageFormat::Jpeg);
+            images.save(&key, b"cached".to_vec()).await.unwrap();
+
+            // Importing the same artworks keeps the cached images
+            import_from_json_str_with_images(&client, &images, &json)
+                .await
+                .expect("Could not import cards from JSON");
+            assert!(images.read(&key).await.unwrap().is_some());
+
+            // The second artwork was replaced by another one
+            card["card_images"] = serde_json::json!([{ "id": 89631139 }, { "id": 89631141 }]);
+            let json = serde_json::json!({ "data": [card] }).to_string();
+            import_from_json_str_with_images(&client, &images, &json)
+                .await
+                .expect("Could not import cards from JSON");
+            assert!(images.read(&key).await.unwrap().is_none());
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_import_json_saves_banlists() {
+        with_db_pool(async move |db_pool| {
+            let json = r#"{"data":[
+                {
+                    "id": 55144522,
+                    "name": "Pot of Greed",
+                    "frameType": "spell",
+                    "desc": "Draw 2 cards.",
+                    "race": "Normal",
+                    "banlist_info": { "ban_tcg": "Banned", "ban_ocg": "Forbidden", "ban_goat": "Limited" },
+                    "misc_info": [{ "konami_id": 4844 }]
+                },
+                {
+                    "id": 46533533,
+                    "name": "Dipity",
+                    "typeline": ["Fiend", "Normal"],
+                    "frameType": "normal",
+                    "desc": "''A cute little thing who lives in a glass bottle.''",
+                    "race": "Fiend",
+                    "attribute": "LIGHT",
+                    "banlist_info": { "ban_goat": "Semi-Limited" },
+                    "misc_info": [{ "konami_id": 20274 }]
+                }
+            ]}"#;
+
+            let client = db_pool.get().await.expect("Could not get DB client");
+
+            // Importing twice the same list should not create new banlists
+            for _ in 0..2 {
+                import_from_json_str(&client, json)
+                    .await
+                    .expect("Could not import cards from JSON");
+            }
+
+            let banlists = service::banlist::get_all(&client, None)
+                .await
+                .expect("Could not get banlists");
+            assert_eq!(banlists.len(), 3);
+
+            let pot = service::card::get_by_konami_id(&client, 4844)
+                .await
+                .expect("Could not get card by Konami ID")
+                .expect("Card not found");
+            assert_eq!(
+                pot.banlist,
+                BTreeMap::from([
+                    (ygo::BanlistFormat::Tcg, ygo::BanStatus::Forbidden),
+                    (ygo::BanlistFormat::Ocg, ygo::BanStatus::Forbidden),
+                    (ygo::BanlistFormat::Goat, ygo::BanStatus::Limited),
+                ])
+            );
+
+            let dipity = service::card::get_by_konami_id(&client, 20274)
+                .await
+                .expect("Could not get card by Konami ID")
+                .expect("Card not found");
+            assert_eq!(
+                dipity.banlist,
+                BTreeMap::from([(ygo::BanlistFormat::Goat, ygo::BanStatus::SemiLimited)])
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_import_json_saves_price_snapshot() {
+        with_db_pool(async move |db_pool| {
+            let json = r#"{"data":[{
+                "id": 55144522,
+                "name": "Pot of Greed",
+                "frameType": "spell",
+                "desc": "Draw 2 cards.",
+                "race": "Normal",
+                "card_prices": [{
+                    "cardmarket_price": "0.10",
+                    "tcgplayer_price": "0.08",
+                    "ebay_price": "0.99",
+                    "amazon_price": "0.00",
+                    "coolstuffinc_price": "0.49"
+                }],
+                "misc_info": [{ "konami_id": 4844 }]
+            }]}"#;
+
+            let client = db_pool.get().await.expect("Could not get DB client");
+
+            // Importing twice the same day keeps a single snapshot
+            for _ in 0..2 {
+                import_from_json_str(&client, json)
+                    .await
+                    .expect("Could not import cards from JSON");
+            }
+
+            let card = service::card::get_by_konami_id(&client, 4844)
+                .await
+                .expect("Could not get card by Konami ID")
+                .expect("Card not found");
+            let prices = service::price::get_history(&client, card.id, None)
+                .await
+                .expect("Could not get prices");
+
+            let today = chrono::Utc::now().date_naive();
+            assert!(prices.iter().all(|p| p.date == today));
+            let by_source: Vec<_> = prices
+                .iter()
+                .map(|p| (p.source, p.price.to_string()))
+                .collect();
+            assert_eq!(
+                by_source,
+                vec![
+                    (ygo::PriceSource::Tcgplayer, "0.08".to_string()),
+                    (ygo::PriceSource::Cardmarket, "0.10".to_string()),
+                    (ygo::PriceSource::Ebay, "0.99".to_string()),
+                    (ygo::PriceSource::Coolstuffinc, "0.49".to_string()),
+                ]
+            );
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_import_sets_json() {
+        with_db_pool(async move |db_pool| {
+            let json = r#"[
+                {
+                    "set_name": "Legend of Blue Eyes White Dragon",
+                    "set_code": "LOB",
+                    "num_of_cards": 126,
+                    "tcg_date": "2002-03-08",
+                    "set_image": "https://images.ygoprodeck.com/images/sets/LOB.jpg"
+                },
+                {
+                    "set_name": "Yu-Gi-Oh! Championship Series Prize Cards",
+                    "set_code": "YCSW",
+                    "num_of_cards": 3
+                }
+            ]"#;
+
+            let client = db_pool.get().await.expect("Could not get DB client");
+            let imported = import_sets_from_json_str(&client, json)
+                .await
+                .expect("Could not import sets from JSON");
+            assert_eq!(imported, 2);
+
+            let sets = service::set::get_all(&client, None)
+                .await
+                .expect("Could not get sets");
+            assert_eq!(sets.len(), 2);
+
+            let lob = &sets[0];
+            assert_eq!(lob.data.name, "Legend of Blue Eyes White Dragon");
+            assert_eq!(lob.data.code, "LOB");
+            assert_eq!(lob.data.card_count, Some(126));
+            assert_eq!(
+                lob.data.release_date,
+                chrono::NaiveDate::from_ymd_opt(2002, 3, 8)
+            );
+            assert_eq!(sets[1].data.release_date, None);
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_import_json_attaches_printings_to_imported_sets() {
+        with_db_pool(async move |db_pool| {
+            let json = r#"[{
+                "set_name": "Alliance Insight",
+                "set_code": "ALIN",
+                "num_of_cards": 101,
+                "tcg_date": "2023-05-11"
+            }]"#;
+
+            let client = db_pool.get().await.expect("Could not get DB client");
+            import_sets_from_json_str(&client, json)
+                .await
+                .expect("Could not import sets from JSON");
+            let sets = service::set::get_all(&client, None)
+                .await
+                .expect("Could not get sets");
+
+            let cards =
+                serde_json::json!({ "data": [make_card_json(10_000_001, Some(1), "Card")] });
+            import_from_json_str(&client, &cards.to_string())
+                .await
+                .expect("Could not import cards from JSON");
+
+            // The set is left as imported from the list of sets
+            let imported = service::set::get_all(&client, None)
+                .await
+                .expect("Could not get sets");
+            assert_eq!(imported, sets);
+
+            let card = service::card::get_by_konami_id(&client, 1)
+                .await
+                .expect("Could not get card by Konami ID")
+                .expect("Card not found");
+            let prints = service::set::get_prints_by_card_id(&client, card.id)
+                .await
+                .expect("Could not get card prints");
+            assert_eq!(prints.len(), 1);
+            assert_eq!(prints[0].set, sets[0]);
+        })
+        .await
+    }
+
+    /// Utility to build the json of a normal monster, without a Konami ID when none is given
+    fn make_card_json(id: i32, konami_id: Option<i32>, name: &str) -> serde_json::Value {
+        let misc_info = match konami_id {
+            Some(konami_id) => serde_json::json!([{ "konami_id": konami_id }]),
+            None => serde_json::json!([{}]),
+        };
+
+        serde_json::json!({
+            "id": id,
+            "name": name,
+            "typeline": ["Fiend", "Normal"],
+            "frameType": "normal",
+            "desc": "",
+            "race": "Fiend",
+            "atk": 0,
+            "def": 0,
+            "level": 4,
+            "attribute": "LIGHT",
+            "card_sets": [
+                { "set_name": "Alliance Insight", "set_code": "ALIN-EN097", "set_rarity": "Common" },
+                { "set_name": "Alliance Insight", "set_code": "ALIN-EN097", "set_rarity": "Common" }
+            ],
+            "card_prices": [{ "cardmarket_price": "0.11" }],
+            "misc_info": misc_info
+        })
+    }
+
+    #[tokio::test]
+    async fn test_import_json_in_batches() {
+        with_db_pool(async move |db_pool| {
+            let count = IMPORT_BATCH_SIZE as i32 + 10;
+            let mut cards: Vec<_> = (1..=count)
+                .map(|i| make_card_json(10_000_000 + i, Some(i), &format!("Card {i}")))
+                .collect();
+
+            // Copies of a card in the same batch or in another one update it in turn
+            cards.insert(3, make_card_json(10_000_002, Some(2), "Card 2 again"));
+            cards.push(make_card_json(10_000_001, Some(1), "Card 1 again"));
+
+            // Cards without Konami ID are matched by password
+            cards.push(make_card_json(46533533, None, "Dipity"));
+            cards.push(make_card_json(46533533, None, "Dipity again"));
+
+            let json = serde_json::json!({ "data": cards }).to_string();
+            let client = db_pool.get().await.expect("Could not get DB client");
+            let (inserted, updated) = import_from_json_str(&client, &json)
+                .await
+                .expect("Could not import cards from JSON");
+
+            assert_eq!(inserted, count as usize + 1);
+            assert_eq!(updated, 3);
+
+            let cards = service::card::get_all(&client)
+                .await
+                .expect("Could not get cards");
+            assert_eq!(cards.len(), count as usize + 1);
+
+            for (konami_id, name) in [(1, "Card 1 again"), (2, "Card 2 again"), (count, "")] {
+                let card = service::card::get_by_konami_id(&client, konami_id)
+                    .await
+                    .expect("Could not get card by Konami ID")
+                    .expect("Card not found");
+                let name = match name {
+                    "" => format!("Card {konami_id}"),
+                    name => name.to_string(),
+                };
+                assert_eq!(card.data.name, name);
+            }
+
+            let card = service::card::get_by_password(&client, "46533533")
+                .await
+                .expect("Could not get card by password")
+                .expect("Card not found");
+            assert_eq!(card.data.name, "Dipity again");
+
+            // Every card has its printing, artwork and price, once
+            for table in ["ygo_card_prints", "ygo_card_artworks", "ygo_card_prices"] {
+                let row = client
+                    .query_one(&format!("SELECT COUNT(*) FROM {table}"), &[])
+                    .await
+                    .expect("Could not count rows");
+                assert_eq!(row.get::<_, i64>(0), count as i64 + 1, "{table}");
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_import_json_does_not_save_cards_of_invalid_list() {
+        with_db_pool(async move |db_pool| {
+            // The list is cut off after some batches of cards were saved
+            let cards: Vec<_> = (1..=IMPORT_BATCH_SIZE as i32 * 2)
+                .map(|i| make_card_json(10_000_000 + i, Some(i), &format!("Card {i}")))
+                .collect();
+            let json = serde_json::json!({ "data": cards }).to_string();
+            let truncated = &json[..json.len() - 3];
+
+            let client = db_pool.get().await.expect("Could not get DB client");
+            let result = import_from_json_str(&client, truncated).await;
+            assert!(result.is_err());
+            assert!(import_from_json_str(&client, "{}").await.is_err());
+
+            let cards = service::card::get_all(&client)
+                .await
+                .expect("Could not get cards");
+            assert!(cards.is_empty());
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_import_json_does_not_save_cards_of_failed_response() {
+        with_db_pool(async move |db_pool| {
+            // The response fails after some batches of cards were saved
+            let cards: Vec<_> = (1..=IMPORT_BATCH_SIZE as i32 * 2)
+                .map(|i| make_card_json(10_000_000 + i, Some(i), &format!("Card {i}")))
+                .collect();
+            let json = serde_json::json!({ "data": cards }).to_string();
+            let received = &json.as_bytes()[..json.len() * 3 / 4];
+            let mut chunks: Vec<_> = received
+                .chunks(64)
+                .map(|chunk| Ok(chunk.to_vec()))
+                .collect();
+            chunks.push(Err(anyhow::anyhow!("Connection reset")));
+
+            let dir = test_dir("import-failed");
+            let images = ImageCache::new(Arc::new(FilesystemStorage::new(dir.path())), None);
+            let client = db_pool.get().await.expect("Could not get DB client");
+            let result = import_from_json_chunks(&client, &images, stream::iter(chunks)).await;
+            assert!(result.is_err());
+
+            let cards = service::card::get_all(&client)
+                .await
+                .expect("Could not get cards");
+            assert!(cards.is_empty());
+        })
+        .await
+    }
+
     #[test]
     fn test_get_card_image_url() {
         assert_eq!(
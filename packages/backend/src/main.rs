@@ -3,15 +3,14 @@ use std::net::SocketAddr;
 use anyhow::{Ok, Result};
 use axum::{
     Router, ServiceExt,
-    extract::Request,
-    routing::{get, post},
+    extract::{DefaultBodyLimit, Request},
+    routing::{delete, get, post, put},
 };
 use tokio::{net::TcpListener, signal};
 use tower::Layer;
 use tower_governor::{GovernorLayer, governor::GovernorConfigBuilder};
 use tower_http::{
     compression::CompressionLayer,
-    limit::RequestBodyLimitLayer,
     normalize_path::NormalizePathLayer,
     services::{ServeDir, ServeFile},
     trace::TraceLayer,
@@ -29,11 +28,13 @@ mod services;
 mod test_utils;
 
 use prelude::*;
+use services::image_cache::ImageCache;
 
 fn api_v1() -> Router<AppState> {
     use api::v1::ygo;
 
     Router::new()
+        .route("/ygo/banlists", get(ygo::banlist::get_banlists))
         .route(
             "/ygo/cards",
             get(ygo::card::get_cards).post(ygo::card::create),
@@ -44,9 +45,76 @@ fn api_v1() -> Router<AppState> {
                 .put(ygo::card::update)
                 .delete(ygo::card::delete_by_id),
         )
+        .route("/ygo/cards/{id}/images", get(ygo::card::get_artworks))
+        .route("/ygo/cards/{id}/prices", get(ygo::price::get_card_prices))
+        .route("/ygo/cards/{id}/prints", get(ygo::set::get_card_prints))
         .route("/ygo/cards/import", post(ygo::card::import))
+        .route("/ygo/cards/facets", get(ygo::card::get_facets))
+        .route("/ygo/cards/lookup", get(ygo::card::lookup))
+        .route(
+            "/ygo/collection",
+            get(ygo::collection::get_items).post(ygo::collection::create),
+        )
+        .route("/ygo/collection/value", get(ygo::collection::get_value))
+        .route(
+            "/ygo/collection/{id}",
+            get(ygo::collection::get_by_id)
+                .put(ygo::collection::update)
+                .delete(ygo::collection::delete_by_id),
+        )
+        .route(
+            "/ygo/collection/{id}/scans",
+            get(ygo::collection::get_scans),
+        )
+        .route(
+            "/ygo/collection/{id}/scans/{scan_id}",
+            delete(ygo::collection::delete_scan),
+        )
+        .route(
+            "/ygo/collection/{id}/scans/{scan_id}/image",
+            get(ygo::collection::get_scan_image),
+        )
+        .route(
+            "/ygo/decks",
+            get(ygo::deck::get_decks).post(ygo::deck::create),
+        )
+        .route("/ygo/decks/import", post(ygo::deck::import_ydk))
+        .route("/ygo/decks/import/ydke", post(ygo::deck::import_ydke))
+        .route("/ygo/decks/preview", get(ygo::deck::preview_ydke))
+        .route("/ygo/decks/validate", post(ygo::deck::validate))
+        .route(
+            "/ygo/decks/{id}",
+            get(ygo::deck::get_by_id)
+                .put(ygo::deck::update)
+                .delete(ygo::deck::delete_by_id),
+        )
+        .route("/ygo/decks/{id}/ydk", get(ygo::deck::export_ydk))
+        .route("/ygo/decks/{id}/ydke", get(ygo::deck::export_ydke))
+        .route(
+            "/ygo/images/prefetch",
+            get(ygo::image::get_prefetch_progress).post(ygo::image::start_prefetch),
+        )
+        .route("/ygo/images/stats", get(ygo::image::get_cache_stats))
+        .route("/ygo/images/sweep", post(ygo::image::sweep_orphans))
+        .route("/ygo/sets", get(ygo::set::get_sets))
+        .route("/ygo/sets/{id}", get(ygo::set::get_set_by_id))
 }
 
+/// Routes of API v1 receiving image uploads, which have their own size limit
+fn api_v1_uploads() -> Router<AppState> {
+    use api::v1::ygo;
+
+    Router::new()
+        .route("/ygo/cards/{id}/image", put(ygo::card::upload_image))
+        .route(
+            "/ygo/collection/{id}/scans",
+            post(ygo::collection::upload_scan),
+        )
+}
+
+/// Maximum size of requests, other than image uploads
+const REQUEST_SIZE_LIMIT: usize = 1024 * 1024; // 1 MB
+
 fn app(state: AppState) -> Router {
     // Serve the frontend, and fallback all unknown routes to the index file
     let frontend_path = state.config.get_frontend_path();
@@ -62,14 +130,21 @@ fn app(state: AppState) -> Router {
             .expect("Could not create rate limiter"),
     );
 
+    // Limit request size, the limit of image uploads overriding the one of the app
+    let request_size = DefaultBodyLimit::max(REQUEST_SIZE_LIMIT);
+    let upload_size = DefaultBodyLimit::max(api::v1::ygo::card::MAX_UPLOAD_REQUEST_SIZE);
+
     // API v1
+    let api = api_v1().merge(api_v1_uploads().layer(upload_size));
+
     Router::new()
-        .nest("/api/v1", api_v1().layer(rate_limiter))
+        .nest("/api/v1", api.layer(rate_limiter))
         .route(
             "/api/v1/ygo/cards/{id}/image",
             get(api::v1::ygo::card::get_image_by_id),
         )
         .fallback_service(frontend)
+        .layer(request_size)
         .with_state(state)
 }
 
@@ -128,19 +203,18 @@ async fn main() -> Result<()> {
     // Compression layer for tower
     let compression = CompressionLayer::new();
 
-    // Limit request size
-    let size_limit_bytes = 1024 * 1024; // 1 MB
-    let request_size = RequestBodyLimitLayer::new(size_limit_bytes);
-
     // Create the app
     let state = AppState {
         config: config.clone(),
         db: db_pool,
+        images: ImageCache::new(
+            config.image_storage.build(config.get_content_path()),
+            config.image_cache_max_size,
+        ),
+        prefetch: Default::default(),
+        transcoder: Default::default(),
     };
-    let app = app(state)
-        .layer(trace)
-        .layer(compression)
-        .layer(request_size);
+    let app = app(state).layer(trace).layer(compression);
 
     // Normalize path layer to trim trailing slashes
     let app = NormalizePathLayer::trim_trailing_slash().layer(app);
@@ -13,4 +13,64 @@ pub const MIGRATIONS: &[Migration] = &[
             "migrations/250903_01_dn__add_ygoprodeck_id.sql"
         )),
     ),
+    (
+        "261017_01__ygo_collection",
+        include_str!("migrations/261017_01_up__ygo_collection.sql"),
+        Some(include_str!("migrations/261017_01_dn__ygo_collection.sql")),
+    ),
+    (
+        "261017_02__ygo_sets",
+        include_str!("migrations/261017_02_up__ygo_sets.sql"),
+        Some(include_str!("migrations/261017_02_dn__ygo_sets.sql")),
+    ),
+    (
+        "261017_03__ygo_decks",
+        include_str!("migrations/261017_03_up__ygo_decks.sql"),
+        Some(include_str!("migrations/261017_03_dn__ygo_decks.sql")),
+    ),
+    (
+        "261017_04__ygo_banlists",
+        include_str!("migrations/261017_04_up__ygo_banlists.sql"),
+        Some(include_str!("migrations/261017_04_dn__ygo_banlists.sql")),
+    ),
+    (
+        "261017_05__ygo_card_prices",
+        include_str!("migrations/261017_05_up__ygo_card_prices.sql"),
+        Some(include_str!("migrations/261017_05_dn__ygo_card_prices.sql")),
+    ),
+    (
+        "261017_06__ygo_collection_prints",
+        include_str!("migrations/261017_06_up__ygo_collection_prints.sql"),
+        Some(include_str!(
+            "migrations/261017_06_dn__ygo_collection_prints.sql"
+        )),
+    ),
+    (
+        "261017_07__ygo_cards_search",
+        include_str!("migrations/261017_07_up__ygo_cards_search.sql"),
+        Some(include_str!(
+            "migrations/261017_07_dn__ygo_cards_search.sql"
+        )),
+    ),
+    (
+        "261017_08__ygo_cards_trigram",
+        include_str!("migrations/261017_08_up__ygo_cards_trigram.sql"),
+        Some(include_str!(
+            "migrations/261017_08_dn__ygo_cards_trigram.sql"
+        )),
+    ),
+    (
+        "261017_09__ygo_card_artworks",
+        include_str!("migrations/261017_09_up__ygo_card_artworks.sql"),
+        Some(include_str!(
+            "migrations/261017_09_dn__ygo_card_artworks.sql"
+        )),
+    ),
+    (
+        "261017_10__ygo_collection_scans",
+        include_str!("migrations/261017_10_up__ygo_collection_scans.sql"),
+        Some(include_str!(
+            "migrations/261017_10_dn__ygo_collection_scans.sql"
+        )),
+    ),
 ];
@@ -0,0 +1,6 @@
+DO $$ BEGIN
+    DROP TABLE IF EXISTS ygo_collection_items;
+
+    DROP TYPE IF EXISTS YGO_CARD_CONDITION;
+    DROP TYPE IF EXISTS YGO_CARD_LANGUAGE;
+END $$;
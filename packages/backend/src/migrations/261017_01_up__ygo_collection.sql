@@ -0,0 +1,39 @@
+DO $$ BEGIN
+    CREATE TYPE YGO_CARD_CONDITION AS ENUM(
+        'near_mint',
+        'lightly_played',
+        'moderately_played',
+        'heavily_played',
+        'damaged'
+    );
+
+    CREATE TYPE YGO_CARD_LANGUAGE AS ENUM(
+        'english',
+        'french',
+        'german',
+        'italian',
+        'portuguese',
+        'spanish',
+        'japanese',
+        'korean',
+        'chinese'
+    );
+
+    CREATE TABLE IF NOT EXISTS
+        ygo_collection_items (
+            id SERIAL PRIMARY KEY,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+            card_id INTEGER NOT NULL REFERENCES ygo_cards (id) ON DELETE CASCADE,
+            quantity INTEGER NOT NULL,
+            condition YGO_CARD_CONDITION NOT NULL,
+            language YGO_CARD_LANGUAGE NOT NULL,
+            first_edition BOOLEAN NOT NULL DEFAULT FALSE,
+            acquisition_date DATE,
+            acquisition_price NUMERIC(12, 2),
+
+            CONSTRAINT ygo_collection_items_positive_quantity CHECK (quantity > 0)
+        );
+
+    CREATE INDEX IF NOT EXISTS ygo_collection_items_card_id_idx ON ygo_collection_items (card_id);
+END $$;
@@ -0,0 +1,4 @@
+DO $$ BEGIN
+    DROP TABLE IF EXISTS ygo_card_prints;
+    DROP TABLE IF EXISTS ygo_sets;
+END $$;
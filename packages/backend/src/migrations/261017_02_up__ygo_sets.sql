@@ -0,0 +1,34 @@
+DO $$ BEGIN
+    CREATE TABLE IF NOT EXISTS
+        ygo_sets (
+            id SERIAL PRIMARY KEY,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+            name TEXT NOT NULL,
+            code TEXT NOT NULL,
+            release_date DATE,
+            card_count INTEGER,
+
+            CONSTRAINT ygo_sets_unique_name UNIQUE (name)
+        );
+
+    CREATE INDEX IF NOT EXISTS ygo_sets_code_idx ON ygo_sets (code);
+    CREATE INDEX IF NOT EXISTS ygo_sets_release_date_idx ON ygo_sets (release_date);
+
+    CREATE TABLE IF NOT EXISTS
+        ygo_card_prints (
+            id SERIAL PRIMARY KEY,
+
+            card_id INTEGER NOT NULL REFERENCES ygo_cards (id) ON DELETE CASCADE,
+            set_id INTEGER NOT NULL REFERENCES ygo_sets (id) ON DELETE CASCADE,
+            code TEXT NOT NULL,
+            rarity TEXT NOT NULL,
+            rarity_code TEXT,
+
+            CONSTRAINT ygo_card_prints_unique_print UNIQUE (card_id, set_id, code, rarity)
+        );
+
+    CREATE INDEX IF NOT EXISTS ygo_card_prints_card_id_idx ON ygo_card_prints (card_id);
+    CREATE INDEX IF NOT EXISTS ygo_card_prints_set_id_idx ON ygo_card_prints (set_id);
+    CREATE INDEX IF NOT EXISTS ygo_card_prints_code_idx ON ygo_card_prints (code);
+END $$;
@@ -0,0 +1,6 @@
+DO $$ BEGIN
+    DROP TABLE IF EXISTS ygo_deck_cards;
+    DROP TABLE IF EXISTS ygo_decks;
+
+    DROP TYPE IF EXISTS YGO_DECK_ZONE;
+END $$;
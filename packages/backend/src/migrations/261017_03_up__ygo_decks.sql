@@ -0,0 +1,24 @@
+DO $$ BEGIN
+    CREATE TYPE YGO_DECK_ZONE AS ENUM('main', 'extra', 'side');
+
+    CREATE TABLE IF NOT EXISTS
+        ygo_decks (
+            id SERIAL PRIMARY KEY,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+            name TEXT NOT NULL,
+            description TEXT
+        );
+
+    CREATE TABLE IF NOT EXISTS
+        ygo_deck_cards (
+            deck_id INTEGER NOT NULL REFERENCES ygo_decks (id) ON DELETE CASCADE,
+            zone YGO_DECK_ZONE NOT NULL,
+            position INTEGER NOT NULL,
+            card_id INTEGER NOT NULL REFERENCES ygo_cards (id) ON DELETE CASCADE,
+
+            PRIMARY KEY (deck_id, zone, position)
+        );
+
+    CREATE INDEX IF NOT EXISTS ygo_deck_cards_card_id_idx ON ygo_deck_cards (card_id);
+END $$;
@@ -0,0 +1,6 @@
+DO $$ BEGIN
+    DROP TABLE IF EXISTS ygo_banlist_cards;
+    DROP TABLE IF EXISTS ygo_banlists;
+
+    DROP TYPE IF EXISTS YGO_BANLIST_FORMAT;
+END $$;
@@ -0,0 +1,25 @@
+DO $$ BEGIN
+    CREATE TYPE YGO_BANLIST_FORMAT AS ENUM('tcg', 'ocg', 'goat');
+
+    CREATE TABLE IF NOT EXISTS
+        ygo_banlists (
+            id SERIAL PRIMARY KEY,
+            updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+            format YGO_BANLIST_FORMAT NOT NULL,
+            effective_date DATE NOT NULL,
+
+            UNIQUE (format, effective_date)
+        );
+
+    CREATE TABLE IF NOT EXISTS
+        ygo_banlist_cards (
+            banlist_id INTEGER NOT NULL REFERENCES ygo_banlists (id) ON DELETE CASCADE,
+            card_id INTEGER NOT NULL REFERENCES ygo_cards (id) ON DELETE CASCADE,
+            card_limit SMALLINT NOT NULL CHECK (card_limit BETWEEN 0 AND 2),
+
+            PRIMARY KEY (banlist_id, card_id)
+        );
+
+    CREATE INDEX IF NOT EXISTS ygo_banlist_cards_card_id_idx ON ygo_banlist_cards (card_id);
+END $$;
@@ -0,0 +1,5 @@
+DO $$ BEGIN
+    DROP TABLE IF EXISTS ygo_card_prices;
+
+    DROP TYPE IF EXISTS YGO_PRICE_SOURCE;
+END $$;
@@ -0,0 +1,13 @@
+DO $$ BEGIN
+    CREATE TYPE YGO_PRICE_SOURCE AS ENUM('tcgplayer', 'cardmarket', 'ebay', 'amazon', 'coolstuffinc');
+
+    CREATE TABLE IF NOT EXISTS
+        ygo_card_prices (
+            card_id INTEGER NOT NULL REFERENCES ygo_cards (id) ON DELETE CASCADE,
+            source YGO_PRICE_SOURCE NOT NULL,
+            date DATE NOT NULL,
+            price NUMERIC(12, 2) NOT NULL,
+
+            PRIMARY KEY (card_id, source, date)
+        );
+END $$;
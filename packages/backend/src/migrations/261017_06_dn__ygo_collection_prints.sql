@@ -0,0 +1,3 @@
+DO $$ BEGIN
+    ALTER TABLE ygo_collection_items DROP COLUMN IF EXISTS print_id;
+END $$;
@@ -0,0 +1,4 @@
+DO $$ BEGIN
+    ALTER TABLE ygo_collection_items
+        ADD COLUMN IF NOT EXISTS print_id INTEGER REFERENCES ygo_card_prints (id) ON DELETE SET NULL;
+END $$;
@@ -0,0 +1,4 @@
+DO $$ BEGIN
+    DROP INDEX IF EXISTS ygo_cards_search_vector_idx;
+    ALTER TABLE ygo_cards DROP COLUMN IF EXISTS search_vector;
+END $$;
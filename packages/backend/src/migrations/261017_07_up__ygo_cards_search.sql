@@ -0,0 +1,10 @@
+DO $$ BEGIN
+    -- Full-text search document, names weighing more than effect texts
+    ALTER TABLE ygo_cards ADD COLUMN IF NOT EXISTS search_vector TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', name), 'A') ||
+        setweight(to_tsvector('english', description), 'B') ||
+        setweight(to_tsvector('english', coalesce(monster_pendulum_effect, '')), 'B')
+    ) STORED;
+
+    CREATE INDEX IF NOT EXISTS ygo_cards_search_vector_idx ON ygo_cards USING GIN (search_vector);
+END $$;
@@ -0,0 +1,4 @@
+DO $$ BEGIN
+    DROP INDEX IF EXISTS ygo_cards_name_trgm_idx;
+    DROP EXTENSION IF EXISTS pg_trgm;
+END $$;
@@ -0,0 +1,6 @@
+DO $$ BEGIN
+    CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+    -- Typo-tolerant name lookups
+    CREATE INDEX IF NOT EXISTS ygo_cards_name_trgm_idx ON ygo_cards USING GIN (name gin_trgm_ops);
+END $$;
@@ -0,0 +1,3 @@
+DO $$ BEGIN
+    DROP TABLE IF EXISTS ygo_card_artworks;
+END $$;
@@ -0,0 +1,17 @@
+DO $$ BEGIN
+    CREATE TABLE IF NOT EXISTS
+        ygo_card_artworks (
+            card_id INTEGER NOT NULL REFERENCES ygo_cards (id) ON DELETE CASCADE,
+            art_index SMALLINT NOT NULL,
+            ygoprodeck_id INTEGER NOT NULL,
+
+            PRIMARY KEY (card_id, art_index)
+        );
+
+    -- Cards imported so far only know their default artwork
+    INSERT INTO ygo_card_artworks (card_id, art_index, ygoprodeck_id)
+    SELECT id, 0, ygoprodeck_id
+    FROM ygo_cards
+    WHERE ygoprodeck_id IS NOT NULL
+    ON CONFLICT DO NOTHING;
+END $$;
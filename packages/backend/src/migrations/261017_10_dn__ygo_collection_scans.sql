@@ -0,0 +1,5 @@
+DO $$ BEGIN
+    DROP TABLE IF EXISTS ygo_collection_scans;
+
+    DROP TYPE IF EXISTS YGO_SCAN_SIDE;
+END $$;
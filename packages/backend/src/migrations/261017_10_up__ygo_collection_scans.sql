@@ -0,0 +1,22 @@
+DO $$ BEGIN
+    CREATE TYPE YGO_SCAN_SIDE AS ENUM(
+        'front',
+        'back',
+        'slab'
+    );
+
+    CREATE TABLE IF NOT EXISTS
+        ygo_collection_scans (
+            id SERIAL PRIMARY KEY,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+
+            item_id INTEGER NOT NULL REFERENCES ygo_collection_items (id) ON DELETE CASCADE,
+            side YGO_SCAN_SIDE NOT NULL,
+            capture_date DATE,
+            width INTEGER NOT NULL,
+            height INTEGER NOT NULL,
+            size INTEGER NOT NULL
+        );
+
+    CREATE INDEX IF NOT EXISTS ygo_collection_scans_item_id_idx ON ygo_collection_scans (item_id);
+END $$;
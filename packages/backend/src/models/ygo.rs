@@ -1,8 +1,9 @@
-use std::fmt;
+use std::{collections::BTreeMap, fmt};
 
 use bitflags::bitflags;
 use chrono::{DateTime, NaiveDate, Utc};
 use postgres_types::{FromSql, ToSql};
+use rust_decimal::Decimal;
 use serde::{
     Deserialize, Deserializer, Serialize, Serializer,
     de::{self, SeqAccess, Visitor},
@@ -17,6 +18,9 @@ pub struct Card {
     pub updated_at: DateTime<Utc>,
     #[serde(flatten)]
     pub data: CardData,
+    /// Current status on the banlist of each format, when not unlimited
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub banlist: BTreeMap<BanlistFormat, BanStatus>,
 }
 
 /// A new Yu-Gi-Oh! card to be inserted into the database.
@@ -100,6 +104,16 @@ pub enum MonsterKind {
     Link,
 }
 
+impl MonsterKind {
+    /// Whether monsters of this kind belong to the Extra Deck
+    pub fn is_extra_deck(&self) -> bool {
+        matches!(
+            self,
+            MonsterKind::Fusion | MonsterKind::Synchro | MonsterKind::Xyz | MonsterKind::Link
+        )
+    }
+}
+
 /// Monster card subtypes (Flip, Gemini, Spirit, etc.)
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, ToSql, FromSql)]
 #[postgres(name = "ygo_monster_subtype", rename_all = "snake_case")]
@@ -286,6 +300,347 @@ pub enum TrapKind {
     Counter,
 }
 
+/// A Yu-Gi-Oh! product cards are printed in (booster pack, structure deck, etc.)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CardSet {
+    pub id: i32,
+    #[serde(flatten)]
+    pub data: CardSetData,
+}
+
+/// Represents card set informations.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CardSetData {
+    pub name: String,
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub card_count: Option<i32>,
+}
+
+/// A printing of a card in a given set, with its own code (e.g. LOB-EN001) and rarity.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CardPrint {
+    pub id: i32,
+    pub card_id: i32,
+    pub code: String,
+    pub rarity: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rarity_code: Option<String>,
+    pub set: CardSet,
+}
+
+/// A new card printing to be inserted into the database.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NewCardPrint {
+    pub card_id: i32,
+    pub set_id: i32,
+    pub code: String,
+    pub rarity: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rarity_code: Option<String>,
+}
+
+/// An owned copy (or stack of identical copies) of a Yu-Gi-Oh! card.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CollectionItem {
+    pub id: i32,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub data: CollectionItemData,
+}
+
+/// A new collection item to be inserted into the database.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct NewCollectionItem {
+    #[serde(flatten)]
+    pub data: CollectionItemData,
+}
+
+/// Represents collection item informations.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionItemData {
+    pub card_id: i32,
+    /// Printing of the card (set and rarity) the copies come from, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub print_id: Option<i32>,
+    pub quantity: i32,
+    #[serde(default)]
+    pub condition: CardCondition,
+    #[serde(default)]
+    pub language: CardLanguage,
+    #[serde(default)]
+    pub first_edition: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acquisition_date: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acquisition_price: Option<Decimal>,
+}
+
+/// A photo of an owned copy of a card, such as its front, its back or its graded slab.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionScan {
+    pub id: i32,
+    pub created_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub data: CollectionScanData,
+}
+
+/// A new collection scan to be inserted into the database.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NewCollectionScan {
+    #[serde(flatten)]
+    pub data: CollectionScanData,
+}
+
+/// Represents collection scan informations. The image itself is kept in the image storage.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionScanData {
+    pub item_id: i32,
+    pub side: ScanSide,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capture_date: Option<NaiveDate>,
+    /// Dimensions of the image, in pixels
+    pub width: i32,
+    pub height: i32,
+    /// Size of the image, in bytes
+    pub size: i32,
+}
+
+/// What a collection scan shows
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSql, FromSql)]
+#[postgres(name = "ygo_scan_side", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ScanSide {
+    Front,
+    Back,
+    /// The graded slab the card is sealed in
+    Slab,
+}
+
+/// Physical card conditions (NM, LP, MP, HP, DMG)
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, ToSql, FromSql)]
+#[postgres(name = "ygo_card_condition", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum CardCondition {
+    #[default]
+    NearMint,
+    LightlyPlayed,
+    ModeratelyPlayed,
+    HeavilyPlayed,
+    Damaged,
+}
+
+/// Languages cards are printed in (English, French, Japanese, etc.)
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq, ToSql, FromSql)]
+#[postgres(name = "ygo_card_language", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum CardLanguage {
+    #[default]
+    English,
+    French,
+    German,
+    Italian,
+    Portuguese,
+    Spanish,
+    Japanese,
+    Korean,
+    Chinese,
+}
+
+/// A Yu-Gi-Oh! deck.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Deck {
+    pub id: i32,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub data: DeckData,
+}
+
+/// A new deck to be inserted into the database.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct NewDeck {
+    #[serde(flatten)]
+    pub data: DeckData,
+}
+
+/// Represents deck informations. Zones list card IDs in order, one entry per copy.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeckData {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub main: Vec<i32>,
+    #[serde(default)]
+    pub extra: Vec<i32>,
+    #[serde(default)]
+    pub side: Vec<i32>,
+}
+
+impl DeckData {
+    /// Returns the card IDs in the given zone
+    pub fn zone(&self, zone: &DeckZone) -> &[i32] {
+        match zone {
+            DeckZone::Main => &self.main,
+            DeckZone::Extra => &self.extra,
+            DeckZone::Side => &self.side,
+        }
+    }
+
+    /// Returns the card IDs in the given zone, for modification
+    pub fn zone_mut(&mut self, zone: &DeckZone) -> &mut Vec<i32> {
+        match zone {
+            DeckZone::Main => &mut self.main,
+            DeckZone::Extra => &mut self.extra,
+            DeckZone::Side => &mut self.side,
+        }
+    }
+
+    /// Iterates over every card ID of the deck, in all zones
+    pub fn card_ids(&self) -> impl Iterator<Item = i32> + '_ {
+        self.main
+            .iter()
+            .chain(self.extra.iter())
+            .chain(self.side.iter())
+            .copied()
+    }
+}
+
+/// Deck zones (Main, Extra, Side)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, ToSql, FromSql)]
+#[postgres(name = "ygo_deck_zone", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum DeckZone {
+    Main,
+    Extra,
+    Side,
+}
+
+impl DeckZone {
+    pub const ALL: [DeckZone; 3] = [DeckZone::Main, DeckZone::Extra, DeckZone::Side];
+}
+
+/// Banlist formats (TCG, OCG, Goat)
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    ToSql,
+    FromSql,
+)]
+#[postgres(name = "ygo_banlist_format", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum BanlistFormat {
+    #[default]
+    Tcg,
+    Ocg,
+    Goat,
+}
+
+/// A forbidden & limited list of a format, effective from a date
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Banlist {
+    pub id: i32,
+    pub format: BanlistFormat,
+    pub effective_date: NaiveDate,
+}
+
+/// Status of a card on a banlist
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BanStatus {
+    Forbidden,
+    Limited,
+    SemiLimited,
+    Unlimited,
+}
+
+impl BanStatus {
+    /// Maximum number of copies allowed in a deck
+    pub fn limit(&self) -> i16 {
+        match self {
+            BanStatus::Forbidden => 0,
+            BanStatus::Limited => 1,
+            BanStatus::SemiLimited => 2,
+            BanStatus::Unlimited => 3,
+        }
+    }
+
+    /// Returns the status matching a limit of copies
+    pub fn from_limit(limit: i16) -> Self {
+        match limit {
+            ..=0 => BanStatus::Forbidden,
+            1 => BanStatus::Limited,
+            2 => BanStatus::SemiLimited,
+            _ => BanStatus::Unlimited,
+        }
+    }
+}
+
+/// Marketplaces card prices come from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, ToSql, FromSql)]
+#[postgres(name = "ygo_price_source", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum PriceSource {
+    #[default]
+    Tcgplayer,
+    Cardmarket,
+    Ebay,
+    Amazon,
+    Coolstuffinc,
+}
+
+impl PriceSource {
+    pub const ALL: [PriceSource; 5] = [
+        PriceSource::Tcgplayer,
+        PriceSource::Cardmarket,
+        PriceSource::Ebay,
+        PriceSource::Amazon,
+        PriceSource::Coolstuffinc,
+    ];
+}
+
+/// Price of a card on a marketplace at a date
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CardPrice {
+    pub source: PriceSource,
+    pub date: NaiveDate,
+    pub price: Decimal,
+}
+
+/// An artwork of a card, as numbered by YGOPRODeck. Index 0 is the card's default artwork.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CardArtwork {
+    pub index: i16,
+    pub ygoprodeck_id: i32,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
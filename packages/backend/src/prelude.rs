@@ -6,6 +6,10 @@ use std::{
 use tracing::level_filters::LevelFilter;
 
 use crate::database::Pool;
+use crate::services::image::Transcoder;
+use crate::services::image_cache::ImageCache;
+use crate::services::image_storage::{ImageStorageConfig, S3Config};
+use crate::services::ygo::image::ImagePrefetch;
 
 /// Common struct for request state
 #[derive(Debug, Clone)]
@@ -13,6 +17,9 @@ pub struct AppState {
     pub config: AppConfig,
     #[allow(dead_code)] // TODO: Remove this when an endpoint uses the database
     pub db: Pool,
+    pub images: ImageCache,
+    pub prefetch: ImagePrefetch,
+    pub transcoder: Transcoder,
 }
 
 /// App configuration
@@ -31,6 +38,10 @@ pub struct AppConfig {
     // Local directories
     pub frontend_dir: String,
     pub content_dir: String,
+
+    // Where cached images are stored, and how much space they may take in bytes
+    pub image_storage: ImageStorageConfig,
+    pub image_cache_max_size: Option<u64>,
 }
 
 impl AppConfig {
@@ -55,6 +66,28 @@ impl AppConfig {
         let content_dir = env::var("CARDFOLIO_CONTENT_DIR").unwrap_or("run/content/".to_string());
         std::fs::create_dir_all(&content_dir)?;
 
+        let image_storage = match env::var("CARDFOLIO_IMAGE_STORAGE")
+            .unwrap_or("filesystem".to_string())
+            .as_str()
+        {
+            "filesystem" => ImageStorageConfig::Filesystem,
+            "s3" => ImageStorageConfig::S3(S3Config {
+                endpoint: required_var("CARDFOLIO_S3_ENDPOINT")?,
+                bucket: required_var("CARDFOLIO_S3_BUCKET")?,
+                region: env::var("CARDFOLIO_S3_REGION").unwrap_or("us-east-1".to_string()),
+                access_key: required_var("CARDFOLIO_S3_ACCESS_KEY")?,
+                secret_key: required_var("CARDFOLIO_S3_SECRET_KEY")?,
+            }),
+            other => {
+                anyhow::bail!("CARDFOLIO_IMAGE_STORAGE must be 'filesystem' or 's3', got '{other}'")
+            }
+        };
+
+        let image_cache_max_size = env::var("CARDFOLIO_IMAGE_CACHE_MAX_SIZE_MB")
+            .ok()
+            .map(|size| size.parse::<u64>().map(|size| size * 1024 * 1024))
+            .transpose()?;
+
         Ok(Self {
             log_level,
             port,
@@ -62,6 +95,8 @@ impl AppConfig {
             db_pool_size,
             frontend_dir,
             content_dir,
+            image_storage,
+            image_cache_max_size,
         })
     }
 
@@ -74,6 +109,14 @@ impl AppConfig {
     }
 }
 
+/// Utility to read an environment variable that must be set when its feature is used
+fn required_var(name: &str) -> anyhow::Result<String> {
+    env::var(name).map_err(|error| match error {
+        VarError::NotPresent => anyhow::anyhow!("{name} must be set"),
+        VarError::NotUnicode(_) => anyhow::anyhow!("{name} must be valid UTF-8"),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use temp_env::with_vars;
@@ -148,4 +191,88 @@ mod tests {
             },
         );
     }
+
+    #[test]
+    fn test_app_config_image_storage() {
+        with_vars(
+            [
+                ("CARDFOLIO_DB", Some("postgres://localhost:5432/cardfolio")),
+                ("CARDFOLIO_IMAGE_STORAGE", None::<&str>),
+            ],
+            || {
+                let config = AppConfig::from_env().unwrap();
+                assert!(matches!(
+                    config.image_storage,
+                    ImageStorageConfig::Filesystem
+                ));
+            },
+        );
+
+        with_vars(
+            [
+                ("CARDFOLIO_DB", Some("postgres://localhost:5432/cardfolio")),
+                ("CARDFOLIO_IMAGE_CACHE_MAX_SIZE_MB", None::<&str>),
+            ],
+            || {
+                let config = AppConfig::from_env().unwrap();
+                assert_eq!(config.image_cache_max_size, None);
+            },
+        );
+
+        with_vars(
+            [
+                ("CARDFOLIO_DB", Some("postgres://localhost:5432/cardfolio")),
+                ("CARDFOLIO_IMAGE_CACHE_MAX_SIZE_MB", Some("512")),
+            ],
+            || {
+                let config = AppConfig::from_env().unwrap();
+                assert_eq!(config.image_cache_max_size, Some(512 * 1024 * 1024));
+            },
+        );
+
+        with_vars(
+            [
+                ("CARDFOLIO_DB", Some("postgres://localhost:5432/cardfolio")),
+                ("CARDFOLIO_IMAGE_STORAGE", Some("s3")),
+                ("CARDFOLIO_S3_ENDPOINT", Some("http://localhost:9000")),
+                ("CARDFOLIO_S3_BUCKET", Some("cardfolio")),
+                ("CARDFOLIO_S3_REGION", None),
+                ("CARDFOLIO_S3_ACCESS_KEY", Some("minio")),
+                ("CARDFOLIO_S3_SECRET_KEY", Some("minio-secret")),
+            ],
+            || {
+                let config = AppConfig::from_env().unwrap();
+                let ImageStorageConfig::S3(s3) = config.image_storage else {
+                    panic!("expected S3 storage");
+                };
+                assert_eq!(s3.endpoint, "http://localhost:9000");
+                assert_eq!(s3.bucket, "cardfolio");
+                assert_eq!(s3.region, "us-east-1");
+                assert_eq!(s3.access_key, "minio");
+                assert_eq!(s3.secret_key, "minio-secret");
+            },
+        );
+
+        with_vars(
+            [
+                ("CARDFOLIO_DB", Some("postgres://localhost:5432/cardfolio")),
+                ("CARDFOLIO_IMAGE_STORAGE", Some("s3")),
+                ("CARDFOLIO_S3_ENDPOINT", None::<&str>),
+            ],
+            || {
+                let err = AppConfig::from_env().unwrap_err();
+                assert_eq!(err.to_string(), "CARDFOLIO_S3_ENDPOINT must be set");
+            },
+        );
+
+        with_vars(
+            [
+                ("CARDFOLIO_DB", Some("postgres://localhost:5432/cardfolio")),
+                ("CARDFOLIO_IMAGE_STORAGE", Some("ftp")),
+            ],
+            || {
+                assert!(AppConfig::from_env().is_err());
+            },
+        );
+    }
 }
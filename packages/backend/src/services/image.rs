@@ -0,0 +1,332 @@
+use std::io::Cursor;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use image::{ImageEncoder, codecs, imageops::FilterType};
+use sha2::{Digest, Sha256};
+use tokio::sync::Semaphore;
+
+/// Image formats images can be served as. YGOPRODeck images are JPEG.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Jpeg,
+    Webp,
+    Avif,
+}
+
+impl ImageFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Webp => "image/webp",
+            ImageFormat::Avif => "image/avif",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Avif => "avif",
+        }
+    }
+
+    /// Picks the smallest format accepted by the client, from an `Accept` header value.
+    /// Falls back to JPEG, which every client can display.
+    pub fn negotiate(accept: &str) -> ImageFormat {
+        let accepted = |content_type: &str| {
+            accept.split(',').any(|range| {
+                let mut parts = range.split(';').map(str::trim);
+                let media_range = parts.next().unwrap_or_default();
+
+                // Media ranges with a quality of 0 are explicitly refused
+                let refused = parts.any(|param| {
+                    param
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        .is_some_and(|q| q <= 0.0)
+                });
+
+                media_range.eq_ignore_ascii_case(content_type) && !refused
+            })
+        };
+
+        [ImageFormat::Avif, ImageFormat::Webp]
+            .into_iter()
+            .find(|format| accepted(format.content_type()))
+            .unwrap_or(ImageFormat::Jpeg)
+    }
+}
+
+/// Resizes an image down to the given width, keeping its aspect ratio, and encodes it in the
+/// given format. Images are never upscaled.
+///
+/// This is CPU-bound, and should be run outside of the async runtime.
+pub fn transcode(data: &[u8], width: Option<u32>, format: ImageFormat) -> anyhow::Result<Vec<u8>> {
+    let mut image = image::load_from_memory(data)?;
+
+    if let Some(width) = width
+        && width < image.width()
+    {
+        let height = (image.height() as u64 * width as u64 / image.width() as u64).max(1) as u32;
+        image = image.resize_exact(width, height, FilterType::Lanczos3);
+    }
+
+    let image = image.to_rgb8();
+    let mut data = Cursor::new(Vec::new());
+    match format {
+        ImageFormat::Jpeg => codecs::jpeg::JpegEncoder::new_with_quality(&mut data, 90)
+            .write_image(
+                &image,
+                image.width(),
+                image.height(),
+                image::ExtendedColorType::Rgb8,
+            )?,
+        ImageFormat::Webp => {
+            let encoded = webp::Encoder::from_rgb(&image, image.width(), image.height());
+            data.get_mut().extend_from_slice(&encoded.encode(80.0));
+        }
+        ImageFormat::Avif => codecs::avif::AvifEncoder::new_with_speed_quality(&mut data, 8, 80)
+            .write_image(
+                &image,
+                image.width(),
+                image.height(),
+                image::ExtendedColorType::Rgb8,
+            )?,
+    }
+
+    Ok(data.into_inner())
+}
+
+/// Maximum number of images transcoded at once
+const MAX_CONCURRENT_TRANSCODES: usize = 4;
+
+/// Transcodes images outside of the async runtime, a few at a time, so that requests for many
+/// image variants can't take every CPU of the server.
+#[derive(Debug, Clone)]
+pub struct Transcoder {
+    permits: Arc<Semaphore>,
+}
+
+impl Default for Transcoder {
+    fn default() -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(MAX_CONCURRENT_TRANSCODES)),
+        }
+    }
+}
+
+impl Transcoder {
+    /// Resizes and encodes an image once a permit is available. See [`transcode`].
+    pub async fn transcode(
+        &self,
+        data: Vec<u8>,
+        width: Option<u32>,
+        format: ImageFormat,
+    ) -> anyhow::Result<Vec<u8>> {
+        let _permit = self.permits.acquire().await?;
+
+        tokio::task::spawn_blocking(move || transcode(&data, width, format)).await?
+    }
+}
+
+/// Reads the width and height of an image from its header, without decoding it
+pub fn dimensions(data: &[u8]) -> anyhow::Result<(u32, u32)> {
+    let dimensions = image::ImageReader::new(Cursor::new(data))
+        .with_guessed_format()?
+        .into_dimensions()?;
+
+    Ok(dimensions)
+}
+
+/// Formats of the images users can upload
+const UPLOAD_FORMATS: [image::ImageFormat; 3] = [
+    image::ImageFormat::Jpeg,
+    image::ImageFormat::Png,
+    image::ImageFormat::WebP,
+];
+
+/// Checks that an uploaded image is a JPEG, PNG or WebP image that can be decoded, and that its
+/// width and height are in the given range. Returns its width and height.
+///
+/// Dimensions are checked before decoding the image, so that huge images are not decoded.
+/// This is CPU-bound, and should be run outside of the async runtime.
+pub fn validate_upload(data: &[u8], dimensions: RangeInclusive<u32>) -> Result<(u32, u32), String> {
+    let format = image::guess_format(data)
+        .ok()
+        .filter(|format| UPLOAD_FORMATS.contains(format))
+        .ok_or("image must be a JPEG, PNG or WebP image")?;
+
+    let (width, height) = image::ImageReader::with_format(Cursor::new(data), format)
+        .into_dimensions()
+        .map_err(|err| format!("image cannot be read: {err}"))?;
+    if !dimensions.contains(&width) || !dimensions.contains(&height) {
+        return Err(format!(
+            "image width and height must be between {} and {} pixels, got {width}x{height}",
+            dimensions.start(),
+            dimensions.end()
+        ));
+    }
+
+    // Truncated or corrupted images only fail once decoded
+    image::load_from_memory_with_format(data, format)
+        .map_err(|err| format!("image cannot be read: {err}"))?;
+
+    Ok((width, height))
+}
+
+/// Detects the content type of an image from its content, rather than trusting where it comes from
+pub fn content_type(data: &[u8]) -> &'static str {
+    image::guess_format(data)
+        .map(|format| format.to_mime_type())
+        .unwrap_or("application/octet-stream")
+}
+
+/// Hashes the content of an image, to be used as a strong ETag
+pub fn content_hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        let image = image::RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([(x * 8) as u8, (y * 8) as u8, 128])
+        });
+
+        let mut data = Cursor::new(Vec::new());
+        image
+            .write_to(&mut data, image::ImageFormat::Jpeg)
+            .expect("jpeg");
+        data.into_inner()
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let cases = [
+            (
+                "image/avif,image/webp,image/apng,*/*;q=0.8",
+                ImageFormat::Avif,
+            ),
+            ("image/webp,*/*", ImageFormat::Webp),
+            ("image/avif;q=0, image/webp;q=0.9", ImageFormat::Webp),
+            ("image/*", ImageFormat::Jpeg),
+            ("*/*", ImageFormat::Jpeg),
+            ("", ImageFormat::Jpeg),
+        ];
+        for (accept, format) in cases {
+            assert_eq!(ImageFormat::negotiate(accept), format, "{accept}");
+        }
+    }
+
+    #[test]
+    fn test_transcode_resizes_without_upscaling() {
+        let data = jpeg(40, 58);
+
+        let resized = transcode(&data, Some(20), ImageFormat::Webp).expect("webp");
+        let image = webp::Decoder::new(&resized).decode().expect("decode webp");
+        assert_eq!((image.width(), image.height()), (20, 29));
+
+        let original = transcode(&data, Some(400), ImageFormat::Jpeg).expect("jpeg");
+        let image = image::load_from_memory_with_format(&original, image::ImageFormat::Jpeg)
+            .expect("decode jpeg");
+        assert_eq!((image.width(), image.height()), (40, 58));
+    }
+
+    #[tokio::test]
+    async fn test_transcoder() {
+        let transcoder = Transcoder::default();
+
+        let transcoded = futures_util::future::try_join_all(
+            (0..MAX_CONCURRENT_TRANSCODES * 2)
+                .map(|_| transcoder.transcode(jpeg(40, 58), Some(20), ImageFormat::Webp)),
+        )
+        .await
+        .expect("webp");
+        for data in transcoded {
+            let image = webp::Decoder::new(&data).decode().expect("decode webp");
+            assert_eq!((image.width(), image.height()), (20, 29));
+        }
+        assert_eq!(dimensions(&jpeg(40, 58)).unwrap(), (40, 58));
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(content_type(&jpeg(4, 4)), "image/jpeg");
+
+        let webp = transcode(&jpeg(4, 4), None, ImageFormat::Webp).expect("webp");
+        assert_eq!(content_type(&webp), "image/webp");
+
+        let mut png = Cursor::new(Vec::new());
+        image::RgbImage::new(4, 4)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .expect("png");
+        assert_eq!(content_type(png.get_ref()), "image/png");
+
+        assert_eq!(content_type(b"<html>"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(
+            content_hash(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_transcode_to_avif() {
+        let data = transcode(&jpeg(16, 16), None, ImageFormat::Avif).expect("avif");
+
+        // AVIF files are ISO-BMFF files with an avif brand
+        assert_eq!(&data[4..12], b"ftypavif");
+    }
+
+    #[test]
+    fn test_validate_upload() {
+        let valid = jpeg(100, 145);
+        assert_eq!(validate_upload(&valid, 64..=4096), Ok((100, 145)));
+
+        let mut png = Cursor::new(Vec::new());
+        image::RgbImage::new(64, 64)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .expect("png");
+        assert_eq!(validate_upload(png.get_ref(), 64..=4096), Ok((64, 64)));
+
+        assert_eq!(
+            validate_upload(b"not an image", 64..=4096),
+            Err("image must be a JPEG, PNG or WebP image".to_string())
+        );
+
+        let mut gif = Cursor::new(Vec::new());
+        image::RgbaImage::new(100, 100)
+            .write_to(&mut gif, image::ImageFormat::Gif)
+            .ok();
+        assert!(validate_upload(gif.get_ref(), 64..=4096).is_err());
+
+        assert_eq!(
+            validate_upload(&jpeg(32, 145), 64..=4096),
+            Err(
+                "image width and height must be between 64 and 4096 pixels, got 32x145".to_string()
+            )
+        );
+
+        let mut png = Cursor::new(Vec::new());
+        image::RgbImage::from_fn(100, 145, |x, y| {
+            image::Rgb([x as u8, y as u8, (x * y) as u8])
+        })
+        .write_to(&mut png, image::ImageFormat::Png)
+        .expect("png");
+        let truncated = &png.get_ref()[..png.get_ref().len() / 2];
+        assert!(
+            validate_upload(truncated, 64..=4096)
+                .unwrap_err()
+                .starts_with("image cannot be read")
+        );
+    }
+}
@@ -0,0 +1,622 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::services::image;
+use crate::services::image_storage::ImageStorage;
+
+/// How long images missing upstream are remembered, before trying to retrieve them again
+const MISSING_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a listing of the stored images is trusted. Other instances sharing the storage may
+/// store or evict images meanwhile, so the cache size is computed from a new listing once it's
+/// older than that.
+const LISTING_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Prefix of the keys of cached images. They are copies that can be created again on demand, so
+/// that they may be evicted when the cache is full.
+pub const CACHE_PREFIX: &str = "card/";
+
+/// An image from the cache, with the hash of its content
+#[derive(Debug, Clone)]
+pub struct CachedImage {
+    pub data: Vec<u8>,
+    pub hash: String,
+}
+
+/// Statistics of the image cache
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    /// Cached images
+    pub images: u64,
+    /// Images remembered as missing upstream
+    pub missing: u64,
+    /// Size of the cached images and their sidecar files, in bytes
+    pub size: u64,
+    pub max_size: Option<u64>,
+    /// Images found in the cache when requested, since startup
+    pub hits: u64,
+    /// Images created because they were not cached, since startup
+    pub misses: u64,
+    /// Images evicted to stay under the maximum size, since startup
+    pub evictions: u64,
+    /// When the least recently used image was last used
+    pub least_recently_used_at: Option<DateTime<Utc>>,
+}
+
+/// Cache of images, kept in an image storage by key.
+///
+/// Images missing from the cache are created once, even when requested concurrently, and written
+/// atomically so that they are never read half-written.
+///
+/// When the cache grows over its maximum size, the least recently used images are evicted. Its
+/// size is computed from a listing of the stored images, renewed every few minutes, so that
+/// instances sharing a storage account for each other's images. Uses are tracked in memory by
+/// each instance, so images used by other instances, or before a restart, are considered last
+/// used when stored.
+#[derive(Debug, Clone)]
+pub struct ImageCache {
+    storage: Arc<dyn ImageStorage>,
+    /// Maximum size of the cache in bytes, unlimited if not set
+    max_size: Option<u64>,
+    /// How long a listing of the stored images is trusted
+    listing_ttl: Duration,
+    index: Arc<Mutex<CacheIndex>>,
+    /// Held while listing stored images or evicting some
+    maintenance: Arc<tokio::sync::Mutex<()>>,
+    in_flight: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+/// Size and last use of cached images, by key
+#[derive(Debug, Default)]
+struct CacheIndex {
+    /// When stored images were last listed. Until then, only images used since startup are known.
+    listed_at: Option<Instant>,
+    entries: HashMap<String, IndexEntry>,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+#[derive(Debug, Clone)]
+struct IndexEntry {
+    /// Size of the image and its sidecar files
+    size: u64,
+    last_used: DateTime<Utc>,
+    /// Whether the image is stored, rather than only marked as missing
+    stored: bool,
+}
+
+impl ImageCache {
+    pub fn new(storage: Arc<dyn ImageStorage>, max_size: Option<u64>) -> Self {
+        Self {
+            storage,
+            max_size,
+            listing_ttl: LISTING_TTL,
+            index: Default::default(),
+            maintenance: Default::default(),
+            in_flight: Default::default(),
+        }
+    }
+
+    /// The storage images are kept in
+    pub fn storage(&self) -> &dyn ImageStorage {
+        &*self.storage
+    }
+
+    /// Reads an image from the cache, or creates and caches it when missing.
+    ///
+    /// Concurrent calls for the same image wait for the first one to create it. When `create`
+    /// finds no image, that's remembered for a while, and `None` is returned without creating it.
+    pub async fn get_or_create<F, Fut, E>(
+        &self,
+        key: &str,
+        create: F,
+    ) -> Result<Option<CachedImage>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<Vec<u8>>, E>>,
+        E: From<std::io::Error>,
+    {
+        if let Some(image) = self.read(key).await? {
+            self.update_index(|index| index.hits += 1);
+            return Ok(Some(image));
+        }
+
+        let lock = self.lock_for(key);
+        let result = async {
+            let _guard = lock.lock().await;
+
+            // The image may have been created while waiting for the lock
+            if let Some(image) = self.read(key).await? {
+                self.update_index(|index| index.hits += 1);
+                return Ok(Some(image));
+            }
+            if self.is_missing(key).await {
+                return Ok(None);
+            }
+
+            self.update_index(|index| index.misses += 1);
+            match create().await? {
+                Some(data) => Ok(Some(self.save(key, data).await?)),
+                None => {
+                    let now = Utc::now().timestamp().to_string();
+                    self.storage
+                        .write(&get_missing_key(key), now.as_bytes())
+                        .await?;
+                    self.record(key, now.len() as u64, false);
+                    self.evict(key).await;
+                    Ok(None)
+                }
+            }
+        }
+        .await;
+
+        self.release(key, lock);
+        result
+    }
+
+    /// Reads an image from the cache, if cached.
+    /// The content hash of images cached without one is computed and saved.
+    pub async fn read(&self, key: &str) -> std::io::Result<Option<CachedImage>> {
+        let Some(data) = self.storage.read(key).await? else {
+            return Ok(None);
+        };
+
+        let hash_key = get_hash_key(key);
+        let hash = match self.storage.read(&hash_key).await {
+            Ok(Some(hash)) => String::from_utf8_lossy(&hash).trim().to_string(),
+            _ => {
+                let hash = image::content_hash(&data);
+                self.storage.write(&hash_key, hash.as_bytes()).await?;
+                hash
+            }
+        };
+
+        self.record(key, (data.len() + hash.len()) as u64, true);
+
+        Ok(Some(CachedImage { data, hash }))
+    }
+
+    /// Saves an image to the cache, along with its content hash.
+    /// Least recently used images are evicted if the cache grows over its maximum size.
+    pub async fn save(&self, key: &str, data: Vec<u8>) -> std::io::Result<CachedImage> {
+        let hash = image::content_hash(&data);
+
+        // Save the hash first, so that cached images always have one
+        self.storage
+            .write(&get_hash_key(key), hash.as_bytes())
+            .await?;
+        self.storage.write(key, &data).await?;
+
+        self.record(key, (data.len() + hash.len()) as u64, true);
+        self.evict(key).await;
+
+        Ok(CachedImage { data, hash })
+    }
+
+    /// Deletes an image from the cache, along with its content hash and missing marker
+    pub async fn delete(&self, key: &str) -> std::io::Result<()> {
+        self.update_index(|index| index.entries.remove(key));
+        self.delete_stored(key).await
+    }
+
+    /// Returns statistics about the cached images, listing them unless done recently
+    pub async fn stats(&self) -> std::io::Result<CacheStats> {
+        {
+            let _guard = self.maintenance.lock().await;
+            self.load_index().await?;
+        }
+
+        let index = self.index.lock().expect("image cache index lock poisoned");
+        let entries = index.entries.values();
+        Ok(CacheStats {
+            images: entries.clone().filter(|entry| entry.stored).count() as u64,
+            missing: entries.clone().filter(|entry| !entry.stored).count() as u64,
+            size: entries.clone().map(|entry| entry.size).sum(),
+            max_size: self.max_size,
+            hits: index.hits,
+            misses: index.misses,
+            evictions: index.evictions,
+            least_recently_used_at: entries.map(|entry| entry.last_used).min(),
+        })
+    }
+
+    /// Evicts the least recently used images until the cache is under its maximum size,
+    /// keeping the image just used. Does nothing if another call is already evicting images.
+    async fn evict(&self, used_key: &str) {
+        let Some(max_size) = self.max_size else {
+            return;
+        };
+        if self.total_size().is_some_and(|size| size <= max_size) {
+            return;
+        }
+        let Ok(_guard) = self.maintenance.try_lock() else {
+            return;
+        };
+
+        if let Err(err) = self.load_index().await {
+            tracing::warn!("Could not list cached images: {err}");
+            return;
+        }
+
+        let evicted = {
+            let mut index = self.index.lock().expect("image cache index lock poisoned");
+            let mut size: u64 = index.entries.values().map(|entry| entry.size).sum();
+
+            let mut entries: Vec<_> = index
+                .entries
+                .iter()
+                .filter(|(key, _)| key.as_str() != used_key)
+                .map(|(key, entry)| (entry.last_used, entry.size, key.clone()))
+                .collect();
+            entries.sort();
+
+            let mut evicted = Vec::new();
+            for (_, entry_size, key) in entries {
+                if size <= max_size {
+                    break;
+                }
+                size -= entry_size;
+                index.entries.remove(&key);
+                evicted.push(key);
+            }
+            index.evictions += evicted.len() as u64;
+            evicted
+        };
+
+        for key in evicted {
+            tracing::debug!("Evicting cached image {key}");
+            if let Err(err) = self.delete_stored(&key).await {
+                tracing::warn!("Could not evict cached image {key}: {err}");
+            }
+        }
+    }
+
+    /// Utility to get the total size of cached images, if listed recently
+    fn total_size(&self) -> Option<u64> {
+        let index = self.index.lock().expect("image cache index lock poisoned");
+        self.is_listed(&index)
+            .then(|| index.entries.values().map(|entry| entry.size).sum())
+    }
+
+    /// Utility to check whether stored images were listed recently
+    fn is_listed(&self, index: &CacheIndex) -> bool {
+        index
+            .listed_at
+            .is_some_and(|listed_at| listed_at.elapsed() < self.listing_ttl)
+    }
+
+    /// Lists stored images into the index, unless done recently. Images no longer stored are
+    /// dropped from it. Uses since startup are kept, other images being considered last used
+    /// when stored.
+    async fn load_index(&self) -> std::io::Result<()> {
+        if self.update_index(|index| self.is_listed(index)) {
+            return Ok(());
+        }
+
+        let mut entries: HashMap<String, IndexEntry> = HashMap::new();
+        for object in self.storage.list(CACHE_PREFIX).await? {
+            let key = get_image_key(&object.key);
+            let entry = entries.entry(key.to_string()).or_insert(IndexEntry {
+                size: 0,
+                last_used: object.modified,
+                stored: false,
+            });
+            entry.size += object.size;
+            entry.last_used = entry.last_used.max(object.modified);
+            entry.stored |= key == object.key;
+        }
+
+        self.update_index(|index| {
+            for (key, entry) in entries.iter_mut() {
+                if let Some(used) = index.entries.get(key) {
+                    entry.last_used = entry.last_used.max(used.last_used);
+                }
+            }
+            index.entries = entries;
+            index.listed_at = Some(Instant::now());
+        });
+
+        Ok(())
+    }
+
+    /// Utility to record the use of an image in the index.
+    /// Images stored outside of the cache prefix are not accounted for, and never evicted.
+    fn record(&self, key: &str, size: u64, stored: bool) {
+        if !key.starts_with(CACHE_PREFIX) {
+            return;
+        }
+
+        self.update_index(|index| {
+            let entry = index.entries.entry(key.to_string()).or_insert(IndexEntry {
+                size,
+                last_used: Utc::now(),
+                stored,
+            });
+            entry.size = size;
+            entry.last_used = Utc::now();
+            entry.stored = stored;
+        });
+    }
+
+    /// Utility to update the index
+    fn update_index<T>(&self, f: impl FnOnce(&mut CacheIndex) -> T) -> T {
+        f(&mut self.index.lock().expect("image cache index lock poisoned"))
+    }
+
+    /// Utility to delete an image and its sidecar files from the storage
+    async fn delete_stored(&self, key: &str) -> std::io::Result<()> {
+        self.storage.delete(key).await?;
+        self.storage.delete(&get_hash_key(key)).await?;
+        self.storage.delete(&get_missing_key(key)).await
+    }
+
+    /// Whether an image was found missing upstream recently
+    async fn is_missing(&self, key: &str) -> bool {
+        let Ok(Some(marker)) = self.storage.read(&get_missing_key(key)).await else {
+            return false;
+        };
+
+        String::from_utf8_lossy(&marker)
+            .trim()
+            .parse::<i64>()
+            .is_ok_and(|marked_at| {
+                let age = Utc::now().timestamp() - marked_at;
+                (0..MISSING_TTL.as_secs() as i64).contains(&age)
+            })
+    }
+
+    /// Utility to get the lock of an image, shared by concurrent calls
+    fn lock_for(&self, key: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .expect("in-flight images lock poisoned");
+        in_flight.entry(key.to_string()).or_default().clone()
+    }
+
+    /// Utility to forget the lock of an image once no call uses it anymore
+    fn release(&self, key: &str, lock: Arc<tokio::sync::Mutex<()>>) {
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .expect("in-flight images lock poisoned");
+
+        // Held by the map and by this call only
+        if Arc::strong_count(&lock) == 2 {
+            in_flight.remove(key);
+        }
+    }
+}
+
+/// Utility to get the key an image's content hash is stored at, next to the image
+pub fn get_hash_key(key: &str) -> String {
+    format!("{key}.sha256")
+}
+
+/// Utility to get the key of the marker of an image missing upstream
+pub fn get_missing_key(key: &str) -> String {
+    format!("{key}.missing")
+}
+
+/// Utility to get the key of the image a stored key belongs to, sidecar files included
+pub fn get_image_key(key: &str) -> &str {
+    key.strip_suffix(".sha256")
+        .or_else(|| key.strip_suffix(".missing"))
+        .unwrap_or(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::services::image_storage::FilesystemStorage;
+    use crate::test_utils::test_dir;
+
+    async fn list_dir(dir: &Path) -> Vec<String> {
+        let mut entries = tokio::fs::read_dir(dir).await.unwrap();
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_get_or_create_coalesces_concurrent_calls() {
+        let dir = test_dir("coalesce");
+        let cache = ImageCache::new(Arc::new(FilesystemStorage::new(dir.path())), None);
+        let created = AtomicUsize::new(0);
+
+        let get = || {
+            cache.get_or_create("1.jpg", async || {
+                created.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok::<_, std::io::Error>(Some(b"image".to_vec()))
+            })
+        };
+        let results = futures_util::future::join_all((0..10).map(|_| get())).await;
+
+        assert_eq!(created.load(Ordering::SeqCst), 1);
+        for result in results {
+            let image = result.unwrap().expect("image");
+            assert_eq!(image.data, b"image");
+            assert_eq!(image.hash, image::content_hash(b"image"));
+        }
+
+        // Only the image and its hash are left, and locks are released
+        assert_eq!(list_dir(dir.path()).await, vec!["1.jpg", "1.jpg.sha256"]);
+        assert!(cache.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_or_create_remembers_missing_images() {
+        let dir = test_dir("missing");
+        let cache = ImageCache::new(Arc::new(FilesystemStorage::new(dir.path())), None);
+        let created = AtomicUsize::new(0);
+
+        for _ in 0..2 {
+            let image = cache
+                .get_or_create("1.jpg", async || {
+                    created.fetch_add(1, Ordering::SeqCst);
+                    Ok::<_, std::io::Error>(None)
+                })
+                .await
+                .unwrap();
+            assert!(image.is_none());
+        }
+
+        assert_eq!(created.load(Ordering::SeqCst), 1);
+        assert_eq!(list_dir(dir.path()).await, vec!["1.jpg.missing"]);
+
+        // Until the marker expires
+        let expired = Utc::now().timestamp() - MISSING_TTL.as_secs() as i64;
+        cache
+            .storage()
+            .write("1.jpg.missing", expired.to_string().as_bytes())
+            .await
+            .unwrap();
+        cache
+            .get_or_create("1.jpg", async || {
+                created.fetch_add(1, Ordering::SeqCst);
+                Ok::<_, std::io::Error>(None)
+            })
+            .await
+            .unwrap();
+        assert_eq!(created.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_or_create_does_not_cache_errors() {
+        let dir = test_dir("errors");
+        let cache = ImageCache::new(Arc::new(FilesystemStorage::new(dir.path())), None);
+
+        let result = cache
+            .get_or_create("1.jpg", async || {
+                Err(std::io::Error::other("upstream unavailable"))
+            })
+            .await;
+        assert!(result.is_err());
+        assert!(list_dir(dir.path()).await.is_empty());
+
+        let image = cache
+            .get_or_create("1.jpg", async || {
+                Ok::<_, std::io::Error>(Some(b"image".to_vec()))
+            })
+            .await
+            .unwrap();
+        assert!(image.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_save_evicts_least_recently_used_images() {
+        let dir = test_dir("evict");
+        let storage = Arc::new(FilesystemStorage::new(dir.path()));
+
+        // Images take 69 bytes along with their hash, so that two of them fit
+        let cache = ImageCache::new(storage.clone(), Some(150));
+        for key in ["card/1.jpg", "card/2.jpg"] {
+            cache
+                .get_or_create(key, async || {
+                    Ok::<_, std::io::Error>(Some(b"image".to_vec()))
+                })
+                .await
+                .unwrap();
+        }
+        cache
+            .get_or_create("card/1.jpg", async || {
+                Ok::<_, std::io::Error>(Some(b"other".to_vec()))
+            })
+            .await
+            .unwrap()
+            .expect("image");
+        cache
+            .get_or_create("card/3.jpg", async || {
+                Ok::<_, std::io::Error>(Some(b"image".to_vec()))
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            list_dir(&dir.path().join("card")).await,
+            vec!["1.jpg", "1.jpg.sha256", "3.jpg", "3.jpg.sha256"]
+        );
+
+        let stats = cache.stats().await.unwrap();
+        assert_eq!(stats.images, 2);
+        assert_eq!(stats.missing, 0);
+        assert_eq!(stats.size, 138);
+        assert_eq!(stats.max_size, Some(150));
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 3);
+        assert_eq!(stats.evictions, 1);
+    }
+
+    #[tokio::test]
+    async fn test_stats_lists_stored_images() {
+        let dir = test_dir("stats");
+        let storage = Arc::new(FilesystemStorage::new(dir.path()));
+
+        // Images stored before startup are accounted for
+        let cache = ImageCache::new(storage.clone(), None);
+        cache.save("card/1.jpg", b"image".to_vec()).await.unwrap();
+        cache
+            .get_or_create("card/2.jpg", async || Ok::<_, std::io::Error>(None))
+            .await
+            .unwrap();
+        storage.write("prefetch_checkpoint", b"2").await.unwrap();
+
+        let cache = ImageCache::new(storage, None);
+        let stats = cache.stats().await.unwrap();
+        assert_eq!(stats.images, 1);
+        assert_eq!(stats.missing, 1);
+        assert_eq!(stats.size, 69 + 10);
+        assert_eq!(stats.max_size, None);
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (0, 0, 0));
+        assert!(stats.least_recently_used_at.is_some());
+
+        // Deleted images are no longer accounted for
+        cache.delete("card/1.jpg").await.unwrap();
+        cache.delete("card/2.jpg").await.unwrap();
+        let stats = cache.stats().await.unwrap();
+        assert_eq!((stats.images, stats.missing, stats.size), (0, 0, 0));
+        assert!(list_dir(&dir.path().join("card")).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_evict_accounts_for_images_of_other_instances() {
+        let dir = test_dir("evict-shared");
+        let storage = Arc::new(FilesystemStorage::new(dir.path()));
+
+        // Two instances share the storage, each listing it anew every time
+        let mut first = ImageCache::new(storage.clone(), Some(150));
+        let mut second = ImageCache::new(storage.clone(), Some(150));
+        first.listing_ttl = Duration::ZERO;
+        second.listing_ttl = Duration::ZERO;
+
+        first.save("card/1.jpg", b"image".to_vec()).await.unwrap();
+        second.save("card/2.jpg", b"image".to_vec()).await.unwrap();
+        assert_eq!(second.stats().await.unwrap().size, 138);
+
+        // The least recently used image is evicted, whichever instance saved it
+        first.save("card/3.jpg", b"image".to_vec()).await.unwrap();
+        assert_eq!(
+            list_dir(&dir.path().join("card")).await,
+            vec!["2.jpg", "2.jpg.sha256", "3.jpg", "3.jpg.sha256"]
+        );
+
+        // Images evicted by another instance are no longer accounted for
+        second.save("card/4.jpg", b"image".to_vec()).await.unwrap();
+        let stats = first.stats().await.unwrap();
+        assert_eq!((stats.images, stats.size), (2, 138));
+        assert_eq!(stats.evictions, 1);
+    }
+}
@@ -0,0 +1,185 @@
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+
+use super::{ImageStorage, StoredObject};
+
+/// Stores images as files in a directory, keys being paths relative to it
+#[derive(Debug, Clone)]
+pub struct FilesystemStorage {
+    root: PathBuf,
+}
+
+impl FilesystemStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Utility to get the path of the file a key is stored in
+    fn get_path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+}
+
+impl ImageStorage for FilesystemStorage {
+    fn read<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<Option<Vec<u8>>>> {
+        Box::pin(async move {
+            match tokio::fs::read(self.get_path(key)).await {
+                Ok(data) => Ok(Some(data)),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err),
+            }
+        })
+    }
+
+    fn write<'a>(&'a self, key: &'a str, data: &'a [u8]) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(write_atomically(self.get_path(key), data))
+    }
+
+    fn exists<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<bool>> {
+        Box::pin(tokio::fs::try_exists(self.get_path(key)))
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<()>> {
+        Box::pin(async move {
+            match tokio::fs::remove_file(self.get_path(key)).await {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            }
+        })
+    }
+
+    fn list<'a>(&'a self, prefix: &'a str) -> BoxFuture<'a, io::Result<Vec<StoredObject>>> {
+        Box::pin(async move {
+            // Walk the deepest directory containing every key with the prefix
+            let dir = match prefix.rfind('/') {
+                Some(end) => &prefix[..end],
+                None => "",
+            };
+
+            let mut objects = Vec::new();
+            let mut dirs = vec![(self.get_path(dir), dir.to_string())];
+            while let Some((path, dir)) = dirs.pop() {
+                let mut entries = match tokio::fs::read_dir(&path).await {
+                    Ok(entries) => entries,
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                    Err(err) => return Err(err),
+                };
+
+                while let Some(entry) = entries.next_entry().await? {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    let key = match dir.as_str() {
+                        "" => name,
+                        dir => format!("{dir}/{name}"),
+                    };
+
+                    let metadata = entry.metadata().await?;
+                    if metadata.is_dir() {
+                        dirs.push((entry.path(), key));
+                    } else if key.starts_with(prefix) && !key.ends_with(".tmp") {
+                        objects.push(StoredObject {
+                            key,
+                            size: metadata.len(),
+                            modified: DateTime::<Utc>::from(metadata.modified()?),
+                        });
+                    }
+                }
+            }
+
+            Ok(objects)
+        })
+    }
+}
+
+/// Writes a file through a temporary file renamed in place, so that readers see either the
+/// previous file or the whole new one
+async fn write_atomically(path: PathBuf, data: &[u8]) -> io::Result<()> {
+    static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    // Make sure the parent directory exists
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let counter = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let temp_path = get_temp_path(&path, counter);
+
+    tokio::fs::write(&temp_path, data).await?;
+    if let Err(err) = tokio::fs::rename(&temp_path, &path).await {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// Utility to get the path of a temporary file next to a file, unique to this process
+fn get_temp_path(path: &Path, counter: u64) -> PathBuf {
+    let mut temp_path: OsString = path.as_os_str().to_owned();
+    temp_path.push(format!(".{}-{counter}.tmp", std::process::id()));
+    temp_path.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_dir;
+
+    #[tokio::test]
+    async fn test_filesystem_storage() {
+        let dir = test_dir("fs-storage");
+        let storage = FilesystemStorage::new(dir.path());
+
+        assert_eq!(storage.read("card/1.jpg").await.unwrap(), None);
+        assert!(!storage.exists("card/1.jpg").await.unwrap());
+
+        // Parent directories are created as needed
+        storage.write("card/1.jpg", b"image").await.unwrap();
+        storage.write("card/1.jpg", b"new image").await.unwrap();
+        assert_eq!(
+            storage.read("card/1.jpg").await.unwrap(),
+            Some(b"new image".to_vec())
+        );
+        assert!(storage.exists("card/1.jpg").await.unwrap());
+
+        // No temporary file is left behind
+        let mut entries = tokio::fs::read_dir(dir.path().join("card")).await.unwrap();
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            names.push(entry.file_name());
+        }
+        assert_eq!(names, vec!["1.jpg"]);
+
+        // Keys are listed by prefix, without temporary files
+        storage.write("card/12.jpg", b"other").await.unwrap();
+        storage.write("other.txt", b"other").await.unwrap();
+        tokio::fs::write(dir.path().join("card/2.jpg.1-0.tmp"), b"")
+            .await
+            .unwrap();
+        let mut keys: Vec<_> = storage
+            .list("card/1")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|object| (object.key, object.size))
+            .collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                ("card/1.jpg".to_string(), 9),
+                ("card/12.jpg".to_string(), 5)
+            ]
+        );
+        assert_eq!(storage.list("").await.unwrap().len(), 3);
+        assert!(storage.list("scan/").await.unwrap().is_empty());
+
+        storage.delete("card/1.jpg").await.unwrap();
+        storage.delete("card/1.jpg").await.unwrap();
+        assert!(!storage.exists("card/1.jpg").await.unwrap());
+    }
+}
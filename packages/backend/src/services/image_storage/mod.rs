@@ -0,0 +1,61 @@
+mod filesystem;
+mod s3;
+
+use std::fmt::Debug;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+
+pub use filesystem::FilesystemStorage;
+pub use s3::{S3Config, S3Storage};
+
+/// A key listed from an image storage
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredObject {
+    pub key: String,
+    /// Size of the content, in bytes
+    pub size: u64,
+    pub modified: DateTime<Utc>,
+}
+
+/// Where cached images are stored, by key (e.g. `card/42_full.jpg`).
+///
+/// Writes must be atomic: readers see either the previous content of a key, or the whole new one.
+pub trait ImageStorage: Debug + Send + Sync {
+    /// Reads the content of a key, if stored
+    fn read<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<Option<Vec<u8>>>>;
+
+    /// Writes the content of a key, replacing the previous one
+    fn write<'a>(&'a self, key: &'a str, data: &'a [u8]) -> BoxFuture<'a, io::Result<()>>;
+
+    /// Whether a key is stored, without reading it
+    fn exists<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<bool>>;
+
+    /// Deletes a key, if stored
+    fn delete<'a>(&'a self, key: &'a str) -> BoxFuture<'a, io::Result<()>>;
+
+    /// Lists the keys starting with a prefix, in no particular order
+    fn list<'a>(&'a self, prefix: &'a str) -> BoxFuture<'a, io::Result<Vec<StoredObject>>>;
+}
+
+/// Image storage backends, selected through the app configuration
+#[derive(Debug, Clone)]
+pub enum ImageStorageConfig {
+    /// Files in the content directory
+    Filesystem,
+    /// Objects in an S3-compatible bucket, shared by several instances of the app
+    S3(S3Config),
+}
+
+impl ImageStorageConfig {
+    /// Creates the configured storage. Files are stored in the content directory.
+    pub fn build(&self, content_path: &Path) -> Arc<dyn ImageStorage> {
+        match self {
+            ImageStorageConfig::Filesystem => Arc::new(FilesystemStorage::new(content_path)),
+            ImageStorageConfig::S3(config) => Arc::new(S3Storage::new(config.clone())),
+        }
+    }
+}
@@ -1 +1,4 @@
+pub mod image;
+pub mod image_cache;
+pub mod image_storage;
 pub mod ygo;
@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+use std::result::Result;
+use tokio_postgres::{Client, Error, Row};
+
+use crate::models::ygo;
+
+/// Lists the artworks of a card, default one first
+pub async fn get_by_card_id(client: &Client, card_id: i32) -> Result<Vec<ygo::CardArtwork>, Error> {
+    let rows = client
+        .query(
+            "SELECT * FROM ygo_card_artworks WHERE card_id = $1 ORDER BY art_index ASC",
+            &[&card_id],
+        )
+        .await?;
+
+    rows.iter().map(|row| row.try_into()).collect()
+}
+
+/// Retrieves an artwork of a card by its index
+pub async fn get_by_index(
+    client: &Client,
+    card_id: i32,
+    index: i16,
+) -> Result<Option<ygo::CardArtwork>, Error> {
+    let row = client
+        .query_opt(
+            "SELECT * FROM ygo_card_artworks WHERE card_id = $1 AND art_index = $2",
+            &[&card_id, &index],
+        )
+        .await?;
+
+    row.as_ref().map(|row| row.try_into()).transpose()
+}
+
+/// Saves the artworks of many cards at once from their YGOPRODeck image IDs, in order.
+/// Artworks left over from a previous save are removed. Cards must not be repeated.
+/// Returns the cards whose saved artworks were replaced or removed, as their images changed.
+pub async fn save_all_by_card(
+    client: &Client,
+    artworks: &[(i32, Vec<i32>)],
+) -> Result<HashSet<i32>, Error> {
+    let card_ids: Vec<i32> = artworks.iter().map(|(card_id, _)| *card_id).collect();
+    let counts: Vec<i16> = artworks.iter().map(|(_, ids)| ids.len() as i16).collect();
+
+    let removed = client
+        .query(
+            r#"
+            DELETE FROM ygo_card_artworks a
+            USING UNNEST($1::INTEGER[], $2::SMALLINT[]) AS cards (card_id, count)
+            WHERE a.card_id = cards.card_id AND a.art_index >= cards.count
+            RETURNING a.card_id
+            "#,
+            &[&card_ids, &counts],
+        )
+        .await?;
+
+    let mut rows: (Vec<i32>, Vec<i16>, Vec<i32>) = Default::default();
+    for (card_id, ygoprodeck_ids) in artworks {
+        for (index, ygoprodeck_id) in ygoprodeck_ids.iter().enumerate() {
+            rows.0.push(*card_id);
+            rows.1.push(index as i16);
+            rows.2.push(*ygoprodeck_id);
+        }
+    }
+
+    let replaced = client
+        .query(
+            r#"
+            SELECT a.card_id FROM ygo_card_artworks a
+            JOIN UNNEST($1::INTEGER[], $2::SMALLINT[], $3::INTEGER[])
+                AS artworks (card_id, art_index, ygoprodeck_id)
+                ON a.card_id = artworks.card_id AND a.art_index = artworks.art_index
+            WHERE a.ygoprodeck_id <> artworks.ygoprodeck_id
+            "#,
+            &[&rows.0, &rows.1, &rows.2],
+        )
+        .await?;
+
+    client
+        .execute(
+            r#"
+            INSERT INTO ygo_card_artworks (card_id, art_index, ygoprodeck_id)
+            SELECT * FROM UNNEST($1::INTEGER[], $2::SMALLINT[], $3::INTEGER[])
+            ON CONFLICT (card_id, art_index) DO UPDATE SET
+                ygoprodeck_id = EXCLUDED.ygoprodeck_id
+            "#,
+            &[&rows.0, &rows.1, &rows.2],
+        )
+        .await?;
+
+    Ok(removed
+        .iter()
+        .chain(&replaced)
+        .map(|row| row.get(0))
+        .collect())
+}
+
+impl TryFrom<&Row> for ygo::CardArtwork {
+    type Error = Error;
+
+    /// Converts a database row into a CardArtwork struct
+    fn try_from(value: &Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            index: value.try_get("art_index")?,
+            ygoprodeck_id: value.try_get("ygoprodeck_id")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{services::ygo::card::seed_cards, test_utils::with_db_pool};
+
+    #[tokio::test]
+    async fn test_save_all_by_card_and_get_artworks() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 1).await.expect("seed");
+
+            let changed = save_all_by_card(&client, &[(1, vec![89631139, 89631140, 89631141])])
+                .await
+                .expect("save");
+            assert!(changed.is_empty());
+            let artworks = get_by_card_id(&client, 1).await.expect("artworks");
+            assert_eq!(
+                artworks,
+                vec![
+                    ygo::CardArtwork {
+                        index: 0,
+                        ygoprodeck_id: 89631139
+                    },
+                    ygo::CardArtwork {
+                        index: 1,
+                        ygoprodeck_id: 89631140
+                    },
+                    ygo::CardArtwork {
+                        index: 2,
+                        ygoprodeck_id: 89631141
+                    },
+                ]
+            );
+
+            // Saving fewer artworks removes the extra ones
+            let changed = save_all_by_card(&client, &[(1, vec![89631139, 89631142])])
+                .await
+                .expect("save");
+            assert_eq!(changed, HashSet::from([1]));
+            let artworks = get_by_card_id(&client, 1).await.expect("artworks");
+            assert_eq!(artworks.len(), 2);
+            assert_eq!(artworks[1].ygoprodeck_id, 89631142);
+
+            let artwork = get_by_index(&client, 1, 1).await.expect("artwork");
+            assert_eq!(artwork.map(|a| a.ygoprodeck_id), Some(89631142));
+            let artwork = get_by_index(&client, 1, 2).await.expect("artwork");
+            assert_eq!(artwork, None);
+
+            // Saving the same artworks again changes nothing
+            let changed = save_all_by_card(&client, &[(1, vec![89631139, 89631142])])
+                .await
+                .expect("save");
+            assert!(changed.is_empty());
+        })
+        .await;
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::result::Result;
+use tokio_postgres::{Client, Error, Row};
+
+use crate::database::{QueryParams, with_transaction};
+use crate::models::ygo;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Filter {
+    pub format: Option<ygo::BanlistFormat>,
+}
+
+/// Retrieves all banlists, most recent first
+pub async fn get_all(client: &Client, filter: Option<Filter>) -> Result<Vec<ygo::Banlist>, Error> {
+    let mut query = String::from("SELECT * FROM ygo_banlists");
+    let mut params = QueryParams::new();
+
+    if let Some(format) = filter.and_then(|filter| filter.format) {
+        let idx = params.push(format);
+        query.push_str(&format!(" WHERE format = ${idx}"));
+    }
+
+    query.push_str(" ORDER BY effective_date DESC, format ASC");
+
+    let rows = client.query(&query, &params.as_refs()).await?;
+
+    rows.iter().map(|row| row.try_into()).collect()
+}
+
+/// Retrieves the banlist of a format in effect on the given date
+pub async fn get_effective(
+    client: &Client,
+    format: ygo::BanlistFormat,
+    date: NaiveDate,
+) -> Result<Option<ygo::Banlist>, Error> {
+    let query = r#"
+        SELECT * FROM ygo_banlists
+        WHERE format = $1 AND effective_date <= $2
+        ORDER BY effective_date DESC
+        LIMIT 1
+    "#;
+    let row = &client.query_opt(query, &[&format, &date]).await?;
+
+    row.as_ref().map(|r| r.try_into()).transpose()
+}
+
+/// Retrieves the limited cards of a banlist, as the number of allowed copies by card ID
+pub async fn get_limits(client: &Client, banlist_id: i32) -> Result<HashMap<i32, i16>, Error> {
+    let query = "SELECT card_id, card_limit FROM ygo_banlist_cards WHERE banlist_id = $1";
+    let rows = client.query(query, &[&banlist_id]).await?;
+
+    rows.iter()
+        .map(|row| Ok((row.try_get("card_id")?, row.try_get("card_limit")?)))
+        .collect()
+}
+
+/// Retrieves the status of the given cards on the banlists currently in effect.
+/// Unlimited cards are left out.
+pub async fn get_current_statuses(
+    client: &Client,
+    card_ids: &[i32],
+) -> Result<HashMap<i32, BTreeMap<ygo::BanlistFormat, ygo::BanStatus>>, Error> {
+    let query = r#"
+        SELECT c.card_id, c.card_limit, b.format
+        FROM ygo_banlist_cards c
+        JOIN ygo_banlists b ON b.id = c.banlist_id
+        WHERE c.card_id = ANY($1) AND b.id IN (
+            SELECT DISTINCT ON (format) id
+            FROM ygo_banlists
+            WHERE effective_date <= CURRENT_DATE
+            ORDER BY format, effective_date DESC
+        )
+    "#;
+    let rows = client.query(query, &[&card_ids]).await?;
+
+    let mut statuses: HashMap<i32, BTreeMap<_, _>> = HashMap::new();
+    for row in rows {
+        let card_id: i32 = row.try_get("card_id")?;
+        let limit: i16 = row.try_get("card_limit")?;
+        statuses
+            .entry(card_id)
+            .or_default()
+            .insert(row.try_get("format")?, ygo::BanStatus::from_limit(limit));
+    }
+
+    Ok(statuses)
+}
+
+/// Saves the banlist of a format effective from the given date, replacing its cards if it exists
+pub async fn save(
+    client: &Client,
+    format: ygo::BanlistFormat,
+    effective_date: NaiveDate,
+    limits: &HashMap<i32, i16>,
+) -> Result<ygo::Banlist, Error> {
+    with_transaction(
+        client,
+        None,
+        async |client| -> Result<ygo::Banlist, Error> {
+            let row = client
+                .query_one(
+                    r#"
+                INSERT INTO ygo_banlists (format, effective_date) VALUES ($1, $2)
+                ON CONFLICT (format, effective_date) DO UPDATE SET
+                    updated_at = CURRENT_TIMESTAMP
+                RETURNING *
+                "#,
+                    &[&format, &effective_date],
+                )
+                .await?;
+            let banlist: ygo::Banlist = (&row).try_into()?;
+
+            client
+                .execute(
+                    "DELETE FROM ygo_banlist_cards WHERE banlist_id = $1",
+                    &[&banlist.id],
+                )
+                .await?;
+
+            let (card_ids, card_limits): (Vec<i32>, Vec<i16>) = limits.iter().unzip();
+            client
+                .execute(
+                    r#"
+                INSERT INTO ygo_banlist_cards (banlist_id, card_id, card_limit)
+                SELECT $1, card_id, card_limit
+                FROM UNNEST($2::INTEGER[], $3::SMALLINT[]) AS cards (card_id, card_limit)
+                "#,
+                    &[&banlist.id, &card_ids, &card_limits],
+                )
+                .await?;
+
+            Ok(banlist)
+        },
+    )
+    .await
+}
+
+impl TryFrom<&Row> for ygo::Banlist {
+    type Error = Error;
+
+    /// Converts a database row into a Banlist struct
+    fn try_from(value: &Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.try_get("id")?,
+            format: value.try_get("format")?,
+            effective_date: value.try_get("effective_date")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{services::ygo::card::seed_cards, test_utils::with_db_pool};
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_save_and_get_effective() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 3).await.expect("seed");
+
+            let old = save(
+                &client,
+                ygo::BanlistFormat::Tcg,
+                date(2005, 4, 1),
+                &HashMap::from([(1, 0), (2, 1)]),
+            )
+            .await
+            .expect("save");
+            let new = save(
+                &client,
+                ygo::BanlistFormat::Tcg,
+                date(2024, 9, 1),
+                &HashMap::from([(1, 1)]),
+            )
+            .await
+            .expect("save");
+
+            let effective = get_effective(&client, ygo::BanlistFormat::Tcg, date(2010, 1, 1))
+                .await
+                .expect("effective");
+            assert_eq!(effective, Some(old.clone()));
+            let limits = get_limits(&client, old.id).await.expect("limits");
+            assert_eq!(limits, HashMap::from([(1, 0), (2, 1)]));
+
+            let effective = get_effective(&client, ygo::BanlistFormat::Tcg, date(2025, 1, 1))
+                .await
+                .expect("effective");
+            assert_eq!(effective, Some(new.clone()));
+
+            let none = get_effective(&client, ygo::BanlistFormat::Tcg, date(2000, 1, 1))
+                .await
+                .expect("effective");
+            assert_eq!(none, None);
+
+            // Saving again replaces the cards
+            let saved = save(
+                &client,
+                ygo::BanlistFormat::Tcg,
+                date(2024, 9, 1),
+                &HashMap::from([(3, 2)]),
+            )
+            .await
+            .expect("save");
+            assert_eq!(saved.id, new.id);
+            let limits = get_limits(&client, new.id).await.expect("limits");
+            assert_eq!(limits, HashMap::from([(3, 2)]));
+
+            let all = get_all(&client, None).await.expect("all");
+            assert_eq!(all, vec![saved, old]);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_get_current_statuses() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 3).await.expect("seed");
+
+            let limits = [
+                (
+                    ygo::BanlistFormat::Tcg,
+                    date(2005, 4, 1),
+                    vec![(1, 0), (2, 0)],
+                ),
+                (ygo::BanlistFormat::Tcg, date(2024, 9, 1), vec![(1, 1)]),
+                (ygo::BanlistFormat::Ocg, date(2005, 4, 1), vec![(1, 2)]),
+                // Not in effect yet
+                (ygo::BanlistFormat::Goat, date(2999, 1, 1), vec![(1, 0)]),
+            ];
+            for (format, effective_date, limits) in limits {
+                let limits = limits.into_iter().collect();
+                save(&client, format, effective_date, &limits)
+                    .await
+                    .expect("save");
+            }
+
+            let statuses = get_current_statuses(&client, &[1, 2, 3])
+                .await
+                .expect("statuses");
+            assert_eq!(
+                statuses,
+                HashMap::from([(
+                    1,
+                    BTreeMap::from([
+                        (ygo::BanlistFormat::Tcg, ygo::BanStatus::Limited),
+                        (ygo::BanlistFormat::Ocg, ygo::BanStatus::SemiLimited),
+                    ])
+                )])
+            );
+        })
+        .await;
+    }
+}
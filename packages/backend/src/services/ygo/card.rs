@@ -1,18 +1,30 @@
+use std::collections::{BTreeMap, HashMap};
+
 use chrono::NaiveDate;
-use serde::{Deserialize, Serialize};
+use futures_util::pin_mut;
+use rust_decimal::Decimal;
+use serde::de::{self, IntoDeserializer, value::Error as ValueError};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::result::Result;
+use std::str::FromStr;
+use tokio_postgres::binary_copy::BinaryCopyInWriter;
 use tokio_postgres::{Client, Error, Row};
 
 use crate::database::{QueryParams, TzTimestamp};
 use crate::models::ygo;
+use crate::services::ygo as service;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PageCursor {
     pub id: i32,
-    pub sorting_value: Option<SortingCursor>,
+    /// Values of the card for each sorting field before the ID
+    pub sorting_values: Vec<Option<SortingCursor>>,
+    /// Whether the cursor points to the items before it, to walk the list backwards
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub backward: bool,
 }
 
-#[derive(Debug, Default, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum SortingField {
     #[default]
@@ -23,9 +35,29 @@ pub enum SortingField {
     Level,
     TcgDate,
     OcgDate,
+    Price,
+    /// Rank of the card against the full-text search (`q` filter)
+    Relevance,
 }
 
-#[derive(Debug, Default, Deserialize)]
+impl SortingField {
+    /// Column holding the values of the field
+    fn column(&self) -> &'static str {
+        match self {
+            SortingField::Id => "id",
+            SortingField::Name => "name",
+            SortingField::Atk => "monster_atk",
+            SortingField::Def => "monster_def",
+            SortingField::Level => "monster_level",
+            SortingField::TcgDate => "tcg_date",
+            SortingField::OcgDate => "ocg_date",
+            SortingField::Price => "price",
+            SortingField::Relevance => "relevance",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum SortingDirection {
     #[default]
@@ -33,24 +65,91 @@ pub enum SortingDirection {
     Desc,
 }
 
+/// A field to sort by, and its direction if not the default one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub field: SortingField,
+    pub dir: Option<SortingDirection>,
+}
+
+impl From<SortingField> for SortKey {
+    fn from(field: SortingField) -> Self {
+        Self { field, dir: None }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    /// Parses a sorting field, optionally followed by its direction (e.g. `atk:desc`)
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        let (field, dir) = match key.split_once(':') {
+            Some((field, dir)) => (field, Some(dir)),
+            None => (key, None),
+        };
+
+        let field = SortingField::deserialize(field.into_deserializer())
+            .map_err(|_: ValueError| format!("unknown sorting field '{field}'"))?;
+        let dir = dir
+            .map(|dir| {
+                SortingDirection::deserialize(dir.into_deserializer())
+                    .map_err(|_: ValueError| format!("unknown sorting direction '{dir}'"))
+            })
+            .transpose()?;
+
+        Ok(Self { field, dir })
+    }
+}
+
+/// Deserializes a comma-separated list of sorting fields, e.g. `level:desc,atk:desc,name`
+fn deserialize_sort_keys<'de, D>(deserializer: D) -> Result<Vec<SortKey>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer)?
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(|key| key.parse().map_err(de::Error::custom))
+        .collect()
+}
+
 #[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Sort {
-    #[serde(default)]
-    pub sort: SortingField,
+    /// Fields to sort by, ties being broken by the next fields then by ID
+    #[serde(default, deserialize_with = "deserialize_sort_keys")]
+    pub sort: Vec<SortKey>,
+    /// Direction of the fields without their own
     #[serde(default)]
     pub dir: SortingDirection,
+    /// Marketplace whose latest price is used when sorting by price
+    #[serde(default)]
+    pub price_source: ygo::PriceSource,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SortingCursor {
     Name(String),
     Int(i16),
     Date(NaiveDate),
+    Price(Decimal),
+    Relevance(f32),
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseRegion {
+    Tcg,
+    Ocg,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Filter {
+    /// Search query, parsed by the API from the `q` parameter
+    #[serde(skip)]
+    pub q: Option<service::query::Expr>,
     pub name: Option<String>,
     pub description: Option<String>,
     #[serde(default)]
@@ -61,143 +160,345 @@ pub struct Filter {
     pub race: Vec<ygo::MonsterRace>,
     #[serde(default)]
     pub subtype: Vec<ygo::MonsterSubtype>,
+    #[serde(default)]
+    pub monster_kind: Vec<ygo::MonsterKind>,
     pub atk_min: Option<i16>,
     pub atk_max: Option<i16>,
     pub def_min: Option<i16>,
     pub def_max: Option<i16>,
     pub level_min: Option<i16>,
     pub level_max: Option<i16>,
+    pub link_rating_min: Option<i16>,
+    pub link_rating_max: Option<i16>,
+    pub scale_min: Option<i16>,
+    pub scale_max: Option<i16>,
+    /// Link arrows the card must all have
+    pub link_arrows_all: Option<ygo::LinkArrows>,
+    /// Link arrows the card must have at least one of
+    pub link_arrows_any: Option<ygo::LinkArrows>,
+    pub tcg_date_from: Option<NaiveDate>,
+    pub tcg_date_to: Option<NaiveDate>,
+    pub ocg_date_from: Option<NaiveDate>,
+    pub ocg_date_to: Option<NaiveDate>,
+    /// Region the card was only released in
+    pub only_in: Option<ReleaseRegion>,
+    pub has_password: Option<bool>,
     #[serde(default)]
     pub spell: Vec<ygo::SpellKind>,
     #[serde(default)]
     pub trap: Vec<ygo::TrapKind>,
+    pub banlist: Option<ygo::BanStatus>,
+    pub banlist_format: Option<ygo::BanlistFormat>,
 }
 
-/// Retrieves cards with cursor-based pagination
-pub async fn get_page(
-    client: &Client,
-    filter: Option<Filter>,
-    sort: Option<Sort>,
-    limit: u32,
-    cursor: Option<PageCursor>,
-) -> Result<(Vec<ygo::Card>, Option<PageCursor>), Error> {
-    // Tiny query builder
-    let mut query = String::from("SELECT * FROM ygo_cards");
-    let mut params = QueryParams::new();
+/// Builds the WHERE conditions matching the filter
+fn filter_queries(filter: Filter, params: &mut QueryParams) -> Vec<String> {
     let mut where_queries: Vec<String> = Vec::new();
 
-    if let Some(filter) = filter {
-        // Filter by name
-        if let Some(name) = filter.name {
-            let idx = params.push(format!("%{}%", name));
-            where_queries.push(format!("name ILIKE ${idx}"));
-        }
+    // Filter by search query
+    if let Some(q) = filter.q {
+        where_queries.push(q.to_sql(params));
+    }
 
-        // Filter by description
-        if let Some(description) = filter.description {
-            let idx = params.push(format!("%{}%", description));
-            where_queries.push(format!("description ILIKE ${idx}"));
-        }
+    // Filter by name
+    if let Some(name) = filter.name {
+        let idx = params.push(format!("%{}%", name));
+        where_queries.push(format!("name ILIKE ${idx}"));
+    }
 
-        // Filter by kind
-        if let Some(kind) = filter.kind {
-            let idx = params.push(kind);
-            where_queries.push(format!("kind = ${idx}"));
-        }
+    // Filter by description
+    if let Some(description) = filter.description {
+        let idx = params.push(format!("%{}%", description));
+        where_queries.push(format!("description ILIKE ${idx}"));
+    }
 
-        // Filter by attribute
-        if !filter.attribute.is_empty() {
-            let idx = params.push(filter.attribute);
-            where_queries.push(format!("monster_attribute = ANY(${idx})"));
-        }
+    // Filter by kind
+    if let Some(kind) = filter.kind {
+        let idx = params.push(kind);
+        where_queries.push(format!("kind = ${idx}"));
+    }
 
-        // Filter by race
-        if !filter.race.is_empty() {
-            let idx = params.push(filter.race);
-            where_queries.push(format!("monster_race = ANY(${idx})"));
-        }
+    // Filter by attribute
+    if !filter.attribute.is_empty() {
+        let idx = params.push(filter.attribute);
+        where_queries.push(format!("monster_attribute = ANY(${idx})"));
+    }
 
-        // Filter by subtype
-        if !filter.subtype.is_empty() {
-            let idx = params.push(filter.subtype);
-            where_queries.push(format!("monster_subtypes @> ${idx}"));
-        }
+    // Filter by race
+    if !filter.race.is_empty() {
+        let idx = params.push(filter.race);
+        where_queries.push(format!("monster_race = ANY(${idx})"));
+    }
 
-        // Filter by attack points
-        if let Some(atk_min) = filter.atk_min {
-            let idx = params.push(atk_min);
-            where_queries.push(format!("monster_atk >= ${idx}"));
-        }
-        if let Some(atk_max) = filter.atk_max {
-            let idx = params.push(atk_max);
-            where_queries.push(format!("monster_atk <= ${idx}"));
-        }
+    // Filter by subtype
+    if !filter.subtype.is_empty() {
+        let idx = params.push(filter.subtype);
+        where_queries.push(format!("monster_subtypes @> ${idx}"));
+    }
+
+    // Filter by attack points
+    if let Some(atk_min) = filter.atk_min {
+        let idx = params.push(atk_min);
+        where_queries.push(format!("monster_atk >= ${idx}"));
+    }
+    if let Some(atk_max) = filter.atk_max {
+        let idx = params.push(atk_max);
+        where_queries.push(format!("monster_atk <= ${idx}"));
+    }
+
+    // Filter by defense points
+    if let Some(def_min) = filter.def_min {
+        let idx = params.push(def_min);
+        where_queries.push(format!("monster_def >= ${idx}"));
+    }
+    if let Some(def_max) = filter.def_max {
+        let idx = params.push(def_max);
+        where_queries.push(format!("monster_def <= ${idx}"));
+    }
+
+    // Filter by level
+    if let Some(level_min) = filter.level_min {
+        let idx = params.push(level_min);
+        where_queries.push(format!("monster_level >= ${idx}"));
+    }
+    if let Some(level_max) = filter.level_max {
+        let idx = params.push(level_max);
+        where_queries.push(format!("monster_level <= ${idx}"));
+    }
+
+    // Filter by spell kind
+    if !filter.spell.is_empty() {
+        let idx = params.push(filter.spell);
+        where_queries.push(format!("spell_kind = ANY(${idx})"));
+    }
 
-        // Filter by defense points
-        if let Some(def_min) = filter.def_min {
-            let idx = params.push(def_min);
-            where_queries.push(format!("monster_def >= ${idx}"));
+    // Filter by trap kind
+    if !filter.trap.is_empty() {
+        let idx = params.push(filter.trap);
+        where_queries.push(format!("trap_kind = ANY(${idx})"));
+    }
+
+    // Filter by monster kind
+    if !filter.monster_kind.is_empty() {
+        let idx = params.push(filter.monster_kind);
+        where_queries.push(format!("monster_kind = ANY(${idx})"));
+    }
+
+    // Filter by link rating, stored as the level of link monsters
+    if filter.link_rating_min.is_some() || filter.link_rating_max.is_some() {
+        let idx = params.push(ygo::MonsterKind::Link);
+        where_queries.push(format!("monster_kind = ${idx}"));
+    }
+    if let Some(link_rating_min) = filter.link_rating_min {
+        let idx = params.push(link_rating_min);
+        where_queries.push(format!("monster_level >= ${idx}"));
+    }
+    if let Some(link_rating_max) = filter.link_rating_max {
+        let idx = params.push(link_rating_max);
+        where_queries.push(format!("monster_level <= ${idx}"));
+    }
+
+    // Filter by pendulum scale
+    if let Some(scale_min) = filter.scale_min {
+        let idx = params.push(scale_min);
+        where_queries.push(format!("monster_pendulum_scale >= ${idx}"));
+    }
+    if let Some(scale_max) = filter.scale_max {
+        let idx = params.push(scale_max);
+        where_queries.push(format!("monster_pendulum_scale <= ${idx}"));
+    }
+
+    // Filter by link arrows
+    if let Some(link_arrows) = filter.link_arrows_all {
+        let idx = params.push(link_arrows);
+        where_queries.push(format!("monster_link_arrows & ${idx} = ${idx}"));
+    }
+    if let Some(link_arrows) = filter.link_arrows_any {
+        let idx = params.push(link_arrows);
+        where_queries.push(format!("monster_link_arrows & ${idx} <> 0"));
+    }
+
+    // Filter by release dates (inclusive)
+    for (column, from, to) in [
+        ("tcg_date", filter.tcg_date_from, filter.tcg_date_to),
+        ("ocg_date", filter.ocg_date_from, filter.ocg_date_to),
+    ] {
+        if let Some(from) = from {
+            let idx = params.push(from);
+            where_queries.push(format!("{column} >= ${idx}"));
         }
-        if let Some(def_max) = filter.def_max {
-            let idx = params.push(def_max);
-            where_queries.push(format!("monster_def <= ${idx}"));
+        if let Some(to) = to {
+            let idx = params.push(to);
+            where_queries.push(format!("{column} <= ${idx}"));
         }
+    }
 
-        // Filter by level
-        if let Some(level_min) = filter.level_min {
-            let idx = params.push(level_min);
-            where_queries.push(format!("monster_level >= ${idx}"));
+    // Filter by region exclusives
+    match filter.only_in {
+        Some(ReleaseRegion::Tcg) => {
+            where_queries.push("tcg_date IS NOT NULL AND ocg_date IS NULL".to_string())
         }
-        if let Some(level_max) = filter.level_max {
-            let idx = params.push(level_max);
-            where_queries.push(format!("monster_level <= ${idx}"));
+        Some(ReleaseRegion::Ocg) => {
+            where_queries.push("ocg_date IS NOT NULL AND tcg_date IS NULL".to_string())
         }
+        None => {}
+    }
 
-        // Filter by spell kind
-        if !filter.spell.is_empty() {
-            let idx = params.push(filter.spell);
-            where_queries.push(format!("spell_kind = ANY(${idx})"));
-        }
+    // Filter by password
+    match filter.has_password {
+        Some(true) => where_queries.push("password IS NOT NULL".to_string()),
+        Some(false) => where_queries.push("password IS NULL".to_string()),
+        None => {}
+    }
+
+    // Filter by status on the current banlist of the format (TCG by default)
+    if let Some(status) = filter.banlist {
+        let idx = params.push(filter.banlist_format.unwrap_or_default());
+        let banlist_cards = format!(
+            r#"
+            SELECT card_id FROM ygo_banlist_cards WHERE banlist_id = (
+                SELECT id FROM ygo_banlists
+                WHERE format = ${idx} AND effective_date <= CURRENT_DATE
+                ORDER BY effective_date DESC
+                LIMIT 1
+            )
+            "#
+        );
 
-        // Filter by trap kind
-        if !filter.trap.is_empty() {
-            let idx = params.push(filter.trap);
-            where_queries.push(format!("trap_kind = ANY(${idx})"));
+        if status == ygo::BanStatus::Unlimited {
+            where_queries.push(format!("id NOT IN ({banlist_cards})"));
+        } else {
+            let idx = params.push(status.limit());
+            where_queries.push(format!("id IN ({banlist_cards} AND card_limit = ${idx})"));
         }
     }
 
+    where_queries
+}
+
+/// Retrieves cards with cursor-based pagination.
+/// Returns the cards along with the cursors of the next and previous pages.
+pub async fn get_page(
+    client: &Client,
+    filter: Option<Filter>,
+    sort: Option<Sort>,
+    limit: u32,
+    cursor: Option<PageCursor>,
+) -> Result<(Vec<ygo::Card>, Option<PageCursor>, Option<PageCursor>), Error> {
+    // Tiny query builder
+    let mut query = String::from("SELECT * FROM ygo_cards");
+    let mut params = QueryParams::new();
+    let mut where_queries: Vec<String> = Vec::new();
+    // Computed columns, selected to generate the next cursor
+    let mut computed_columns: Vec<String> = Vec::new();
+
     let sort = sort.unwrap_or_default();
-    let (sort_dir, sort_comparator) = match sort.dir {
-        SortingDirection::Asc => ("ASC", ">"),
-        SortingDirection::Desc => ("DESC", "<"),
-    };
-    let sorting_field = match sort.sort {
-        SortingField::Id => "id",
-        SortingField::Name => "name",
-        SortingField::Atk => "monster_atk",
-        SortingField::Def => "monster_def",
-        SortingField::Level => "monster_level",
-        SortingField::TcgDate => "tcg_date",
-        SortingField::OcgDate => "ocg_date",
-    };
+    let has_cursor = cursor.is_some();
+    // Items before a backward cursor are retrieved in the reverse order, then put back in order
+    let backward = cursor.as_ref().is_some_and(|cursor| cursor.backward);
+
+    // Sorting fields, and whether they're sorted in descending order.
+    // The ID breaks ties, in the direction of the last field.
+    let mut keys: Vec<(SortingField, bool)> = Vec::new();
+    let mut id_descending = sort.dir == SortingDirection::Desc;
+    for key in &sort.sort {
+        let descending = (key.dir.unwrap_or(sort.dir) == SortingDirection::Desc) != backward;
+        id_descending = descending;
+        if key.field == SortingField::Id {
+            break;
+        }
+        keys.push((key.field, descending));
+    }
+    if sort.sort.is_empty() {
+        id_descending = id_descending != backward;
+    }
 
-    // Retrieve only items after the cursor index
-    if let Some(PageCursor { id, sorting_value }) = cursor {
-        let id_idx = params.push(id);
+    if keys.iter().any(|(field, _)| *field == SortingField::Price) {
+        // Latest price of the card
+        let idx = params.push(sort.price_source);
+        computed_columns.push(format!(
+            r#"
+            (
+                SELECT price FROM ygo_card_prices
+                WHERE card_id = ygo_cards.id AND source = ${idx}
+                ORDER BY date DESC
+                LIMIT 1
+            ) AS price
+            "#
+        ));
+    }
+    if keys
+        .iter()
+        .any(|(field, _)| *field == SortingField::Relevance)
+    {
+        // Every card is as relevant without a search
+        let rank = match filter.as_ref().and_then(|filter| filter.q.as_ref()) {
+            Some(q) => q.rank_sql(&mut params),
+            None => "0::REAL".to_string(),
+        };
+        computed_columns.push(format!("{rank} AS relevance"));
+    }
+    if !computed_columns.is_empty() {
+        query = format!(
+            "SELECT * FROM (SELECT *, {} FROM ygo_cards) AS ygo_cards",
+            computed_columns.join(", ")
+        );
+    }
 
-        if let Some(value) = sorting_value
-            && sort.sort != SortingField::Id
-        {
-            let value_idx = match value {
+    if let Some(filter) = filter {
+        where_queries.extend(filter_queries(filter, &mut params));
+    }
+
+    // Retrieve only items after the cursor, i.e. with the same values for the first fields
+    // and a value after the cursor's for the next one.
+    // NULL values come last, or first when walking backwards.
+    if let Some(PageCursor {
+        id, sorting_values, ..
+    }) = cursor
+    {
+        let mut same_values: Vec<String> = Vec::new();
+        let mut after_cursor: Vec<String> = Vec::new();
+
+        for ((field, descending), value) in keys.iter().zip(sorting_values) {
+            let column = field.column();
+            let comparator = if *descending { "<" } else { ">" };
+            let value_idx = value.map(|value| match value {
                 SortingCursor::Name(name) => params.push(name),
                 SortingCursor::Int(int) => params.push(int),
                 SortingCursor::Date(date) => params.push(date),
+                SortingCursor::Price(price) => params.push(price),
+                SortingCursor::Relevance(relevance) => params.push(relevance),
+            });
+
+            let (after, same) = match value_idx {
+                Some(idx) if backward => (
+                    Some(format!("{column} {comparator} ${idx}")),
+                    format!("{column} = ${idx}"),
+                ),
+                Some(idx) => (
+                    Some(format!(
+                        "({column} {comparator} ${idx} OR {column} IS NULL)"
+                    )),
+                    format!("{column} = ${idx}"),
+                ),
+                None if backward => (
+                    Some(format!("{column} IS NOT NULL")),
+                    format!("{column} IS NULL"),
+                ),
+                None => (None, format!("{column} IS NULL")),
             };
 
-            where_queries.push(format!("{sorting_field} {sort_comparator} ${value_idx} OR ({sorting_field} = ${value_idx} AND id {sort_comparator} ${id_idx})"));
-        } else {
-            where_queries.push(format!("id > ${id_idx}"));
+            if let Some(after) = after {
+                after_cursor.push(all_of(&same_values, after));
+            }
+            same_values.push(same);
         }
+
+        let id_idx = params.push(id);
+        let comparator = if id_descending { "<" } else { ">" };
+        after_cursor.push(all_of(&same_values, format!("id {comparator} ${id_idx}")));
+
+        where_queries.push(format!("({})", after_cursor.join(" OR ")));
     }
 
     // Build the where queries
@@ -206,14 +507,17 @@ pub async fn get_page(
         query.push_str(&where_queries.join(" AND "));
     }
 
-    // Sort by both the sorting field and ID
-    if sort.sort == SortingField::Id {
-        query.push_str(&format!(" ORDER BY id {sort_dir}"));
-    } else {
-        query.push_str(&format!(
-            " ORDER BY {sorting_field} {sort_dir}, id {sort_dir}"
-        ));
-    }
+    // Sort by the sorting fields, then by ID
+    let nulls = if backward { "FIRST" } else { "LAST" };
+    let mut order_by: Vec<String> = keys
+        .iter()
+        .map(|(field, descending)| {
+            let dir = if *descending { "DESC" } else { "ASC" };
+            format!("{} {dir} NULLS {nulls}", field.column())
+        })
+        .collect();
+    order_by.push(format!("id {}", if id_descending { "DESC" } else { "ASC" }));
+    query.push_str(&format!(" ORDER BY {}", order_by.join(", ")));
 
     // Retrieve one extra item to check if there's still another page
     let idx = params.push((limit + 1) as i64);
@@ -222,36 +526,280 @@ pub async fn get_page(
     // Make the query
     let rows = client.query(&query, &params.as_refs()).await?;
 
+    let has_more = rows.len() > limit as usize;
+    let mut rows = rows;
+    rows.truncate(limit as usize);
+    if backward {
+        rows.reverse();
+    }
+
     // Retrieve the cards
-    let cards: Vec<ygo::Card> = rows
+    let mut cards: Vec<ygo::Card> = rows
         .iter()
-        .take(limit as usize)
         .map(|row| row.try_into())
         .collect::<Result<_, _>>()?;
+    load_banlists(client, &mut cards).await?;
 
-    // Generate the next cursor
-    let next_cursor = if rows.len() > limit as usize {
-        cards.last().map(move |card| {
-            let sorting_value = match sort.sort {
-                SortingField::Id => None,
-                SortingField::Name => Some(SortingCursor::Name(card.data.name.clone())),
-                SortingField::Atk => card.data.monster_atk.map(SortingCursor::Int),
-                SortingField::Def => card.data.monster_def.map(SortingCursor::Int),
-                SortingField::Level => card.data.monster_level.map(SortingCursor::Int),
-                SortingField::TcgDate => card.data.tcg_date.map(SortingCursor::Date),
-                SortingField::OcgDate => card.data.ocg_date.map(SortingCursor::Date),
-            };
-
-            PageCursor {
-                id: card.id,
-                sorting_value,
-            }
+    // Generate the cursors, knowing there are items before a forward cursor and after a backward one
+    let (has_prev, has_next) = match backward {
+        false => (has_cursor, has_more),
+        true => (has_more, true),
+    };
+    let next_cursor = match (cards.last(), rows.last()) {
+        (Some(card), Some(row)) if has_next => Some(page_cursor(&keys, card, row, false)?),
+        _ => None,
+    };
+    let prev_cursor = match (cards.first(), rows.first()) {
+        (Some(card), Some(row)) if has_prev => Some(page_cursor(&keys, card, row, true)?),
+        _ => None,
+    };
+
+    Ok((cards, next_cursor, prev_cursor))
+}
+
+/// Number of cards matching a filter
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Total {
+    pub count: i64,
+    /// Whether the count is an estimate from the query planner
+    pub estimated: bool,
+}
+
+/// Unfiltered listings of more cards than this are counted from the query planner's estimate
+const ESTIMATE_THRESHOLD: i64 = 10_000;
+
+/// Counts the cards matching the filter. Counting every card is slow on large tables, so
+/// unfiltered listings are estimated by the query planner when there are many cards.
+pub async fn count(client: &Client, filter: Option<Filter>) -> Result<Total, Error> {
+    let mut params = QueryParams::new();
+    let where_queries = filter
+        .map(|filter| filter_queries(filter, &mut params))
+        .unwrap_or_default();
+
+    if where_queries.is_empty() {
+        let rows = client.query("EXPLAIN SELECT 1 FROM ygo_cards", &[]).await?;
+        let estimate = match rows.first() {
+            Some(row) => plan_rows(row.try_get(0)?),
+            None => None,
+        };
+        if let Some(estimate) = estimate
+            && estimate > ESTIMATE_THRESHOLD
+        {
+            return Ok(Total {
+                count: estimate,
+                estimated: true,
+            });
+        }
+    }
+
+    let mut query = String::from("SELECT COUNT(*) FROM ygo_cards");
+    if !where_queries.is_empty() {
+        query.push_str(" WHERE ");
+        query.push_str(&where_queries.join(" AND "));
+    }
+    let row = client.query_one(&query, &params.as_refs()).await?;
+
+    Ok(Total {
+        count: row.try_get(0)?,
+        estimated: false,
+    })
+}
+
+/// Reads the estimated number of rows from the first line of a query plan,
+/// e.g. `Seq Scan on ygo_cards  (cost=0.00..412.00 rows=12000 width=4)`
+fn plan_rows(plan: &str) -> Option<i64> {
+    plan.split(" rows=")
+        .nth(1)?
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Joins conditions that must all be true
+fn all_of(conditions: &[String], last: String) -> String {
+    if conditions.is_empty() {
+        return last;
+    }
+    format!("({} AND {last})", conditions.join(" AND "))
+}
+
+/// Builds a cursor pointing to a card, with the values it's sorted by
+fn page_cursor(
+    keys: &[(SortingField, bool)],
+    card: &ygo::Card,
+    row: &Row,
+    backward: bool,
+) -> Result<PageCursor, Error> {
+    let sorting_values = keys
+        .iter()
+        .map(|(field, _)| sorting_value(field, card, row))
+        .collect::<Result<_, _>>()?;
+
+    Ok(PageCursor {
+        id: card.id,
+        sorting_values,
+        backward,
+    })
+}
+
+/// Returns the value of a card for a sorting field
+fn sorting_value(
+    field: &SortingField,
+    card: &ygo::Card,
+    row: &Row,
+) -> Result<Option<SortingCursor>, Error> {
+    Ok(match field {
+        SortingField::Id => None,
+        SortingField::Name => Some(SortingCursor::Name(card.data.name.clone())),
+        SortingField::Atk => card.data.monster_atk.map(SortingCursor::Int),
+        SortingField::Def => card.data.monster_def.map(SortingCursor::Int),
+        SortingField::Level => card.data.monster_level.map(SortingCursor::Int),
+        SortingField::TcgDate => card.data.tcg_date.map(SortingCursor::Date),
+        SortingField::OcgDate => card.data.ocg_date.map(SortingCursor::Date),
+        SortingField::Price => row
+            .try_get::<_, Option<Decimal>>("price")?
+            .map(SortingCursor::Price),
+        SortingField::Relevance => row
+            .try_get::<_, Option<f32>>("relevance")?
+            .map(SortingCursor::Relevance),
+    })
+}
+
+/// A card found by a name lookup
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CardMatch {
+    pub card: ygo::Card,
+    /// Trigram similarity between the card name and the looked up name, from 0 to 1
+    pub similarity: f32,
+}
+
+/// Retrieves the cards whose name is the most similar to the given one, best matches first.
+/// Tolerates typos and missing punctuation (e.g. "blue eyes white dragn").
+pub async fn lookup(client: &Client, name: &str, limit: u32) -> Result<Vec<CardMatch>, Error> {
+    let query = r#"
+        SELECT *, similarity(name, $1) AS similarity
+        FROM ygo_cards
+        WHERE name % $1
+        ORDER BY similarity DESC, id ASC
+        LIMIT $2
+    "#;
+    let rows = client.query(query, &[&name, &(limit as i64)]).await?;
+
+    let mut cards: Vec<ygo::Card> = rows
+        .iter()
+        .map(|row| row.try_into())
+        .collect::<Result<_, _>>()?;
+    load_banlists(client, &mut cards).await?;
+
+    cards
+        .into_iter()
+        .zip(rows.iter())
+        .map(|(card, row)| {
+            Ok(CardMatch {
+                card,
+                similarity: row.try_get("similarity")?,
+            })
         })
-    } else {
-        None
+        .collect()
+}
+
+/// Number of cards matching a filter for each value of the card list filters
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Facets {
+    pub attribute: BTreeMap<String, i64>,
+    pub race: BTreeMap<String, i64>,
+    pub monster_kind: BTreeMap<String, i64>,
+    pub subtype: BTreeMap<String, i64>,
+    pub level: BTreeMap<i16, i64>,
+    pub spell: BTreeMap<String, i64>,
+    pub trap: BTreeMap<String, i64>,
+}
+
+/// Counts the cards matching the filter by attribute, race, monster kind, subtype, level,
+/// spell kind and trap kind.
+///
+/// Since choosing more attributes, races, monster kinds, spell or trap kinds, or widening the
+/// level range returns more cards, the counts of these facets ignore their own part of the filter.
+pub async fn get_facets(client: &Client, filter: Filter) -> Result<Facets, Error> {
+    let mut params = QueryParams::new();
+    let mut facet_queries: Vec<String> = Vec::new();
+
+    let mut facet = |name: &str, value: &str, filter: Filter| {
+        let mut where_queries = filter_queries(filter, &mut params);
+        where_queries.push(format!("{value} IS NOT NULL"));
+        // Cards have several subtypes, counted once each
+        let from = match name {
+            "subtype" => "ygo_cards, UNNEST(monster_subtypes) AS subtype",
+            _ => "ygo_cards",
+        };
+        facet_queries.push(format!(
+            "SELECT '{name}' AS facet, {value}::TEXT AS value, COUNT(*) AS count FROM {from} WHERE {} GROUP BY {value}",
+            where_queries.join(" AND ")
+        ));
+    };
+
+    let without_attribute = Filter {
+        attribute: Vec::new(),
+        ..filter.clone()
     };
+    facet("attribute", "monster_attribute", without_attribute);
+    let without_race = Filter {
+        race: Vec::new(),
+        ..filter.clone()
+    };
+    facet("race", "monster_race", without_race);
+    let without_monster_kind = Filter {
+        monster_kind: Vec::new(),
+        ..filter.clone()
+    };
+    facet("monster_kind", "monster_kind", without_monster_kind);
+    facet("subtype", "subtype", filter.clone());
+    let without_level = Filter {
+        level_min: None,
+        level_max: None,
+        ..filter.clone()
+    };
+    facet("level", "monster_level", without_level);
+    let without_spell = Filter {
+        spell: Vec::new(),
+        ..filter.clone()
+    };
+    facet("spell", "spell_kind", without_spell);
+    let without_trap = Filter {
+        trap: Vec::new(),
+        ..filter
+    };
+    facet("trap", "trap_kind", without_trap);
+
+    let query = facet_queries.join(" UNION ALL ");
+    let rows = client.query(&query, &params.as_refs()).await?;
+
+    let mut facets = Facets::default();
+    for row in rows {
+        let facet: &str = row.try_get("facet")?;
+        let value: String = row.try_get("value")?;
+        let count: i64 = row.try_get("count")?;
+
+        let counts = match facet {
+            "attribute" => &mut facets.attribute,
+            "race" => &mut facets.race,
+            "monster_kind" => &mut facets.monster_kind,
+            "subtype" => &mut facets.subtype,
+            "spell" => &mut facets.spell,
+            "trap" => &mut facets.trap,
+            _ => {
+                if let Ok(level) = value.parse() {
+                    facets.level.insert(level, count);
+                }
+                continue;
+            }
+        };
+        counts.insert(value, count);
+    }
 
-    Ok((cards, next_cursor))
+    Ok(facets)
 }
 
 /// Retrieves all cards in the database
@@ -260,10 +808,11 @@ pub async fn get_all(client: &Client) -> Result<Vec<ygo::Card>, Error> {
     let query = "SELECT * FROM ygo_cards ORDER BY id ASC";
     let rows = client.query(query, &[]).await?;
 
-    let cards: Vec<ygo::Card> = rows
+    let mut cards: Vec<ygo::Card> = rows
         .iter()
         .map(|row| row.try_into())
         .collect::<Result<_, _>>()?;
+    load_banlists(client, &mut cards).await?;
 
     Ok(cards)
 }
@@ -273,15 +822,32 @@ pub async fn get_by_id(client: &Client, id: i32) -> Result<Option<ygo::Card>, Er
     let query = "SELECT * FROM ygo_cards WHERE id = $1";
     let row = &client.query_opt(query, &[&id]).await?;
 
-    row.as_ref().map(|r| r.try_into()).transpose()
+    let card = row.as_ref().map(|r| r.try_into()).transpose()?;
+    load_banlist(client, card).await
+}
+
+/// Retrieves the cards matching the given IDs. Unknown IDs are ignored.
+pub async fn get_by_ids(client: &Client, ids: &[i32]) -> Result<Vec<ygo::Card>, Error> {
+    let query = "SELECT * FROM ygo_cards WHERE id = ANY($1)";
+    let rows = client.query(query, &[&ids]).await?;
+
+    let mut cards: Vec<ygo::Card> = rows
+        .iter()
+        .map(|row| row.try_into())
+        .collect::<Result<_, _>>()?;
+    load_banlists(client, &mut cards).await?;
+
+    Ok(cards)
 }
 
 /// Retrieves a card by Konami ID
+#[cfg(test)]
 pub async fn get_by_konami_id(client: &Client, konami_id: i32) -> Result<Option<ygo::Card>, Error> {
     let query = "SELECT * FROM ygo_cards WHERE konami_id = $1";
     let row = &client.query_opt(query, &[&konami_id]).await?;
 
-    row.as_ref().map(|r| r.try_into()).transpose()
+    let card = row.as_ref().map(|r| r.try_into()).transpose()?;
+    load_banlist(client, card).await
 }
 
 /// Retrieves a card by password
@@ -289,7 +855,8 @@ pub async fn get_by_password(client: &Client, password: &str) -> Result<Option<y
     let query = "SELECT * FROM ygo_cards WHERE password = $1";
     let row = &client.query_opt(query, &[&password]).await?;
 
-    row.as_ref().map(|r| r.try_into()).transpose()
+    let card = row.as_ref().map(|r| r.try_into()).transpose()?;
+    load_banlist(client, card).await
 }
 
 /// Deletes a card by ID. Returns true if a row was deleted, false otherwise.
@@ -423,7 +990,217 @@ pub async fn save(client: &Client, card: &ygo::Card) -> Result<Option<ygo::Card>
         )
         .await?;
 
-    row.as_ref().map(|r| r.try_into()).transpose()
+    let card = row.as_ref().map(|r| r.try_into()).transpose()?;
+    load_banlist(client, card).await
+}
+
+/// Columns of the cards saved by `upsert_all`
+const UPSERT_COLUMNS: [&str; 21] = [
+    "name",
+    "description",
+    "kind",
+    "password",
+    "konami_id",
+    "treated_as",
+    "tcg_date",
+    "ocg_date",
+    "monster_kind",
+    "monster_attribute",
+    "monster_race",
+    "monster_subtypes",
+    "monster_atk",
+    "monster_def",
+    "monster_level",
+    "monster_pendulum_scale",
+    "monster_pendulum_effect",
+    "monster_link_arrows",
+    "spell_kind",
+    "trap_kind",
+    "ygoprodeck_id",
+];
+
+/// Inserts or updates many cards at once, matching existing cards by Konami ID, or by password
+/// for cards without one. Cards must not share a Konami ID, nor a password when they have no
+/// Konami ID.
+///
+/// Returns the ID of each card and whether it was inserted, or `None` for cards with neither a
+/// Konami ID nor a password, which are skipped.
+pub async fn upsert_all(
+    client: &Client,
+    cards: &[ygo::CardData],
+) -> Result<Vec<Option<(i32, bool)>>, Error> {
+    let columns = UPSERT_COLUMNS.join(", ");
+
+    // Cards are copied into a staging table first, which is much faster than binding them
+    client
+        .batch_execute(&format!(
+            r#"
+            CREATE TEMP TABLE IF NOT EXISTS ygo_cards_import AS
+                SELECT 0 AS position, {columns} FROM ygo_cards WITH NO DATA;
+            TRUNCATE ygo_cards_import;
+            "#
+        ))
+        .await?;
+
+    let statement = client
+        .prepare(&format!("SELECT position, {columns} FROM ygo_cards_import"))
+        .await?;
+    let types: Vec<_> = statement
+        .columns()
+        .iter()
+        .map(|column| column.type_().clone())
+        .collect();
+
+    let sink = client
+        .copy_in(&format!(
+            "COPY ygo_cards_import (position, {columns}) FROM STDIN BINARY"
+        ))
+        .await?;
+    let writer = BinaryCopyInWriter::new(sink, &types);
+    pin_mut!(writer);
+    for (position, d) in cards.iter().enumerate() {
+        if d.konami_id.is_none() && d.password.is_none() {
+            continue;
+        }
+
+        writer
+            .as_mut()
+            .write(&[
+                &(position as i32),
+                &d.name,
+                &d.description,
+                &d.kind,
+                &d.password,
+                &d.konami_id,
+                &d.treated_as,
+                &d.tcg_date,
+                &d.ocg_date,
+                &d.monster_kind,
+                &d.monster_attribute,
+                &d.monster_race,
+                &d.monster_subtypes,
+                &d.monster_atk,
+                &d.monster_def,
+                &d.monster_level,
+                &d.monster_pendulum_scale,
+                &d.monster_pendulum_effect,
+                &d.monster_link_arrows,
+                &d.spell_kind,
+                &d.trap_kind,
+                &d.ygoprodeck_id,
+            ])
+            .await?;
+    }
+    writer.finish().await?;
+
+    let mut ids = vec![None; cards.len()];
+    let assignments = |source: &str| {
+        UPSERT_COLUMNS
+            .iter()
+            .map(|column| format!("{column} = {source}.{column}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    // Rows inserted rather than updated have no deleting transaction ID (xmax) yet
+    let rows = client
+        .query(
+            &format!(
+                r#"
+                INSERT INTO ygo_cards AS c ({columns})
+                SELECT {columns} FROM ygo_cards_import
+                WHERE konami_id IS NOT NULL
+                ON CONFLICT (konami_id) DO UPDATE SET
+                    {updates},
+                    updated_at = CURRENT_TIMESTAMP
+                RETURNING c.id, c.konami_id, (c.xmax = 0) AS inserted
+                "#,
+                updates = assignments("EXCLUDED")
+            ),
+            &[],
+        )
+        .await?;
+    let mut by_konami_id = HashMap::new();
+    for row in rows {
+        let konami_id: i32 = row.try_get("konami_id")?;
+        by_konami_id.insert(konami_id, (row.try_get("id")?, row.try_get("inserted")?));
+    }
+
+    let rows = client
+        .query(
+            &format!(
+                r#"
+                UPDATE ygo_cards c SET
+                    {updates},
+                    updated_at = CURRENT_TIMESTAMP
+                FROM ygo_cards_import s
+                WHERE s.konami_id IS NULL AND c.password = s.password
+                RETURNING c.id, s.position
+                "#,
+                updates = assignments("s")
+            ),
+            &[],
+        )
+        .await?;
+    for row in rows {
+        let position: i32 = row.try_get("position")?;
+        ids[position as usize] = Some((row.try_get("id")?, false));
+    }
+
+    let rows = client
+        .query(
+            &format!(
+                r#"
+                INSERT INTO ygo_cards ({columns})
+                SELECT {columns} FROM ygo_cards_import s
+                WHERE s.konami_id IS NULL
+                AND NOT EXISTS (SELECT 1 FROM ygo_cards c WHERE c.password = s.password)
+                RETURNING id, password
+                "#
+            ),
+            &[],
+        )
+        .await?;
+    let mut by_password = HashMap::new();
+    for row in rows {
+        let password: String = row.try_get("password")?;
+        by_password.insert(password, row.try_get("id")?);
+    }
+
+    for (id, d) in ids.iter_mut().zip(cards) {
+        if let Some(konami_id) = d.konami_id {
+            *id = by_konami_id.get(&konami_id).copied();
+        } else if let Some(password) = &d.password
+            && let Some(&card_id) = by_password.get(password)
+        {
+            *id = Some((card_id, true));
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Fills the status of the cards on the banlists currently in effect
+async fn load_banlists(client: &Client, cards: &mut [ygo::Card]) -> Result<(), Error> {
+    let card_ids: Vec<i32> = cards.iter().map(|card| card.id).collect();
+    let mut statuses = service::banlist::get_current_statuses(client, &card_ids).await?;
+
+    for card in cards {
+        card.banlist = statuses.remove(&card.id).unwrap_or_default();
+    }
+
+    Ok(())
+}
+
+/// Fills the status of a card on the banlists currently in effect
+async fn load_banlist(
+    client: &Client,
+    card: Option<ygo::Card>,
+) -> Result<Option<ygo::Card>, Error> {
+    let mut cards: Vec<ygo::Card> = card.into_iter().collect();
+    load_banlists(client, &mut cards).await?;
+
+    Ok(cards.pop())
 }
 
 #[cfg(test)]
@@ -443,6 +1220,7 @@ fn make_card(id: i32) -> ygo::Card {
                 monster_def: Some(2500),
                 ..Default::default()
             },
+            banlist: Default::default(),
         },
         2 => ygo::Card {
             id: 2,
@@ -458,6 +1236,7 @@ fn make_card(id: i32) -> ygo::Card {
                 monster_def: Some(2100),
                 ..Default::default()
             },
+            banlist: Default::default(),
         },
         _ => {
             let name = format!("Card {}", id);
@@ -504,6 +1283,7 @@ fn make_card(id: i32) -> ygo::Card {
                 id,
                 updated_at: chrono::Utc::now(),
                 data: card_data,
+                banlist: Default::default(),
             }
         }
     }
@@ -609,6 +1389,7 @@ impl TryFrom<&Row> for ygo::Card {
             id,
             updated_at: updated_at.0,
             data: value.try_into()?,
+            banlist: Default::default(),
         })
     }
 }
@@ -656,19 +1437,19 @@ mod tests {
             let _ = seed_cards(&client, 15).await.expect("seed");
 
             // Get first page (limit 5)
-            let (page1, next1) = get_page(&client, None, None, 5, None).await.expect("page1");
+            let (page1, next1, _) = get_page(&client, None, None, 5, None).await.expect("page1");
             assert_eq!(page1.len(), 5);
             assert!(next1.is_some());
 
             // Get second page
-            let (page2, next2) = get_page(&client, None, None, 5, next1)
+            let (page2, next2, _) = get_page(&client, None, None, 5, next1)
                 .await
                 .expect("page2");
             assert_eq!(page2.len(), 5);
             assert!(next2.is_some());
 
             // Get third page (should have 5 or less)
-            let (page3, next3) = get_page(&client, None, None, 5, next2)
+            let (page3, next3, _) = get_page(&client, None, None, 5, next2)
                 .await
                 .expect("page3");
             assert_eq!(page3.len(), 5);
@@ -682,7 +1463,7 @@ mod tests {
         with_db_pool(async move |db| {
             let client = db.get().await.expect("db");
             // No cards in DB
-            let (empty, next) = get_page(&client, None, None, 10, None)
+            let (empty, next, _) = get_page(&client, None, None, 10, None)
                 .await
                 .expect("empty");
             assert!(empty.is_empty());
@@ -690,7 +1471,7 @@ mod tests {
 
             // Seed exactly 3 cards
             let _ = seed_cards(&client, 3).await.expect("seed");
-            let (all, next) = get_page(&client, None, None, 10, None).await.expect("all");
+            let (all, next, _) = get_page(&client, None, None, 10, None).await.expect("all");
             assert_eq!(all.len(), 3);
             assert!(next.is_none());
         })
@@ -728,6 +1509,64 @@ mod tests {
         .await;
     }
 
+    #[tokio::test]
+    async fn test_upsert_all() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+
+            let existing = save_new(
+                &client,
+                &ygo::NewCard {
+                    data: ygo::CardData {
+                        name: "Before".to_string(),
+                        konami_id: Some(4007),
+                        ..Default::default()
+                    },
+                },
+            )
+            .await
+            .expect("insert");
+
+            let cards = vec![
+                ygo::CardData {
+                    name: "Blue-Eyes White Dragon".to_string(),
+                    konami_id: Some(4007),
+                    monster_subtypes: Some(vec![ygo::MonsterSubtype::Tuner]),
+                    ..Default::default()
+                },
+                ygo::CardData {
+                    name: "Dark Magician".to_string(),
+                    password: Some("46986414".to_string()),
+                    ..Default::default()
+                },
+                ygo::CardData {
+                    name: "Unknown".to_string(),
+                    ..Default::default()
+                },
+            ];
+            let ids = upsert_all(&client, &cards).await.expect("upsert");
+            assert_eq!(ids[0], Some((existing.id, false)));
+            let (dark_magician_id, inserted) = ids[1].expect("Dark Magician");
+            assert!(inserted);
+            assert_eq!(ids[2], None);
+
+            let updated = get_by_id(&client, existing.id).await.unwrap().unwrap();
+            assert_eq!(updated.data, cards[0]);
+
+            // Cards without Konami ID are matched by password
+            let mut renamed = cards[1].clone();
+            renamed.name = "Dark Magician (renamed)".to_string();
+            let ids = upsert_all(&client, &[renamed.clone()])
+                .await
+                .expect("upsert");
+            assert_eq!(ids, vec![Some((dark_magician_id, false))]);
+
+            let updated = get_by_id(&client, dark_magician_id).await.unwrap().unwrap();
+            assert_eq!(updated.data, renamed);
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn test_save_updates_card() {
         with_db_pool(async move |db| {
@@ -806,7 +1645,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.name = Some("blue-eyes".into());
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -848,7 +1687,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.description = Some("engine of destruction".into());
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -890,7 +1729,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.kind = Some(ygo::CardKind::Spell);
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -930,7 +1769,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.attribute = vec![ygo::MonsterAttribute::Light];
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -974,7 +1813,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.race = vec![ygo::MonsterRace::Dragon];
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -1018,7 +1857,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.subtype = vec![ygo::MonsterSubtype::Flip];
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -1067,7 +1906,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.subtype = vec![ygo::MonsterSubtype::Tuner, ygo::MonsterSubtype::Flip];
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -1116,7 +1955,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.atk_min = Some(2000);
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -1164,7 +2003,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.atk_max = Some(2000);
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -1212,7 +2051,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.def_min = Some(2000);
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -1260,7 +2099,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.def_max = Some(2000);
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -1308,7 +2147,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.level_min = Some(5);
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -1356,7 +2195,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.level_max = Some(4);
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -1402,7 +2241,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.spell = vec![ygo::SpellKind::QuickPlay];
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -1444,7 +2283,7 @@ mod tests {
 
             let mut filter = Filter::default();
             filter.trap = vec![ygo::TrapKind::Normal];
-            let (cards, _) = get_page(&client, Some(filter), None, 50, None)
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
                 .await
                 .unwrap();
             assert!(cards.iter().any(|c| c.id == c_ok.id));
@@ -1458,6 +2297,238 @@ mod tests {
         .await;
     }
 
+    #[tokio::test]
+    async fn test_filter_by_link_arrows() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+
+            let arrows = [
+                ("Top Right", ygo::LinkArrows::Top | ygo::LinkArrows::Right),
+                (
+                    "Top Right Bottom",
+                    ygo::LinkArrows::Top | ygo::LinkArrows::Right | ygo::LinkArrows::Bottom,
+                ),
+                ("Left", ygo::LinkArrows::Left),
+            ];
+            for (name, arrows) in arrows {
+                let new = ygo::NewCard {
+                    data: ygo::CardData {
+                        name: name.into(),
+                        monster_kind: Some(ygo::MonsterKind::Link),
+                        monster_link_arrows: Some(arrows),
+                        ..Default::default()
+                    },
+                };
+                save_new(&client, &new).await.expect("card");
+            }
+
+            let names = async |filter: &str| {
+                let filter = serde_html_form::from_str::<Filter>(filter).unwrap();
+                let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
+                    .await
+                    .unwrap();
+                cards.into_iter().map(|c| c.data.name).collect::<Vec<_>>()
+            };
+
+            assert_eq!(
+                names("linkArrowsAll=top&linkArrowsAll=right").await,
+                vec!["Top Right", "Top Right Bottom"]
+            );
+            assert_eq!(
+                names("linkArrowsAll=right&linkArrowsAll=bottom").await,
+                vec!["Top Right Bottom"]
+            );
+            assert_eq!(
+                names("linkArrowsAny=bottom&linkArrowsAny=left").await,
+                vec!["Top Right Bottom", "Left"]
+            );
+            assert!(names("linkArrowsAny=top_left").await.is_empty());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_filter_by_monster_kind_link_rating_and_scale() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+
+            let cards = [
+                ("Link 2", ygo::MonsterKind::Link, Some(2), None),
+                ("Link 4", ygo::MonsterKind::Link, Some(4), None),
+                ("Level 4", ygo::MonsterKind::Effect, Some(4), None),
+                ("Scale 1", ygo::MonsterKind::Effect, Some(4), Some(1)),
+                ("Scale 8", ygo::MonsterKind::Normal, Some(4), Some(8)),
+            ];
+            for (name, monster_kind, level, scale) in cards {
+                let new = ygo::NewCard {
+                    data: ygo::CardData {
+                        name: name.into(),
+                        monster_kind: Some(monster_kind),
+                        monster_level: level,
+                        monster_pendulum_scale: scale,
+                        ..Default::default()
+                    },
+                };
+                save_new(&client, &new).await.expect("card");
+            }
+
+            let names = async |filter: Filter| {
+                let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
+                    .await
+                    .unwrap();
+                cards.into_iter().map(|c| c.data.name).collect::<Vec<_>>()
+            };
+
+            let filter = Filter {
+                monster_kind: vec![ygo::MonsterKind::Link, ygo::MonsterKind::Normal],
+                ..Default::default()
+            };
+            assert_eq!(names(filter).await, vec!["Link 2", "Link 4", "Scale 8"]);
+
+            // Link rating only matches link monsters, even if others have the same level
+            let filter = Filter {
+                link_rating_min: Some(3),
+                ..Default::default()
+            };
+            assert_eq!(names(filter).await, vec!["Link 4"]);
+            let filter = Filter {
+                link_rating_max: Some(4),
+                ..Default::default()
+            };
+            assert_eq!(names(filter).await, vec!["Link 2", "Link 4"]);
+
+            let filter = Filter {
+                scale_min: Some(2),
+                ..Default::default()
+            };
+            assert_eq!(names(filter).await, vec!["Scale 8"]);
+            let filter = Filter {
+                scale_max: Some(5),
+                ..Default::default()
+            };
+            assert_eq!(names(filter).await, vec!["Scale 1"]);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_filter_by_release_dates_and_password() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+
+            let date = |y| NaiveDate::from_ymd_opt(y, 1, 1);
+            let cards = [
+                ("Both", date(2002), date(1999), Some("12345678")),
+                ("TCG only", date(2010), None, Some("23456789")),
+                ("OCG only", None, date(2015), None),
+                ("Unreleased", None, None, None),
+            ];
+            for (name, tcg_date, ocg_date, password) in cards {
+                let new = ygo::NewCard {
+                    data: ygo::CardData {
+                        name: name.into(),
+                        tcg_date,
+                        ocg_date,
+                        password: password.map(Into::into),
+                        ..Default::default()
+                    },
+                };
+                save_new(&client, &new).await.expect("card");
+            }
+
+            let names = async |filter: Filter| {
+                let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
+                    .await
+                    .unwrap();
+                cards.into_iter().map(|c| c.data.name).collect::<Vec<_>>()
+            };
+
+            let filter = Filter {
+                tcg_date_from: date(2002),
+                tcg_date_to: date(2005),
+                ..Default::default()
+            };
+            assert_eq!(names(filter).await, vec!["Both"]);
+            let filter = Filter {
+                ocg_date_from: date(2000),
+                ..Default::default()
+            };
+            assert_eq!(names(filter).await, vec!["OCG only"]);
+
+            let filter = Filter {
+                only_in: Some(ReleaseRegion::Tcg),
+                ..Default::default()
+            };
+            assert_eq!(names(filter).await, vec!["TCG only"]);
+            let filter = Filter {
+                only_in: Some(ReleaseRegion::Ocg),
+                ..Default::default()
+            };
+            assert_eq!(names(filter).await, vec!["OCG only"]);
+
+            let filter = Filter {
+                has_password: Some(false),
+                ..Default::default()
+            };
+            assert_eq!(names(filter).await, vec!["OCG only", "Unreleased"]);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_filter_by_banlist() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 3).await.expect("seed");
+
+            let date = NaiveDate::from_ymd_opt(2005, 4, 1).unwrap();
+            let limits = std::collections::HashMap::from([(1, 0), (2, 1)]);
+            service::banlist::save(&client, ygo::BanlistFormat::Tcg, date, &limits)
+                .await
+                .unwrap();
+            let limits = std::collections::HashMap::from([(3, 0)]);
+            service::banlist::save(&client, ygo::BanlistFormat::Ocg, date, &limits)
+                .await
+                .unwrap();
+
+            let filter = Filter {
+                banlist: Some(ygo::BanStatus::Forbidden),
+                ..Default::default()
+            };
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
+                .await
+                .unwrap();
+            let ids: Vec<_> = cards.iter().map(|c| c.id).collect();
+            assert_eq!(ids, vec![1]);
+            assert_eq!(
+                cards[0].banlist.get(&ygo::BanlistFormat::Tcg),
+                Some(&ygo::BanStatus::Forbidden)
+            );
+
+            let filter = Filter {
+                banlist: Some(ygo::BanStatus::Forbidden),
+                banlist_format: Some(ygo::BanlistFormat::Ocg),
+                ..Default::default()
+            };
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
+                .await
+                .unwrap();
+            let ids: Vec<_> = cards.iter().map(|c| c.id).collect();
+            assert_eq!(ids, vec![3]);
+
+            let filter = Filter {
+                banlist: Some(ygo::BanStatus::Unlimited),
+                ..Default::default()
+            };
+            let (cards, _, _) = get_page(&client, Some(filter), None, 50, None)
+                .await
+                .unwrap();
+            let ids: Vec<_> = cards.iter().map(|c| c.id).collect();
+            assert_eq!(ids, vec![3]);
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn test_sort_by_name_asc() {
         with_db_pool(async move |db| {
@@ -1492,11 +2563,12 @@ mod tests {
 
             let filter = Filter::default();
             let sort = Sort {
-                sort: SortingField::Name,
+                sort: vec![SortingField::Name.into()],
                 dir: SortingDirection::Asc,
+                ..Default::default()
             };
 
-            let (cards, next) = get_page(&client, Some(filter), Some(sort), 10, None)
+            let (cards, next, _) = get_page(&client, Some(filter), Some(sort), 10, None)
                 .await
                 .unwrap();
 
@@ -1547,11 +2619,12 @@ mod tests {
 
             let filter = Filter::default();
             let sort = Sort {
-                sort: SortingField::Atk,
+                sort: vec![SortingField::Atk.into()],
                 dir: SortingDirection::Asc,
+                ..Default::default()
             };
 
-            let (cards, _) = get_page(&client, Some(filter), Some(sort), 10, None)
+            let (cards, _, _) = get_page(&client, Some(filter), Some(sort), 10, None)
                 .await
                 .unwrap();
 
@@ -1600,11 +2673,12 @@ mod tests {
 
             let filter = Filter::default();
             let sort = Sort {
-                sort: SortingField::Def,
+                sort: vec![SortingField::Def.into()],
                 dir: SortingDirection::Asc,
+                ..Default::default()
             };
 
-            let (cards, _) = get_page(&client, Some(filter), Some(sort), 10, None)
+            let (cards, _, _) = get_page(&client, Some(filter), Some(sort), 10, None)
                 .await
                 .unwrap();
 
@@ -1653,11 +2727,12 @@ mod tests {
 
             let filter = Filter::default();
             let sort = Sort {
-                sort: SortingField::Level,
+                sort: vec![SortingField::Level.into()],
                 dir: SortingDirection::Asc,
+                ..Default::default()
             };
 
-            let (cards, _) = get_page(&client, Some(filter), Some(sort), 10, None)
+            let (cards, _, _) = get_page(&client, Some(filter), Some(sort), 10, None)
                 .await
                 .unwrap();
 
@@ -1710,11 +2785,12 @@ mod tests {
 
             let filter = Filter::default();
             let sort = Sort {
-                sort: SortingField::TcgDate,
+                sort: vec![SortingField::TcgDate.into()],
                 dir: SortingDirection::Asc,
+                ..Default::default()
             };
 
-            let (cards, _) = get_page(&client, Some(filter), Some(sort), 10, None)
+            let (cards, _, _) = get_page(&client, Some(filter), Some(sort), 10, None)
                 .await
                 .unwrap();
 
@@ -1765,11 +2841,12 @@ mod tests {
 
             let filter = Filter::default();
             let sort = Sort {
-                sort: SortingField::OcgDate,
+                sort: vec![SortingField::OcgDate.into()],
                 dir: SortingDirection::Asc,
+                ..Default::default()
             };
 
-            let (cards, _) = get_page(&client, Some(filter), Some(sort), 10, None)
+            let (cards, _, _) = get_page(&client, Some(filter), Some(sort), 10, None)
                 .await
                 .unwrap();
 
@@ -1813,12 +2890,13 @@ mod tests {
             let _ = save_new(&client, &c).await.unwrap();
 
             // Page 1: limit 2, sorted by name ASC -> [Alice, Bob]
-            let (p1, next) = get_page(
+            let (p1, next, _) = get_page(
                 &client,
                 None,
                 Some(Sort {
-                    sort: SortingField::Name,
+                    sort: vec![SortingField::Name.into()],
                     dir: SortingDirection::Asc,
+                    ..Default::default()
                 }),
                 2,
                 None,
@@ -1831,12 +2909,13 @@ mod tests {
             assert!(next.is_some());
 
             // Page 2 using cursor -> [Charlie]
-            let (p2, next2) = get_page(
+            let (p2, next2, _) = get_page(
                 &client,
                 None,
                 Some(Sort {
-                    sort: SortingField::Name,
+                    sort: vec![SortingField::Name.into()],
                     dir: SortingDirection::Asc,
+                    ..Default::default()
                 }),
                 2,
                 next,
@@ -1849,4 +2928,451 @@ mod tests {
         })
         .await;
     }
+
+    #[tokio::test]
+    async fn test_sort_by_price_with_cursor() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 3).await.expect("seed");
+
+            let old = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+            let new = NaiveDate::from_ymd_opt(2025, 2, 1).unwrap();
+            let snapshots = [
+                (1, old, ygo::PriceSource::Tcgplayer, "9.00"),
+                (1, new, ygo::PriceSource::Tcgplayer, "1.00"),
+                (2, new, ygo::PriceSource::Tcgplayer, "5.00"),
+                (2, new, ygo::PriceSource::Cardmarket, "0.50"),
+                (3, new, ygo::PriceSource::Tcgplayer, "3.00"),
+            ];
+            for (card_id, date, source, price) in snapshots {
+                let prices = [(card_id, source, price.parse().unwrap())];
+                service::price::save_snapshots(&client, date, &prices)
+                    .await
+                    .unwrap();
+            }
+
+            // Latest prices are used: 2 ($5), 3 ($3), 1 ($1)
+            let sort = || Sort {
+                sort: vec![SortingField::Price.into()],
+                dir: SortingDirection::Desc,
+                ..Default::default()
+            };
+            let (page1, next, _) = get_page(&client, None, Some(sort()), 2, None)
+                .await
+                .unwrap();
+            let ids: Vec<_> = page1.iter().map(|c| c.id).collect();
+            assert_eq!(ids, vec![2, 3]);
+
+            let (page2, _, _) = get_page(&client, None, Some(sort()), 1, next)
+                .await
+                .unwrap();
+            let ids: Vec<_> = page2.iter().map(|c| c.id).collect();
+            assert_eq!(ids, vec![1]);
+
+            let sort = Sort {
+                sort: vec![SortingField::Price.into()],
+                dir: SortingDirection::Asc,
+                price_source: ygo::PriceSource::Cardmarket,
+            };
+            let (cards, _, _) = get_page(&client, None, Some(sort), 1, None).await.unwrap();
+            assert_eq!(cards[0].id, 2);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_search_sorted_by_relevance_with_cursor() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+
+            let cards = [
+                (
+                    "Blue-Eyes White Dragon",
+                    "This legendary dragon is a powerful engine of destruction.",
+                    None,
+                ),
+                (
+                    "Dark Magician",
+                    "The ultimate wizard in terms of attack and defense.",
+                    None,
+                ),
+                (
+                    "Dragon Shrine",
+                    "Send 1 Dragon monster from your Deck to the GY.",
+                    None,
+                ),
+                (
+                    "Red-Eyes Black Dragon",
+                    "A ferocious dragon with a deadly attack.",
+                    None,
+                ),
+                ("Polymerization", "Fusion Summon 1 Fusion Monster.", None),
+                (
+                    "Odd-Eyes Magician",
+                    "Once per turn, you can draw 1 card.",
+                    Some("Destroy 1 Dragons you control."),
+                ),
+            ];
+            let mut ids = Vec::new();
+            for (name, description, pendulum_effect) in cards {
+                let new = ygo::NewCard {
+                    data: ygo::CardData {
+                        name: name.to_string(),
+                        description: description.to_string(),
+                        monster_pendulum_effect: pendulum_effect.map(|e| e.to_string()),
+                        ..Default::default()
+                    },
+                };
+                ids.push(save_new(&client, &new).await.expect("card").id);
+            }
+
+            let filter = |q: &str| Filter {
+                q: service::query::parse(q).ok(),
+                ..Default::default()
+            };
+            let sort = || Sort {
+                sort: vec![SortingField::Relevance.into()],
+                dir: SortingDirection::Desc,
+                ..Default::default()
+            };
+
+            // Words are stemmed, and matches in names rank higher
+            let (all, next, _) = get_page(&client, Some(filter("dragons")), Some(sort()), 10, None)
+                .await
+                .expect("search");
+            assert!(next.is_none());
+            let found: Vec<_> = all.iter().map(|c| c.id).collect();
+            assert_eq!(found.len(), 4);
+            assert!(!found.contains(&ids[1]) && !found.contains(&ids[4]));
+            assert_eq!(found.last(), Some(&ids[5]));
+
+            // Paginating keeps the same order
+            let mut paginated = Vec::new();
+            let mut cursor = None;
+            loop {
+                let (page, next, _) =
+                    get_page(&client, Some(filter("dragons")), Some(sort()), 1, cursor)
+                        .await
+                        .expect("page");
+                paginated.extend(page.iter().map(|c| c.id));
+                if next.is_none() {
+                    break;
+                }
+                cursor = next;
+            }
+            assert_eq!(paginated, found);
+
+            let (cards, _, _) =
+                get_page(&client, Some(filter("\"dark magician\"")), None, 10, None)
+                    .await
+                    .expect("search");
+            let found: Vec<_> = cards.iter().map(|c| c.id).collect();
+            assert_eq!(found, vec![ids[1]]);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_lookup_tolerates_typos() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+
+            let mut ids = Vec::new();
+            for name in [
+                "Blue-Eyes White Dragon",
+                "Blue-Eyes Alternative White Dragon",
+                "Dark Magician",
+            ] {
+                let new = ygo::NewCard {
+                    data: ygo::CardData {
+                        name: name.to_string(),
+                        ..Default::default()
+                    },
+                };
+                ids.push(save_new(&client, &new).await.expect("card").id);
+            }
+
+            let matches = lookup(&client, "blue eyes white dragn", 10)
+                .await
+                .expect("lookup");
+            let found: Vec<_> = matches.iter().map(|m| m.card.id).collect();
+            assert_eq!(found, vec![ids[0], ids[1]]);
+            assert!(matches[0].similarity > matches[1].similarity);
+            assert!(matches[0].similarity < 1.0);
+
+            let matches = lookup(&client, "Dark Magician", 1).await.expect("lookup");
+            assert_eq!(matches.len(), 1);
+            assert_eq!(matches[0].card.id, ids[2]);
+            assert_eq!(matches[0].similarity, 1.0);
+
+            let matches = lookup(&client, "Polymerization", 10).await.expect("lookup");
+            assert!(matches.is_empty());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_get_facets() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+
+            let monster =
+                |attribute, race, kind, level, subtypes: Vec<ygo::MonsterSubtype>| ygo::CardData {
+                    kind: ygo::CardKind::Monster,
+                    monster_attribute: Some(attribute),
+                    monster_race: Some(race),
+                    monster_kind: Some(kind),
+                    monster_level: Some(level),
+                    monster_subtypes: Some(subtypes),
+                    ..Default::default()
+                };
+            let cards = [
+                monster(
+                    ygo::MonsterAttribute::Dark,
+                    ygo::MonsterRace::Spellcaster,
+                    ygo::MonsterKind::Normal,
+                    7,
+                    vec![],
+                ),
+                monster(
+                    ygo::MonsterAttribute::Light,
+                    ygo::MonsterRace::Dragon,
+                    ygo::MonsterKind::Normal,
+                    8,
+                    vec![],
+                ),
+                monster(
+                    ygo::MonsterAttribute::Dark,
+                    ygo::MonsterRace::Dragon,
+                    ygo::MonsterKind::Effect,
+                    7,
+                    vec![ygo::MonsterSubtype::Tuner, ygo::MonsterSubtype::Flip],
+                ),
+                ygo::CardData {
+                    kind: ygo::CardKind::Spell,
+                    spell_kind: Some(ygo::SpellKind::QuickPlay),
+                    ..Default::default()
+                },
+            ];
+            for data in cards {
+                save_new(&client, &ygo::NewCard { data })
+                    .await
+                    .expect("card");
+            }
+
+            let counts = |entries: &[(&str, i64)]| -> BTreeMap<String, i64> {
+                entries.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+            };
+
+            let facets = get_facets(&client, Filter::default())
+                .await
+                .expect("facets");
+            assert_eq!(
+                facets,
+                Facets {
+                    attribute: counts(&[("dark", 2), ("light", 1)]),
+                    race: counts(&[("dragon", 2), ("spellcaster", 1)]),
+                    monster_kind: counts(&[("effect", 1), ("normal", 2)]),
+                    subtype: counts(&[("flip", 1), ("tuner", 1)]),
+                    level: BTreeMap::from([(7, 2), (8, 1)]),
+                    spell: counts(&[("quick_play", 1)]),
+                    trap: BTreeMap::new(),
+                }
+            );
+
+            // Facets ignore their own part of the filter
+            let filter = Filter {
+                attribute: vec![ygo::MonsterAttribute::Dark],
+                race: vec![ygo::MonsterRace::Dragon],
+                ..Default::default()
+            };
+            let facets = get_facets(&client, filter).await.expect("facets");
+            assert_eq!(facets.attribute, counts(&[("dark", 1), ("light", 1)]));
+            assert_eq!(facets.race, counts(&[("dragon", 1), ("spellcaster", 1)]));
+            assert_eq!(facets.monster_kind, counts(&[("effect", 1)]));
+            assert_eq!(facets.level, BTreeMap::from([(7, 1)]));
+            assert!(facets.spell.is_empty());
+        })
+        .await;
+    }
+
+    #[test]
+    fn test_plan_rows() {
+        assert_eq!(
+            plan_rows("Seq Scan on ygo_cards  (cost=0.00..412.00 rows=12000 width=4)"),
+            Some(12000)
+        );
+        assert_eq!(plan_rows("Result  (cost=0.00..0.01 width=4)"), None);
+    }
+
+    #[tokio::test]
+    async fn test_count() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 3).await.expect("seed");
+
+            let total = count(&client, None).await.expect("count");
+            assert_eq!(
+                total,
+                Total {
+                    count: 3,
+                    estimated: false
+                }
+            );
+
+            let filter = Filter {
+                name: Some("Blue-Eyes".to_string()),
+                ..Default::default()
+            };
+            let total = count(&client, Some(filter)).await.expect("count");
+            assert_eq!(total.count, 1);
+            assert!(!total.estimated);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_walk_pages_backward() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 5).await.expect("seed");
+
+            let ids = |cards: &[ygo::Card]| cards.iter().map(|c| c.id).collect::<Vec<_>>();
+            let sort = || Sort {
+                sort: vec![SortingField::Name.into()],
+                dir: SortingDirection::Desc,
+                ..Default::default()
+            };
+
+            let (all, _, _) = get_page(&client, None, Some(sort()), 10, None)
+                .await
+                .expect("all");
+            let all = ids(&all);
+
+            let (page1, next, prev) = get_page(&client, None, Some(sort()), 2, None)
+                .await
+                .expect("page1");
+            assert_eq!(ids(&page1), all[0..2]);
+            assert!(prev.is_none());
+
+            let (page2, next, prev) = get_page(&client, None, Some(sort()), 2, next)
+                .await
+                .expect("page2");
+            assert_eq!(ids(&page2), all[2..4]);
+            assert!(next.is_some());
+
+            // Going back from the second page returns the first page
+            let prev = prev.expect("prev");
+            assert!(prev.backward);
+            let (back, next, prev) = get_page(&client, None, Some(sort()), 2, Some(prev))
+                .await
+                .expect("back");
+            assert_eq!(ids(&back), all[0..2]);
+            assert!(prev.is_none());
+
+            let (forward, _, _) = get_page(&client, None, Some(sort()), 2, next)
+                .await
+                .expect("forward");
+            assert_eq!(ids(&forward), all[2..4]);
+        })
+        .await;
+    }
+
+    #[test]
+    fn test_deserialize_sort_keys() {
+        let sort: Sort =
+            serde_html_form::from_str("sort=level:desc,atk,name:asc&dir=desc").unwrap();
+        assert_eq!(
+            sort.sort,
+            vec![
+                SortKey {
+                    field: SortingField::Level,
+                    dir: Some(SortingDirection::Desc)
+                },
+                SortingField::Atk.into(),
+                SortKey {
+                    field: SortingField::Name,
+                    dir: Some(SortingDirection::Asc)
+                },
+            ]
+        );
+        assert_eq!(sort.dir, SortingDirection::Desc);
+
+        let error = serde_html_form::from_str::<Sort>("sort=level:down").unwrap_err();
+        assert_eq!(error.to_string(), "unknown sorting direction 'down'");
+        let error = serde_html_form::from_str::<Sort>("sort=speed").unwrap_err();
+        assert_eq!(error.to_string(), "unknown sorting field 'speed'");
+    }
+
+    #[tokio::test]
+    async fn test_sort_by_multiple_fields_with_nulls() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+
+            let cards = [
+                ("A", Some(4), Some(1800)),
+                ("B", Some(4), Some(1800)),
+                ("C", Some(4), None),
+                ("D", Some(7), Some(2500)),
+                ("E", None, None),
+                ("F", Some(4), Some(2000)),
+            ];
+            for (name, level, atk) in cards {
+                let new = ygo::NewCard {
+                    data: ygo::CardData {
+                        name: name.to_string(),
+                        monster_level: level,
+                        monster_atk: atk,
+                        ..Default::default()
+                    },
+                };
+                save_new(&client, &new).await.expect("card");
+            }
+
+            let sort =
+                || serde_html_form::from_str::<Sort>("sort=level:desc,atk:desc,name:asc").unwrap();
+            let names = |cards: &[ygo::Card]| {
+                cards
+                    .iter()
+                    .map(|c| c.data.name.clone())
+                    .collect::<Vec<_>>()
+            };
+            let expected = vec!["D", "F", "A", "B", "C", "E"];
+
+            let (all, _, _) = get_page(&client, None, Some(sort()), 10, None)
+                .await
+                .expect("all");
+            assert_eq!(names(&all), expected);
+
+            // Walk forward one card at a time, through cards without level or ATK
+            let mut forward = Vec::new();
+            let mut cursor = None;
+            let mut last_prev = None;
+            loop {
+                let (page, next, prev) = get_page(&client, None, Some(sort()), 1, cursor)
+                    .await
+                    .expect("page");
+                forward.extend(names(&page));
+                last_prev = prev.or(last_prev);
+                match next {
+                    Some(next) => cursor = Some(next),
+                    None => break,
+                }
+            }
+            assert_eq!(forward, expected);
+
+            // Then walk back to the first card
+            let mut backward = Vec::new();
+            let mut cursor = last_prev;
+            while let Some(prev) = cursor {
+                let (page, _, prev) = get_page(&client, None, Some(sort()), 2, Some(prev))
+                    .await
+                    .expect("page");
+                backward.splice(0..0, names(&page));
+                cursor = prev;
+            }
+            assert_eq!(backward, expected[..5]);
+        })
+        .await;
+    }
 }
@@ -0,0 +1,656 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::result::Result;
+use tokio_postgres::{Client, Error, Row};
+
+use crate::database::{QueryParams, TzTimestamp};
+use crate::models::ygo;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PageCursor {
+    pub id: i32,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Filter {
+    pub card_id: Option<i32>,
+}
+
+/// Retrieves collection items with cursor-based pagination
+pub async fn get_page(
+    client: &Client,
+    filter: Option<Filter>,
+    limit: u32,
+    cursor: Option<PageCursor>,
+) -> Result<(Vec<ygo::CollectionItem>, Option<PageCursor>), Error> {
+    let mut query = String::from("SELECT * FROM ygo_collection_items");
+    let mut params = QueryParams::new();
+    let mut where_queries: Vec<String> = Vec::new();
+
+    if let Some(filter) = filter {
+        // Filter by card
+        if let Some(card_id) = filter.card_id {
+            let idx = params.push(card_id);
+            where_queries.push(format!("card_id = ${idx}"));
+        }
+    }
+
+    // Retrieve only items after the cursor index
+    if let Some(PageCursor { id }) = cursor {
+        let idx = params.push(id);
+        where_queries.push(format!("id > ${idx}"));
+    }
+
+    if !where_queries.is_empty() {
+        query.push_str(" WHERE ");
+        query.push_str(&where_queries.join(" AND "));
+    }
+
+    // Retrieve one extra item to check if there's still another page
+    let idx = params.push((limit + 1) as i64);
+    query.push_str(&format!(" ORDER BY id ASC LIMIT ${idx}"));
+
+    let rows = client.query(&query, &params.as_refs()).await?;
+
+    let items: Vec<ygo::CollectionItem> = rows
+        .iter()
+        .take(limit as usize)
+        .map(|row| row.try_into())
+        .collect::<Result<_, _>>()?;
+
+    let next_cursor = if rows.len() > limit as usize {
+        items.last().map(|item| PageCursor { id: item.id })
+    } else {
+        None
+    };
+
+    Ok((items, next_cursor))
+}
+
+/// Retrieves a collection item by ID
+pub async fn get_by_id(client: &Client, id: i32) -> Result<Option<ygo::CollectionItem>, Error> {
+    let query = "SELECT * FROM ygo_collection_items WHERE id = $1";
+    let row = &client.query_opt(query, &[&id]).await?;
+
+    row.as_ref().map(|r| r.try_into()).transpose()
+}
+
+/// Deletes a collection item by ID. Returns true if a row was deleted, false otherwise.
+pub async fn delete_by_id(client: &Client, id: i32) -> Result<bool, Error> {
+    let affected = client
+        .execute("DELETE FROM ygo_collection_items WHERE id = $1", &[&id])
+        .await?;
+    Ok(affected == 1)
+}
+
+/// Insert a new collection item and return the created record.
+pub async fn save_new(
+    client: &Client,
+    new_item: &ygo::NewCollectionItem,
+) -> Result<ygo::CollectionItem, Error> {
+    let d = &new_item.data;
+    let row = client
+        .query_one(
+            r#"
+            INSERT INTO ygo_collection_items (
+                card_id,
+                quantity,
+                condition,
+                language,
+                first_edition,
+                acquisition_date,
+                acquisition_price,
+                print_id
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8
+            ) RETURNING *
+            "#,
+            &[
+                &d.card_id,
+                &d.quantity,
+                &d.condition,
+                &d.language,
+                &d.first_edition,
+                &d.acquisition_date,
+                &d.acquisition_price,
+                &d.print_id,
+            ],
+        )
+        .await?;
+
+    (&row).try_into()
+}
+
+/// Update an existing collection item by id and return the updated record.
+pub async fn save(
+    client: &Client,
+    item: &ygo::CollectionItem,
+) -> Result<Option<ygo::CollectionItem>, Error> {
+    let d = &item.data;
+    let row = client
+        .query_opt(
+            r#"
+            UPDATE ygo_collection_items SET
+                card_id = $1,
+                quantity = $2,
+                condition = $3,
+                language = $4,
+                first_edition = $5,
+                acquisition_date = $6,
+                acquisition_price = $7,
+                print_id = $8,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = $9
+            RETURNING *
+            "#,
+            &[
+                &d.card_id,
+                &d.quantity,
+                &d.condition,
+                &d.language,
+                &d.first_edition,
+                &d.acquisition_date,
+                &d.acquisition_price,
+                &d.print_id,
+                &item.id,
+            ],
+        )
+        .await?;
+
+    row.as_ref().map(|r| r.try_into()).transpose()
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueFilter {
+    /// Date of the valuation, today by default
+    pub date: Option<NaiveDate>,
+    /// Previous date to compare the valuation with
+    pub compare_to: Option<NaiveDate>,
+    pub source: Option<ygo::PriceSource>,
+}
+
+/// Value of the collection at a date, and its change since a previous date
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionValue {
+    pub date: NaiveDate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_date: Option<NaiveDate>,
+    pub sources: Vec<SourceValue>,
+}
+
+/// Value of the collection according to the prices of a source
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceValue {
+    pub source: ygo::PriceSource,
+    #[serde(flatten)]
+    pub value: Value,
+    pub by_set: Vec<GroupValue>,
+    pub by_rarity: Vec<GroupValue>,
+    pub by_kind: Vec<GroupValue>,
+}
+
+/// Value of the collection items sharing a set, rarity or kind.
+/// The name is missing for items whose printing is unknown.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupValue {
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub value: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Value {
+    pub total: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_total: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change: Option<Decimal>,
+}
+
+impl Value {
+    fn new(total: Option<Decimal>, previous_total: Option<Decimal>, compared: bool) -> Self {
+        let total = total.unwrap_or_default();
+        let previous_total = compared.then(|| previous_total.unwrap_or_default());
+
+        Self {
+            total,
+            previous_total,
+            change: previous_total.map(|previous| total - previous),
+        }
+    }
+}
+
+/// Computes the value of the collection for each price source, using the latest known price of
+/// each card at the date. Totals are also broken down by set, rarity and card kind.
+pub async fn get_value(client: &Client, filter: ValueFilter) -> Result<CollectionValue, Error> {
+    let date = filter
+        .date
+        .unwrap_or_else(|| chrono::Utc::now().date_naive());
+    let sources = match filter.source {
+        Some(source) => vec![source],
+        None => ygo::PriceSource::ALL.to_vec(),
+    };
+
+    // GROUPING() tells which breakdown a row belongs to: 7 for the total,
+    // 3 for sets, 5 for rarities, and 6 for kinds
+    let query = r#"
+        WITH items AS (
+            SELECT
+                sources.source,
+                i.quantity,
+                s.name AS set_name,
+                p.rarity,
+                c.kind::TEXT AS kind,
+                (
+                    SELECT price FROM ygo_card_prices
+                    WHERE card_id = i.card_id AND source = sources.source AND date <= $1
+                    ORDER BY date DESC
+                    LIMIT 1
+                ) AS price,
+                (
+                    SELECT price FROM ygo_card_prices
+                    WHERE card_id = i.card_id AND source = sources.source AND date <= $2
+                    ORDER BY date DESC
+                    LIMIT 1
+                ) AS previous_price
+            FROM ygo_collection_items i
+            JOIN ygo_cards c ON c.id = i.card_id
+            LEFT JOIN ygo_card_prints p ON p.id = i.print_id
+            LEFT JOIN ygo_sets s ON s.id = p.set_id
+            CROSS JOIN UNNEST($3::YGO_PRICE_SOURCE[]) AS sources (source)
+        )
+        SELECT
+            source,
+            set_name,
+            rarity,
+            kind,
+            GROUPING(set_name, rarity, kind) AS breakdown,
+            SUM(quantity * price) AS total,
+            SUM(quantity * previous_price) AS previous_total
+        FROM items
+        GROUP BY GROUPING SETS ((source), (source, set_name), (source, rarity), (source, kind))
+        ORDER BY source, total DESC NULLS LAST, set_name, rarity, kind
+    "#;
+    let rows = client
+        .query(query, &[&date, &filter.compare_to, &sources])
+        .await?;
+
+    let compared = filter.compare_to.is_some();
+    let mut values: Vec<SourceValue> = sources
+        .iter()
+        .map(|&source| SourceValue {
+            source,
+            value: Value::new(None, None, compared),
+            by_set: Vec::new(),
+            by_rarity: Vec::new(),
+            by_kind: Vec::new(),
+        })
+        .collect();
+
+    for row in rows {
+        let source: ygo::PriceSource = row.try_get("source")?;
+        let Some(source_value) = values.iter_mut().find(|v| v.source == source) else {
+            continue;
+        };
+        let value = Value::new(
+            row.try_get("total")?,
+            row.try_get("previous_total")?,
+            compared,
+        );
+
+        let breakdown: i32 = row.try_get("breakdown")?;
+        let (groups, column) = match breakdown {
+            3 => (&mut source_value.by_set, "set_name"),
+            5 => (&mut source_value.by_rarity, "rarity"),
+            6 => (&mut source_value.by_kind, "kind"),
+            _ => {
+                source_value.value = value;
+                continue;
+            }
+        };
+        groups.push(GroupValue {
+            name: row.try_get(column)?,
+            value,
+        });
+    }
+
+    Ok(CollectionValue {
+        date,
+        previous_date: filter.compare_to,
+        sources: values,
+    })
+}
+
+impl TryFrom<&Row> for ygo::CollectionItem {
+    type Error = Error;
+
+    /// Converts a database row into a CollectionItem struct
+    fn try_from(value: &Row) -> Result<Self, Self::Error> {
+        let updated_at: TzTimestamp = value.try_get("updated_at")?;
+
+        Ok(Self {
+            id: value.try_get("id")?,
+            updated_at: updated_at.0,
+            data: value.try_into()?,
+        })
+    }
+}
+
+impl TryFrom<&Row> for ygo::CollectionItemData {
+    type Error = Error;
+
+    fn try_from(value: &Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            card_id: value.try_get("card_id")?,
+            print_id: value.try_get("print_id")?,
+            quantity: value.try_get("quantity")?,
+            condition: value.try_get("condition")?,
+            language: value.try_get("language")?,
+            first_edition: value.try_get("first_edition")?,
+            acquisition_date: value.try_get("acquisition_date")?,
+            acquisition_price: value.try_get("acquisition_price")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::services::ygo::{card::seed_cards, price, set};
+    use crate::test_utils::with_db_pool;
+
+    fn make_item(card_id: i32, quantity: i32) -> ygo::NewCollectionItem {
+        ygo::NewCollectionItem {
+            data: ygo::CollectionItemData {
+                card_id,
+                quantity,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_new_inserts_and_returns_item() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 1).await.expect("seed");
+
+            let new = ygo::NewCollectionItem {
+                data: ygo::CollectionItemData {
+                    card_id: 1,
+                    print_id: None,
+                    quantity: 3,
+                    condition: ygo::CardCondition::LightlyPlayed,
+                    language: ygo::CardLanguage::French,
+                    first_edition: true,
+                    acquisition_date: chrono::NaiveDate::from_ymd_opt(2024, 2, 29),
+                    acquisition_price: Some(Decimal::new(1250, 2)),
+                },
+            };
+
+            let created = save_new(&client, &new).await.expect("insert");
+            assert!(created.id > 0);
+            assert_eq!(created.data, new.data);
+
+            let fetched = get_by_id(&client, created.id).await.expect("fetch");
+            assert_eq!(fetched, Some(created));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_save_new_rejects_non_positive_quantity() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 1).await.expect("seed");
+
+            let result = save_new(&client, &make_item(1, 0)).await;
+            assert!(result.is_err());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_save_updates_item() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 1).await.expect("seed");
+
+            let created = save_new(&client, &make_item(1, 1)).await.expect("insert");
+
+            let mut to_update = created.clone();
+            to_update.data.quantity = 2;
+            to_update.data.condition = ygo::CardCondition::Damaged;
+
+            let updated = save(&client, &to_update)
+                .await
+                .expect("save")
+                .expect("updated");
+            assert_eq!(updated.id, created.id);
+            assert_eq!(updated.data.quantity, 2);
+            assert_eq!(updated.data.condition, ygo::CardCondition::Damaged);
+
+            // Missing items are not updated
+            to_update.id = 9999;
+            let missing = save(&client, &to_update).await.expect("save");
+            assert!(missing.is_none());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_delete_by_id() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 1).await.expect("seed");
+
+            let created = save_new(&client, &make_item(1, 1)).await.expect("insert");
+
+            assert!(delete_by_id(&client, created.id).await.expect("delete"));
+            assert!(!delete_by_id(&client, created.id).await.expect("delete"));
+            assert!(
+                get_by_id(&client, created.id)
+                    .await
+                    .expect("fetch")
+                    .is_none()
+            );
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_get_page_filters_by_card_and_paginates() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 2).await.expect("seed");
+
+            for _ in 0..3 {
+                save_new(&client, &make_item(1, 1)).await.expect("insert");
+            }
+            save_new(&client, &make_item(2, 1)).await.expect("insert");
+
+            let filter = || Filter { card_id: Some(1) };
+
+            let (page1, next) = get_page(&client, Some(filter()), 2, None)
+                .await
+                .expect("page1");
+            assert_eq!(page1.len(), 2);
+            assert!(next.is_some());
+
+            let (page2, next) = get_page(&client, Some(filter()), 2, next)
+                .await
+                .expect("page2");
+            assert_eq!(page2.len(), 1);
+            assert!(next.is_none());
+
+            assert!(
+                page1
+                    .iter()
+                    .chain(page2.iter())
+                    .all(|item| item.data.card_id == 1)
+            );
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_items_are_deleted_with_their_card() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 1).await.expect("seed");
+
+            let created = save_new(&client, &make_item(1, 1)).await.expect("insert");
+            crate::services::ygo::card::delete_by_id(&client, 1)
+                .await
+                .expect("delete card");
+
+            assert!(
+                get_by_id(&client, created.id)
+                    .await
+                    .expect("fetch")
+                    .is_none()
+            );
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_get_value() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 2).await.expect("seed");
+
+            let date = |day| NaiveDate::from_ymd_opt(2025, 1, day).unwrap();
+            let decimal = |value: &str| value.parse::<Decimal>().unwrap();
+
+            let lob = set::upsert(
+                &client,
+                &ygo::CardSetData {
+                    name: "Legend of Blue Eyes".to_string(),
+                    code: "LOB".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .expect("set");
+            let prints: Vec<_> = [(1, "LOB-001", "Ultra Rare"), (2, "LOB-005", "Secret Rare")]
+                .into_iter()
+                .map(|(card_id, code, rarity)| ygo::NewCardPrint {
+                    card_id,
+                    set_id: lob.id,
+                    code: code.to_string(),
+                    rarity: rarity.to_string(),
+                    rarity_code: None,
+                })
+                .collect();
+            set::upsert_prints(&client, &prints).await.expect("prints");
+            let mut print_ids = Vec::new();
+            for card_id in [1, 2] {
+                let prints = set::get_prints_by_card_id(&client, card_id)
+                    .await
+                    .expect("prints");
+                print_ids.push(prints[0].id);
+            }
+
+            for (card_id, print_id, quantity) in [
+                (1, Some(print_ids[0]), 2),
+                (2, Some(print_ids[1]), 1),
+                (1, None, 1),
+            ] {
+                let new = ygo::NewCollectionItem {
+                    data: ygo::CollectionItemData {
+                        card_id,
+                        print_id,
+                        quantity,
+                        ..Default::default()
+                    },
+                };
+                save_new(&client, &new).await.expect("insert");
+            }
+
+            let snapshots = [
+                (1, date(1), ygo::PriceSource::Tcgplayer, "1.00"),
+                (1, date(1), ygo::PriceSource::Cardmarket, "0.50"),
+                (2, date(1), ygo::PriceSource::Tcgplayer, "5.00"),
+                (1, date(3), ygo::PriceSource::Tcgplayer, "2.00"),
+                // After the valuation date
+                (2, date(9), ygo::PriceSource::Tcgplayer, "50.00"),
+            ];
+            for (card_id, date, source, value) in snapshots {
+                price::save_snapshots(&client, date, &[(card_id, source, decimal(value))])
+                    .await
+                    .expect("snapshot");
+            }
+
+            let filter = ValueFilter {
+                date: Some(date(4)),
+                compare_to: Some(date(2)),
+                source: None,
+            };
+            let value = get_value(&client, filter).await.expect("value");
+            assert_eq!(value.date, date(4));
+            assert_eq!(value.previous_date, Some(date(2)));
+            assert_eq!(value.sources.len(), ygo::PriceSource::ALL.len());
+
+            let tcgplayer = &value.sources[0];
+            assert_eq!(tcgplayer.source, ygo::PriceSource::Tcgplayer);
+            assert_eq!(tcgplayer.value.total, decimal("11"));
+            assert_eq!(tcgplayer.value.previous_total, Some(decimal("8")));
+            assert_eq!(tcgplayer.value.change, Some(decimal("3")));
+
+            let totals = |groups: &[GroupValue]| -> Vec<(Option<String>, Decimal)> {
+                groups
+                    .iter()
+                    .map(|g| (g.name.clone(), g.value.total))
+                    .collect()
+            };
+            assert_eq!(
+                totals(&tcgplayer.by_set),
+                vec![
+                    (Some("Legend of Blue Eyes".to_string()), decimal("9")),
+                    (None, decimal("2")),
+                ]
+            );
+            assert_eq!(
+                totals(&tcgplayer.by_rarity),
+                vec![
+                    (Some("Secret Rare".to_string()), decimal("5")),
+                    (Some("Ultra Rare".to_string()), decimal("4")),
+                    (None, decimal("2")),
+                ]
+            );
+            assert_eq!(
+                totals(&tcgplayer.by_kind),
+                vec![(Some("monster".to_string()), decimal("11"))]
+            );
+
+            // Cards without a price from the source are worth nothing
+            let cardmarket = &value.sources[1];
+            assert_eq!(cardmarket.source, ygo::PriceSource::Cardmarket);
+            assert_eq!(cardmarket.value.total, decimal("1.5"));
+            assert_eq!(cardmarket.value.change, Some(decimal("0")));
+
+            // Without a date to compare to, there is no change
+            let filter = ValueFilter {
+                date: Some(date(1)),
+                source: Some(ygo::PriceSource::Tcgplayer),
+                ..Default::default()
+            };
+            let value = get_value(&client, filter).await.expect("value");
+            assert_eq!(value.sources.len(), 1);
+            assert_eq!(value.sources[0].value.total, decimal("8"));
+            assert_eq!(value.sources[0].value.previous_total, None);
+            assert_eq!(value.sources[0].value.change, None);
+        })
+        .await;
+    }
+}
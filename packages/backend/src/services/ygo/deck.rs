@@ -0,0 +1,705 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+use std::result::Result;
+use tokio_postgres::{Client, Error, Row};
+
+use crate::database::{TzTimestamp, with_transaction};
+use crate::models::ygo;
+use crate::services::ygo as service;
+
+/// Maximum number of copies of a card across all zones of a deck
+pub const MAX_COPIES: usize = 3;
+
+/// A deck construction rule that the deck breaks
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(
+    tag = "rule",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum DeckViolation {
+    /// The card does not exist
+    UnknownCard { card_id: i32 },
+    /// The card cannot be placed in this zone
+    WrongZone { card_id: i32, zone: ygo::DeckZone },
+    /// The zone holds too few or too many cards
+    ZoneSize {
+        zone: ygo::DeckZone,
+        count: usize,
+        min: usize,
+        max: usize,
+    },
+    /// The deck holds more copies of the card than allowed
+    TooManyCopies {
+        card_id: i32,
+        count: usize,
+        max: usize,
+    },
+}
+
+/// Returns the minimum and maximum number of cards of a zone
+fn zone_size(zone: &ygo::DeckZone) -> (usize, usize) {
+    match zone {
+        ygo::DeckZone::Main => (40, 60),
+        ygo::DeckZone::Extra => (0, 15),
+        ygo::DeckZone::Side => (0, 15),
+    }
+}
+
+/// Whether a card can be placed in the given zone
+fn is_allowed_in_zone(card: &ygo::Card, zone: &ygo::DeckZone) -> bool {
+    let is_extra_deck = card
+        .data
+        .monster_kind
+        .as_ref()
+        .is_some_and(|kind| kind.is_extra_deck());
+
+    match zone {
+        ygo::DeckZone::Main => !is_extra_deck,
+        ygo::DeckZone::Extra => is_extra_deck,
+        ygo::DeckZone::Side => true,
+    }
+}
+
+/// Returns the distinct IDs in order of first appearance
+fn distinct(card_ids: impl Iterator<Item = i32>) -> Vec<i32> {
+    let mut seen = HashSet::new();
+    card_ids.filter(|id| seen.insert(*id)).collect()
+}
+
+/// Checks a deck against the construction rules, using the given cards (indexed by ID),
+/// and the number of copies allowed by the banlist for limited cards (indexed by card ID).
+/// Returns every violation found, or an empty list if the deck is legal.
+pub fn validate(
+    deck: &ygo::DeckData,
+    cards: &HashMap<i32, ygo::Card>,
+    limits: &HashMap<i32, i16>,
+) -> Vec<DeckViolation> {
+    let mut violations = Vec::new();
+
+    // Every card must exist
+    for card_id in distinct(deck.card_ids()) {
+        if !cards.contains_key(&card_id) {
+            violations.push(DeckViolation::UnknownCard { card_id });
+        }
+    }
+
+    for zone in ygo::DeckZone::ALL {
+        let card_ids = deck.zone(&zone);
+
+        // Zones have size limits
+        let (min, max) = zone_size(&zone);
+        if !(min..=max).contains(&card_ids.len()) {
+            violations.push(DeckViolation::ZoneSize {
+                zone,
+                count: card_ids.len(),
+                min,
+                max,
+            });
+        }
+
+        // Extra Deck monsters only go in the Extra Deck, and only them
+        for card_id in distinct(card_ids.iter().copied()) {
+            if let Some(card) = cards.get(&card_id)
+                && !is_allowed_in_zone(card, &zone)
+            {
+                violations.push(DeckViolation::WrongZone { card_id, zone });
+            }
+        }
+    }
+
+    // Cards are limited in copies across all zones, and further by the banlist
+    let mut counts: HashMap<i32, usize> = HashMap::new();
+    for card_id in deck.card_ids() {
+        *counts.entry(card_id).or_default() += 1;
+    }
+    for card_id in distinct(deck.card_ids()) {
+        let count = counts[&card_id];
+        let max = limits
+            .get(&card_id)
+            .map_or(MAX_COPIES, |&limit| limit.max(0) as usize);
+        if count > max {
+            violations.push(DeckViolation::TooManyCopies {
+                card_id,
+                count,
+                max,
+            });
+        }
+    }
+
+    violations
+}
+
+/// Loads a deck's cards from the database, and checks it against the construction rules
+/// and the given banlist, if any
+pub async fn check(
+    client: &Client,
+    deck: &ygo::DeckData,
+    banlist: Option<&ygo::Banlist>,
+) -> Result<Vec<DeckViolation>, Error> {
+    let card_ids = distinct(deck.card_ids());
+    let cards = service::card::get_by_ids(client, &card_ids)
+        .await?
+        .into_iter()
+        .map(|card| (card.id, card))
+        .collect();
+    let limits = match banlist {
+        Some(banlist) => service::banlist::get_limits(client, banlist.id).await?,
+        None => HashMap::new(),
+    };
+
+    Ok(validate(deck, &cards, &limits))
+}
+
+/// Retrieves all decks
+pub async fn get_all(client: &Client) -> Result<Vec<ygo::Deck>, Error> {
+    let query = "SELECT * FROM ygo_decks ORDER BY id ASC";
+    let rows = client.query(query, &[]).await?;
+
+    let mut decks: Vec<ygo::Deck> = rows
+        .iter()
+        .map(|row| row.try_into())
+        .collect::<Result<_, _>>()?;
+    load_cards(client, &mut decks).await?;
+
+    Ok(decks)
+}
+
+/// Retrieves a deck by ID
+pub async fn get_by_id(client: &Client, id: i32) -> Result<Option<ygo::Deck>, Error> {
+    let query = "SELECT * FROM ygo_decks WHERE id = $1";
+    let Some(row) = client.query_opt(query, &[&id]).await? else {
+        return Ok(None);
+    };
+
+    let mut decks = [(&row).try_into()?];
+    load_cards(client, &mut decks).await?;
+
+    let [deck] = decks;
+    Ok(Some(deck))
+}
+
+/// Deletes a deck by ID. Returns true if a row was deleted, false otherwise.
+pub async fn delete_by_id(client: &Client, id: i32) -> Result<bool, Error> {
+    let affected = client
+        .execute("DELETE FROM ygo_decks WHERE id = $1", &[&id])
+        .await?;
+    Ok(affected == 1)
+}
+
+/// Insert a new deck and return the created record.
+pub async fn save_new(client: &Client, new_deck: &ygo::NewDeck) -> Result<ygo::Deck, Error> {
+    let data = &new_deck.data;
+
+    with_transaction(client, None, async |client| -> Result<ygo::Deck, Error> {
+        let row = client
+            .query_one(
+                "INSERT INTO ygo_decks (name, description) VALUES ($1, $2) RETURNING *",
+                &[&data.name, &data.description],
+            )
+            .await?;
+
+        let mut deck: ygo::Deck = (&row).try_into()?;
+        save_cards(client, deck.id, data).await?;
+        deck.data = data.clone();
+
+        Ok(deck)
+    })
+    .await
+}
+
+/// Update an existing deck by id and return the updated record.
+pub async fn save(client: &Client, deck: &ygo::Deck) -> Result<Option<ygo::Deck>, Error> {
+    let data = &deck.data;
+
+    with_transaction(
+        client,
+        None,
+        async |client| -> Result<Option<ygo::Deck>, Error> {
+            let row = client
+                .query_opt(
+                    r#"
+                UPDATE ygo_decks SET
+                    name = $1,
+                    description = $2,
+                    updated_at = CURRENT_TIMESTAMP
+                WHERE id = $3
+                RETURNING *
+                "#,
+                    &[&data.name, &data.description, &deck.id],
+                )
+                .await?;
+
+            let Some(row) = row else {
+                return Ok(None);
+            };
+
+            let mut updated: ygo::Deck = (&row).try_into()?;
+            save_cards(client, updated.id, data).await?;
+            updated.data = data.clone();
+
+            Ok(Some(updated))
+        },
+    )
+    .await
+}
+
+/// Replaces the cards of a deck
+async fn save_cards(client: &Client, deck_id: i32, data: &ygo::DeckData) -> Result<(), Error> {
+    client
+        .execute("DELETE FROM ygo_deck_cards WHERE deck_id = $1", &[&deck_id])
+        .await?;
+
+    for zone in ygo::DeckZone::ALL {
+        client
+            .execute(
+                r#"
+                INSERT INTO ygo_deck_cards (deck_id, zone, position, card_id)
+                SELECT $1, $2, position::INTEGER, card_id
+                FROM UNNEST($3::INTEGER[]) WITH ORDINALITY AS cards (card_id, position)
+                "#,
+                &[&deck_id, &zone, &data.zone(&zone)],
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Fills in the zones of the given decks
+async fn load_cards(client: &Client, decks: &mut [ygo::Deck]) -> Result<(), Error> {
+    let deck_ids: Vec<i32> = decks.iter().map(|deck| deck.id).collect();
+    let rows = client
+        .query(
+            r#"
+            SELECT deck_id, zone, card_id FROM ygo_deck_cards
+            WHERE deck_id = ANY($1)
+            ORDER BY deck_id, zone, position
+            "#,
+            &[&deck_ids],
+        )
+        .await?;
+
+    let mut decks_by_id: HashMap<i32, &mut ygo::Deck> =
+        decks.iter_mut().map(|deck| (deck.id, deck)).collect();
+
+    for row in rows {
+        let deck_id: i32 = row.try_get("deck_id")?;
+        let zone: ygo::DeckZone = row.try_get("zone")?;
+        let card_id: i32 = row.try_get("card_id")?;
+
+        if let Some(deck) = decks_by_id.get_mut(&deck_id) {
+            deck.data.zone_mut(&zone).push(card_id);
+        }
+    }
+
+    Ok(())
+}
+
+impl TryFrom<&Row> for ygo::Deck {
+    type Error = Error;
+
+    /// Converts a database row into a Deck struct, without its cards
+    fn try_from(value: &Row) -> Result<Self, Self::Error> {
+        let updated_at: TzTimestamp = value.try_get("updated_at")?;
+
+        Ok(Self {
+            id: value.try_get("id")?,
+            updated_at: updated_at.0,
+            data: ygo::DeckData {
+                name: value.try_get("name")?,
+                description: value.try_get("description")?,
+                ..Default::default()
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::with_db_pool;
+
+    fn make_card(id: i32, monster_kind: Option<ygo::MonsterKind>) -> ygo::Card {
+        ygo::Card {
+            id,
+            updated_at: chrono::Utc::now(),
+            data: ygo::CardData {
+                name: format!("Card {id}"),
+                kind: match monster_kind {
+                    Some(_) => ygo::CardKind::Monster,
+                    None => ygo::CardKind::Spell,
+                },
+                monster_kind,
+                ..Default::default()
+            },
+            banlist: Default::default(),
+        }
+    }
+
+    /// Cards 1 to 20 are main deck cards, 21 to 30 are extra deck monsters
+    fn make_cards() -> HashMap<i32, ygo::Card> {
+        let mut cards = HashMap::new();
+        for id in 1..=20 {
+            let kind = (id % 2 == 0).then_some(ygo::MonsterKind::Effect);
+            cards.insert(id, make_card(id, kind));
+        }
+        let extra_kinds = [
+            ygo::MonsterKind::Fusion,
+            ygo::MonsterKind::Synchro,
+            ygo::MonsterKind::Xyz,
+            ygo::MonsterKind::Link,
+        ];
+        for id in 21..=30 {
+            let kind = extra_kinds[id as usize % extra_kinds.len()].clone();
+            cards.insert(id, make_card(id, Some(kind)));
+        }
+        cards
+    }
+
+    /// Builds a legal deck with 40 main deck cards, 10 extra deck cards and 5 side deck cards
+    fn make_legal_deck() -> ygo::DeckData {
+        ygo::DeckData {
+            name: "Legal".to_string(),
+            main: (1..=20).flat_map(|id| [id, id]).collect(),
+            extra: (21..=30).collect(),
+            side: (11..=15).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_legal_deck() {
+        let violations = validate(&make_legal_deck(), &make_cards(), &HashMap::new());
+        assert_eq!(violations, vec![]);
+    }
+
+    #[test]
+    fn test_validate_zone_sizes() {
+        let mut deck = make_legal_deck();
+        deck.main.truncate(39);
+        deck.side.extend(16..=20);
+        deck.side.extend(1..=6);
+
+        let violations = validate(&deck, &make_cards(), &HashMap::new());
+        assert_eq!(
+            violations,
+            vec![
+                DeckViolation::ZoneSize {
+                    zone: ygo::DeckZone::Main,
+                    count: 39,
+                    min: 40,
+                    max: 60,
+                },
+                DeckViolation::ZoneSize {
+                    zone: ygo::DeckZone::Side,
+                    count: 16,
+                    min: 0,
+                    max: 15,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_extra_deck_monsters_placement() {
+        let mut deck = make_legal_deck();
+        deck.main[0] = 21; // Synchro in main deck
+        deck.main[1] = 24; // Fusion in main deck
+        deck.extra[0] = 2; // Effect monster in extra deck
+        deck.extra[1] = 3; // Spell in extra deck
+        deck.side[0] = 25; // Anything goes in side deck
+
+        let violations = validate(&deck, &make_cards(), &HashMap::new());
+        assert_eq!(
+            violations,
+            vec![
+                DeckViolation::WrongZone {
+                    card_id: 21,
+                    zone: ygo::DeckZone::Main,
+                },
+                DeckViolation::WrongZone {
+                    card_id: 24,
+                    zone: ygo::DeckZone::Main,
+                },
+                DeckViolation::WrongZone {
+                    card_id: 2,
+                    zone: ygo::DeckZone::Extra,
+                },
+                DeckViolation::WrongZone {
+                    card_id: 3,
+                    zone: ygo::DeckZone::Extra,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_copies_across_zones() {
+        let mut deck = make_legal_deck();
+        deck.main.extend([1, 1]); // 4 copies in main deck
+        deck.side.extend([2, 2]); // 2 copies in main deck, 2 in side deck
+
+        let violations = validate(&deck, &make_cards(), &HashMap::new());
+        assert_eq!(
+            violations,
+            vec![
+                DeckViolation::TooManyCopies {
+                    card_id: 1,
+                    count: 4,
+                    max: 3,
+                },
+                DeckViolation::TooManyCopies {
+                    card_id: 2,
+                    count: 4,
+                    max: 3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_banlist_limits() {
+        let deck = make_legal_deck();
+        let limits = HashMap::from([(1, 1), (3, 2), (11, 0)]);
+
+        let violations = validate(&deck, &make_cards(), &limits);
+        assert_eq!(
+            violations,
+            vec![
+                DeckViolation::TooManyCopies {
+                    card_id: 1,
+                    count: 2,
+                    max: 1,
+                },
+                // Forbidden cards cannot be in the side deck either
+                DeckViolation::TooManyCopies {
+                    card_id: 11,
+                    count: 3,
+                    max: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_unknown_cards() {
+        let mut deck = make_legal_deck();
+        deck.main[0] = 144;
+        deck.side[0] = 144;
+
+        let violations = validate(&deck, &make_cards(), &HashMap::new());
+        assert_eq!(
+            violations,
+            vec![DeckViolation::UnknownCard { card_id: 144 }]
+        );
+    }
+
+    #[test]
+    fn test_violations_serialize_with_rule_tag() {
+        let violation = DeckViolation::TooManyCopies {
+            card_id: 1,
+            count: 4,
+            max: 3,
+        };
+        let json = serde_json::to_string(&violation).unwrap();
+        assert_eq!(
+            json,
+            r#"{"rule":"too_many_copies","cardId":1,"count":4,"max":3}"#
+        );
+    }
+
+    async fn create_cards(client: &Client, kinds: &[ygo::MonsterKind]) -> Vec<i32> {
+        let mut ids = Vec::new();
+        for kind in kinds {
+            let new = ygo::NewCard {
+                data: ygo::CardData {
+                    name: format!("{kind:?} Monster"),
+                    kind: ygo::CardKind::Monster,
+                    monster_kind: Some(kind.clone()),
+                    ..Default::default()
+                },
+            };
+            let card = service::card::save_new(client, &new).await.expect("card");
+            ids.push(card.id);
+        }
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_save_new_and_get_by_id() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            let ids = create_cards(
+                &client,
+                &[ygo::MonsterKind::Normal, ygo::MonsterKind::Fusion],
+            )
+            .await;
+
+            let new = ygo::NewDeck {
+                data: ygo::DeckData {
+                    name: "Dragons".to_string(),
+                    description: Some("Roar".to_string()),
+                    main: vec![ids[0], ids[0]],
+                    extra: vec![ids[1]],
+                    side: vec![ids[0]],
+                },
+            };
+
+            let created = save_new(&client, &new).await.expect("insert");
+            assert!(created.id > 0);
+            assert_eq!(created.data, new.data);
+
+            let fetched = get_by_id(&client, created.id).await.expect("fetch");
+            assert_eq!(fetched, Some(created));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_save_replaces_cards_and_keeps_order() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            let ids = create_cards(
+                &client,
+                &[
+                    ygo::MonsterKind::Normal,
+                    ygo::MonsterKind::Effect,
+                    ygo::MonsterKind::Ritual,
+                ],
+            )
+            .await;
+
+            let new = ygo::NewDeck {
+                data: ygo::DeckData {
+                    name: "Before".to_string(),
+                    main: vec![ids[0], ids[1]],
+                    ..Default::default()
+                },
+            };
+            let created = save_new(&client, &new).await.expect("insert");
+
+            let mut to_update = created.clone();
+            to_update.data.name = "After".to_string();
+            to_update.data.main = vec![ids[2], ids[0], ids[2]];
+            to_update.data.side = vec![ids[1]];
+
+            let updated = save(&client, &to_update)
+                .await
+                .expect("save")
+                .expect("updated");
+            assert_eq!(updated.data, to_update.data);
+
+            let fetched = get_by_id(&client, created.id)
+                .await
+                .expect("fetch")
+                .expect("deck");
+            assert_eq!(fetched.data.name, "After");
+            assert_eq!(fetched.data.main, vec![ids[2], ids[0], ids[2]]);
+            assert_eq!(fetched.data.side, vec![ids[1]]);
+
+            // Missing decks are not updated
+            to_update.id = 9999;
+            assert!(save(&client, &to_update).await.expect("save").is_none());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_get_all_and_delete_by_id() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            let ids = create_cards(&client, &[ygo::MonsterKind::Normal]).await;
+
+            for name in ["First", "Second"] {
+                let new = ygo::NewDeck {
+                    data: ygo::DeckData {
+                        name: name.to_string(),
+                        main: vec![ids[0]],
+                        ..Default::default()
+                    },
+                };
+                save_new(&client, &new).await.expect("insert");
+            }
+
+            let decks = get_all(&client).await.expect("all");
+            let names: Vec<_> = decks.iter().map(|d| d.data.name.as_str()).collect();
+            assert_eq!(names, vec!["First", "Second"]);
+            assert!(decks.iter().all(|d| d.data.main == vec![ids[0]]));
+
+            assert!(delete_by_id(&client, decks[0].id).await.expect("delete"));
+            assert!(!delete_by_id(&client, decks[0].id).await.expect("delete"));
+            assert_eq!(get_all(&client).await.expect("all").len(), 1);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_check_loads_cards() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            let ids = create_cards(&client, &[ygo::MonsterKind::Xyz]).await;
+
+            let deck = ygo::DeckData {
+                name: "Misplaced".to_string(),
+                main: vec![ids[0]; 40],
+                ..Default::default()
+            };
+
+            let violations = check(&client, &deck, None).await.expect("check");
+            assert_eq!(
+                violations,
+                vec![
+                    DeckViolation::WrongZone {
+                        card_id: ids[0],
+                        zone: ygo::DeckZone::Main,
+                    },
+                    DeckViolation::TooManyCopies {
+                        card_id: ids[0],
+                        count: 40,
+                        max: 3,
+                    },
+                ]
+            );
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_check_with_banlist() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            let ids = create_cards(&client, &[ygo::MonsterKind::Normal]).await;
+            let banlist = service::banlist::save(
+                &client,
+                ygo::BanlistFormat::Goat,
+                chrono::NaiveDate::from_ymd_opt(2005, 4, 1).unwrap(),
+                &HashMap::from([(ids[0], 1)]),
+            )
+            .await
+            .expect("banlist");
+
+            let deck = ygo::DeckData {
+                name: "Goat".to_string(),
+                side: vec![ids[0]; 2],
+                ..Default::default()
+            };
+
+            let violations = check(&client, &deck, Some(&banlist)).await.expect("check");
+            assert!(violations.contains(&DeckViolation::TooManyCopies {
+                card_id: ids[0],
+                count: 2,
+                max: 1,
+            }));
+
+            let violations = check(&client, &deck, None).await.expect("check");
+            assert!(
+                !violations
+                    .iter()
+                    .any(|v| matches!(v, DeckViolation::TooManyCopies { .. }))
+            );
+        })
+        .await;
+    }
+}
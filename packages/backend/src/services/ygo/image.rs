@@ -0,0 +1,856 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::{StreamExt, stream};
+use serde::{Deserialize, Serialize};
+use tokio::time::MissedTickBehavior;
+use tokio_postgres::{Client, Error};
+
+use crate::database::Pool;
+use crate::importers::ygoprodeck::{self, CardImageSize};
+use crate::services::image::ImageFormat;
+use crate::services::image_cache::{self, CACHE_PREFIX, ImageCache};
+use crate::services::ygo::scan;
+
+/// YGOPRODeck bans clients sending more than 20 requests per second for an hour
+pub const YGOPRODECK_MAX_REQUESTS_PER_SECOND: u32 = 20;
+
+/// Maximum number of images downloaded at the same time by the prefetch job
+pub const MAX_PREFETCH_CONCURRENCY: usize = 16;
+
+/// Prefix of the keys of uploaded card images
+const UPLOAD_PREFIX: &str = "upload/card/";
+
+/// Key of the checkpoint of an interrupted prefetch job in the image storage
+const PREFETCH_CHECKPOINT_KEY: &str = "prefetch_checkpoint";
+
+/// Number of cards whose images are prefetched between two checkpoints
+const PREFETCH_PAGE_SIZE: i64 = 100;
+
+/// Utility to get the key of a card's artwork image in the image storage, for a given width and
+/// format. The original image of the default artwork has no suffix.
+pub fn get_card_image_key(
+    card_id: i32,
+    art: i16,
+    size: &CardImageSize,
+    width: Option<u32>,
+    format: ImageFormat,
+) -> String {
+    let art_suffix = match art {
+        0 => String::new(),
+        art => format!("_art{art}"),
+    };
+    let size_suffix = match size {
+        CardImageSize::Small => "",
+        CardImageSize::Full => "_full",
+        CardImageSize::ArtOnly => "_cropped",
+    };
+    let width_suffix = width.map(|width| format!("_w{width}")).unwrap_or_default();
+    let extension = format.extension();
+
+    format!("{CACHE_PREFIX}{card_id}{art_suffix}{size_suffix}{width_suffix}.{extension}")
+}
+
+/// Utility to get the key of the image uploaded for a card. Uploaded images are kept out of the
+/// cache, so that they are never evicted.
+pub fn get_card_upload_key(card_id: i32) -> String {
+    format!("{UPLOAD_PREFIX}{card_id}")
+}
+
+/// Utility to get the ID of the card an image key belongs to, sidecar files included
+pub fn get_card_id_from_key(key: &str) -> Option<i32> {
+    let name = key
+        .strip_prefix(CACHE_PREFIX)
+        .or_else(|| key.strip_prefix(UPLOAD_PREFIX))?;
+    let end = name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(name.len());
+    name[..end].parse().ok()
+}
+
+/// Outcome of sweeping the images of cards that no longer exist
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SweepResult {
+    /// Deleted cards that had cached images
+    pub cards: u64,
+    /// Images deleted, in every size, width and format
+    pub images: u64,
+    /// Scans of deleted collection items, see `scan::sweep_orphans`
+    pub scans: u64,
+    /// Bytes freed, sidecar files included
+    pub freed: u64,
+}
+
+/// Deletes the images of a card, uploaded ones included
+pub async fn delete_card_images(cache: &ImageCache, card_id: i32) -> std::io::Result<()> {
+    cache.delete(&get_card_upload_key(card_id)).await?;
+    delete_cached_card_images(cache, card_id).await
+}
+
+/// Deletes the cached images of a card, in every size, width and format
+pub async fn delete_cached_card_images(cache: &ImageCache, card_id: i32) -> std::io::Result<()> {
+    // The prefix also matches the images of cards whose IDs start with this one
+    let objects = cache
+        .storage()
+        .list(&format!("{CACHE_PREFIX}{card_id}"))
+        .await?;
+    let keys: HashSet<_> = objects
+        .iter()
+        .filter(|object| get_card_id_from_key(&object.key) == Some(card_id))
+        .map(|object| image_cache::get_image_key(&object.key))
+        .collect();
+
+    for key in keys {
+        cache.delete(key).await?;
+    }
+
+    Ok(())
+}
+
+/// Deletes the images of cards that no longer exist, uploaded ones included, and the scans of
+/// deleted collection items
+pub async fn sweep_orphans(client: &Client, cache: &ImageCache) -> anyhow::Result<SweepResult> {
+    let mut objects = cache.storage().list(CACHE_PREFIX).await?;
+    objects.extend(cache.storage().list(UPLOAD_PREFIX).await?);
+
+    let card_ids: Vec<i32> = objects
+        .iter()
+        .filter_map(|object| get_card_id_from_key(&object.key))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let existing: HashSet<i32> = client
+        .query("SELECT id FROM ygo_cards WHERE id = ANY($1)", &[&card_ids])
+        .await?
+        .iter()
+        .map(|row| row.try_get(0))
+        .collect::<Result<_, _>>()?;
+
+    let mut orphans = HashSet::new();
+    let mut keys = HashSet::new();
+    let mut result = SweepResult::default();
+    for object in &objects {
+        let Some(card_id) = get_card_id_from_key(&object.key) else {
+            continue;
+        };
+        if existing.contains(&card_id) {
+            continue;
+        }
+
+        orphans.insert(card_id);
+        keys.insert(image_cache::get_image_key(&object.key));
+        result.freed += object.size;
+    }
+
+    for key in &keys {
+        cache.delete(key).await?;
+    }
+
+    result.cards = orphans.len() as u64;
+    result.images = keys.len() as u64;
+
+    let (scans, freed) = scan::sweep_orphans(client, cache).await?;
+    result.scans = scans;
+    result.freed += freed;
+
+    tracing::info!(
+        "Swept {} images of {} deleted cards and {} scans, freeing {} bytes",
+        result.images,
+        result.cards,
+        result.scans,
+        result.freed
+    );
+
+    Ok(result)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefetchOptions {
+    /// Sizes to download, all of them by default
+    #[serde(default = "PrefetchOptions::all_sizes")]
+    pub sizes: Vec<CardImageSize>,
+    #[serde(default = "PrefetchOptions::default_concurrency")]
+    pub concurrency: usize,
+    #[serde(default = "PrefetchOptions::default_requests_per_second")]
+    pub requests_per_second: u32,
+    /// Whether to start over, rather than resume from the last checkpoint
+    #[serde(default)]
+    pub restart: bool,
+}
+
+impl PrefetchOptions {
+    fn all_sizes() -> Vec<CardImageSize> {
+        vec![
+            CardImageSize::Small,
+            CardImageSize::Full,
+            CardImageSize::ArtOnly,
+        ]
+    }
+
+    fn default_concurrency() -> usize {
+        4
+    }
+
+    /// Half of YGOPRODeck's limit, leaving room for the images requested by users meanwhile
+    fn default_requests_per_second() -> u32 {
+        YGOPRODECK_MAX_REQUESTS_PER_SECOND / 2
+    }
+}
+
+impl Default for PrefetchOptions {
+    fn default() -> Self {
+        Self {
+            sizes: Self::all_sizes(),
+            concurrency: Self::default_concurrency(),
+            requests_per_second: Self::default_requests_per_second(),
+            restart: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrefetchStatus {
+    #[default]
+    Idle,
+    Running,
+    Finished,
+    Failed,
+}
+
+/// Progress of the image prefetch job. Counts are in images, one per artwork and size.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefetchProgress {
+    pub status: PrefetchStatus,
+    /// Images to check, after the checkpoint the job resumed from
+    pub total: u64,
+    /// Images that were already cached
+    pub cached: u64,
+    pub downloaded: u64,
+    /// Images YGOPRODeck has none of
+    pub missing: u64,
+    pub failed: u64,
+    /// ID up to which the images of every card were checked, without failing
+    pub checkpoint: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Outcome of prefetching an image
+enum Prefetched {
+    Cached,
+    Downloaded,
+    Missing,
+    Failed,
+}
+
+/// Background job downloading the images of every card from YGOPRODeck into the local cache,
+/// so that they can be served offline. One job runs at a time.
+///
+/// The job saves a checkpoint every few cards, and resumes from it when restarted after being
+/// interrupted.
+#[derive(Debug, Clone, Default)]
+pub struct ImagePrefetch {
+    progress: Arc<Mutex<PrefetchProgress>>,
+}
+
+impl ImagePrefetch {
+    /// Returns the progress of the current or last job
+    pub fn progress(&self) -> PrefetchProgress {
+        self.progress
+            .lock()
+            .expect("prefetch progress lock poisoned")
+            .clone()
+    }
+
+    /// Starts prefetching images in the background. Returns false if a job is already running.
+    pub fn start(&self, db: Pool, cache: ImageCache, options: PrefetchOptions) -> bool {
+        self.start_with(db, cache, options, |ygoprodeck_id, size| async move {
+            ygoprodeck::get_card_image(ygoprodeck_id, &size).await
+        })
+    }
+
+    /// Starts prefetching images in the background, retrieving them with `fetch`
+    fn start_with<F, Fut>(
+        &self,
+        db: Pool,
+        cache: ImageCache,
+        options: PrefetchOptions,
+        fetch: F,
+    ) -> bool
+    where
+        F: Fn(i32, CardImageSize) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<Option<Vec<u8>>>> + Send,
+    {
+        {
+            let mut progress = self
+                .progress
+                .lock()
+                .expect("prefetch progress lock poisoned");
+            if progress.status == PrefetchStatus::Running {
+                return false;
+            }
+
+            *progress = PrefetchProgress {
+                status: PrefetchStatus::Running,
+                started_at: Some(Utc::now()),
+                ..Default::default()
+            };
+        }
+
+        let job = self.clone();
+        tokio::spawn(async move {
+            let result = job.run(&db, &cache, &options, &fetch).await;
+
+            job.update(|progress| {
+                progress.finished_at = Some(Utc::now());
+                match result {
+                    Ok(()) => progress.status = PrefetchStatus::Finished,
+                    Err(err) => {
+                        tracing::error!("Image prefetch failed: {err}");
+                        progress.status = PrefetchStatus::Failed;
+                        progress.error = Some(err.to_string());
+                    }
+                }
+            });
+        });
+
+        true
+    }
+
+    /// Utility to update the progress of the job
+    fn update(&self, f: impl FnOnce(&mut PrefetchProgress)) {
+        f(&mut self
+            .progress
+            .lock()
+            .expect("prefetch progress lock poisoned"));
+    }
+
+    /// Downloads the images missing from the cache, a page of cards at a time
+    async fn run<F, Fut>(
+        &self,
+        db: &Pool,
+        cache: &ImageCache,
+        options: &PrefetchOptions,
+        fetch: &F,
+    ) -> anyhow::Result<()>
+    where
+        F: Fn(i32, CardImageSize) -> Fut,
+        Fut: Future<Output = anyhow::Result<Option<Vec<u8>>>>,
+    {
+        let storage = cache.storage();
+        let mut checkpoint = match options.restart {
+            true => None,
+            false => read_checkpoint(cache).await,
+        };
+
+        let artworks = count_artworks(&*db.get().await?, checkpoint).await?;
+        self.update(|progress| {
+            progress.total = artworks as u64 * options.sizes.len() as u64;
+            progress.checkpoint = checkpoint;
+        });
+
+        // Space out requests to stay under the rate limit
+        let requests_per_second = options
+            .requests_per_second
+            .clamp(1, YGOPRODECK_MAX_REQUESTS_PER_SECOND);
+        let mut interval = tokio::time::interval(Duration::from_secs(1) / requests_per_second);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let rate_limit = tokio::sync::Mutex::new(interval);
+
+        // Cards whose images could not be downloaded are retried by the next job, so the
+        // checkpoint never goes past the first of them
+        let mut cursor = checkpoint;
+        let mut first_failed = None;
+
+        loop {
+            let artworks = get_artworks_page(&*db.get().await?, cursor).await?;
+            let Some(&(last_card_id, _, _)) = artworks.last() else {
+                break;
+            };
+
+            let images: Vec<_> = artworks
+                .iter()
+                .flat_map(|&(card_id, art, ygoprodeck_id)| {
+                    options
+                        .sizes
+                        .iter()
+                        .map(move |&size| (card_id, art, ygoprodeck_id, size))
+                })
+                .collect();
+
+            let concurrency = options.concurrency.clamp(1, MAX_PREFETCH_CONCURRENCY);
+            let page_failed = stream::iter(images)
+                .map(|(card_id, art, ygoprodeck_id, size)| {
+                    let rate_limit = &rate_limit;
+                    async move {
+                        let key = get_card_image_key(
+                            card_id,
+                            art,
+                            &size,
+                            None,
+                            ImageFormat::Jpeg,
+                        );
+
+                        let prefetched = if storage.exists(&key).await.unwrap_or(false) {
+                            Prefetched::Cached
+                        } else {
+                            let image = cache
+                                .get_or_create(&key, async || {
+                                    rate_limit.lock().await.tick().await;
+                                    fetch(ygoprodeck_id, size).await
+                                })
+                                .await;
+
+                            match image {
+                                Ok(Some(_)) => Prefetched::Downloaded,
+                                Ok(None) => Prefetched::Missing,
+                                Err(err) => {
+                                    tracing::warn!(
+                                        "Could not prefetch image of card ID {card_id} (artwork {art}): {err}"
+                                    );
+                                    Prefetched::Failed
+                                }
+                            }
+                        };
+
+                        self.update(|progress| match prefetched {
+                            Prefetched::Cached => progress.cached += 1,
+                            Prefetched::Downloaded => progress.downloaded += 1,
+                            Prefetched::Missing => progress.missing += 1,
+                            Prefetched::Failed => progress.failed += 1,
+                        });
+
+                        matches!(prefetched, Prefetched::Failed).then_some(card_id)
+                    }
+                })
+                .buffer_unordered(concurrency)
+                .fold(None, async |first, failed| first.into_iter().chain(failed).min())
+                .await;
+
+            cursor = Some(last_card_id);
+            first_failed = first_failed.or(page_failed);
+            let page_checkpoint = first_failed.map_or(last_card_id, |card_id| card_id - 1);
+            if checkpoint < Some(page_checkpoint) {
+                checkpoint = Some(page_checkpoint);
+                storage
+                    .write(
+                        PREFETCH_CHECKPOINT_KEY,
+                        page_checkpoint.to_string().as_bytes(),
+                    )
+                    .await?;
+                self.update(|progress| progress.checkpoint = checkpoint);
+            }
+        }
+
+        // The next job starts over, unless it has images to retry
+        if first_failed.is_none() {
+            storage.delete(PREFETCH_CHECKPOINT_KEY).await.ok();
+        }
+
+        Ok(())
+    }
+}
+
+/// Utility to read the ID of the last card prefetched by an interrupted job
+async fn read_checkpoint(cache: &ImageCache) -> Option<i32> {
+    let checkpoint = cache.storage().read(PREFETCH_CHECKPOINT_KEY).await.ok()??;
+    String::from_utf8_lossy(&checkpoint).trim().parse().ok()
+}
+
+/// Counts the artworks of the cards after the checkpoint, including the default artwork of
+/// cards without known artworks
+async fn count_artworks(client: &Client, checkpoint: Option<i32>) -> Result<i64, Error> {
+    let row = client
+        .query_one(
+            r#"
+            SELECT COUNT(*)
+            FROM ygo_cards c
+            LEFT JOIN ygo_card_artworks a ON a.card_id = c.id
+            WHERE c.ygoprodeck_id IS NOT NULL AND c.id > $1
+            "#,
+            &[&checkpoint.unwrap_or(i32::MIN)],
+        )
+        .await?;
+
+    row.try_get(0)
+}
+
+/// Lists the artworks of the next page of cards after the checkpoint, as (card ID, artwork
+/// index, YGOPRODeck image ID), ordered by card
+async fn get_artworks_page(
+    client: &Client,
+    checkpoint: Option<i32>,
+) -> Result<Vec<(i32, i16, i32)>, Error> {
+    let rows = client
+        .query(
+            r#"
+            SELECT
+                c.id,
+                COALESCE(a.art_index, 0::SMALLINT) AS art_index,
+                COALESCE(a.ygoprodeck_id, c.ygoprodeck_id) AS ygoprodeck_id
+            FROM (
+                SELECT id, ygoprodeck_id
+                FROM ygo_cards
+                WHERE ygoprodeck_id IS NOT NULL AND id > $1
+                ORDER BY id
+                LIMIT $2
+            ) c
+            LEFT JOIN ygo_card_artworks a ON a.card_id = c.id
+            ORDER BY c.id, art_index
+            "#,
+            &[&checkpoint.unwrap_or(i32::MIN), &PREFETCH_PAGE_SIZE],
+        )
+        .await?;
+
+    rows.iter()
+        .map(|row| Ok((row.try_get(0)?, row.try_get(1)?, row.try_get(2)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    use super::*;
+    use crate::models::ygo;
+    use crate::services::image_storage::FilesystemStorage;
+    use crate::services::ygo::{artwork, card};
+    use crate::test_utils::{test_dir, with_db_pool};
+
+    /// Utility to get a cache of images stored in a directory
+    fn test_cache(dir: &Path) -> ImageCache {
+        ImageCache::new(Arc::new(FilesystemStorage::new(dir)), None)
+    }
+
+    /// Utility to save cards with the given YGOPRODeck IDs, returning their IDs
+    async fn save_cards(client: &Client, ygoprodeck_ids: &[i32]) -> Vec<i32> {
+        let mut ids = Vec::new();
+        for &ygoprodeck_id in ygoprodeck_ids {
+            let new = ygo::NewCard {
+                data: ygo::CardData {
+                    name: format!("Card {ygoprodeck_id}"),
+                    ygoprodeck_id: Some(ygoprodeck_id),
+                    ..Default::default()
+                },
+            };
+            ids.push(card::save_new(client, &new).await.expect("card").id);
+        }
+        ids
+    }
+
+    fn fake_image(ygoprodeck_id: i32, size: CardImageSize) -> Vec<u8> {
+        format!("{ygoprodeck_id} {size:?}").into_bytes()
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_downloads_missing_images() {
+        with_db_pool(async move |db| {
+            let dir = test_dir("prefetch");
+            let ids = {
+                let client = db.get().await.expect("db");
+                let ids = save_cards(&client, &[100, 200, 404]).await;
+                artwork::save_all_by_card(&client, &[(ids[1], vec![200, 201])])
+                    .await
+                    .expect("artworks");
+                ids
+            };
+
+            // The small image of the first card is already cached
+            let cache = test_cache(dir.path());
+            let cached_key =
+                get_card_image_key(ids[0], 0, &CardImageSize::Small, None, ImageFormat::Jpeg);
+            cache.storage().write(&cached_key, b"cached").await.unwrap();
+
+            let fetched = AtomicUsize::new(0);
+            let in_flight = AtomicUsize::new(0);
+            let max_in_flight = AtomicUsize::new(0);
+            let fetch = |ygoprodeck_id: i32, size: CardImageSize| {
+                let (fetched, in_flight, max_in_flight) = (&fetched, &in_flight, &max_in_flight);
+                async move {
+                    fetched.fetch_add(1, Ordering::SeqCst);
+                    let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(current, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                    Ok((ygoprodeck_id != 404).then(|| fake_image(ygoprodeck_id, size)))
+                }
+            };
+
+            let job = ImagePrefetch::default();
+            let options = PrefetchOptions {
+                concurrency: 2,
+                requests_per_second: YGOPRODECK_MAX_REQUESTS_PER_SECOND,
+                ..Default::default()
+            };
+            let started = Instant::now();
+            job.run(&db, &cache, &options, &fetch)
+                .await
+                .expect("prefetch");
+
+            // 4 artworks in 3 sizes, one of them already cached
+            let progress = job.progress();
+            assert_eq!(progress.total, 12);
+            assert_eq!(progress.cached, 1);
+            assert_eq!(progress.downloaded, 8);
+            assert_eq!(progress.missing, 3);
+            assert_eq!(progress.failed, 0);
+            assert_eq!(progress.checkpoint, Some(ids[2]));
+            assert_eq!(fetched.load(Ordering::SeqCst), 11);
+            assert!(max_in_flight.load(Ordering::SeqCst) <= 2);
+
+            // Requests are spaced out by the rate limit, the first one going out right away
+            assert!(started.elapsed() >= Duration::from_millis(10 * 50));
+
+            let key = get_card_image_key(ids[1], 1, &CardImageSize::Full, None, ImageFormat::Jpeg);
+            assert_eq!(
+                cache.storage().read(&key).await.unwrap(),
+                Some(fake_image(201, CardImageSize::Full))
+            );
+            assert_eq!(
+                cache.storage().read(&cached_key).await.unwrap(),
+                Some(b"cached".to_vec())
+            );
+
+            // Finished jobs leave no checkpoint behind
+            assert!(!dir.path().join("prefetch_checkpoint").exists());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_resumes_from_checkpoint() {
+        with_db_pool(async move |db| {
+            let dir = test_dir("prefetch-resume");
+            let ids = {
+                let client = db.get().await.expect("db");
+                save_cards(&client, &[100, 200]).await
+            };
+
+            // An interrupted job prefetched the first card
+            let cache = test_cache(dir.path());
+            cache
+                .storage()
+                .write(PREFETCH_CHECKPOINT_KEY, ids[0].to_string().as_bytes())
+                .await
+                .unwrap();
+
+            let fetched = Mutex::new(Vec::new());
+            let fetch = |ygoprodeck_id: i32, size: CardImageSize| {
+                fetched.lock().unwrap().push(ygoprodeck_id);
+                async move { Ok(Some(fake_image(ygoprodeck_id, size))) }
+            };
+
+            let job = ImagePrefetch::default();
+            let options = PrefetchOptions {
+                sizes: vec![CardImageSize::Small],
+                ..Default::default()
+            };
+            job.run(&db, &cache, &options, &fetch)
+                .await
+                .expect("prefetch");
+            assert_eq!(*fetched.lock().unwrap(), vec![200]);
+            assert_eq!(job.progress().total, 1);
+
+            // Unless asked to start over
+            let options = PrefetchOptions {
+                restart: true,
+                ..options
+            };
+            job.run(&db, &cache, &options, &fetch)
+                .await
+                .expect("prefetch");
+            assert_eq!(*fetched.lock().unwrap(), vec![200, 100]);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_retries_failed_images() {
+        with_db_pool(async move |db| {
+            let dir = test_dir("prefetch-retry");
+            let ids = {
+                let client = db.get().await.expect("db");
+                save_cards(&client, &[100, 500, 300]).await
+            };
+            let cache = test_cache(dir.path());
+
+            let fetched = Mutex::new(Vec::new());
+            let fetch = |ygoprodeck_id: i32, size: CardImageSize| {
+                fetched.lock().unwrap().push(ygoprodeck_id);
+                async move {
+                    match ygoprodeck_id {
+                        500 => Err(anyhow::anyhow!("connection reset")),
+                        _ => Ok(Some(fake_image(ygoprodeck_id, size))),
+                    }
+                }
+            };
+
+            let job = ImagePrefetch::default();
+            let options = PrefetchOptions {
+                sizes: vec![CardImageSize::Small],
+                ..Default::default()
+            };
+            job.run(&db, &cache, &options, &fetch)
+                .await
+                .expect("prefetch");
+
+            // The checkpoint stays before the card whose image failed, even if the next card's
+            // image was downloaded
+            let progress = job.progress();
+            assert_eq!((progress.downloaded, progress.failed), (2, 1));
+            assert_eq!(progress.checkpoint, Some(ids[1] - 1));
+            assert_eq!(read_checkpoint(&cache).await, Some(ids[1] - 1));
+
+            // The next job resumes from the failed image
+            let fetched = Mutex::new(Vec::new());
+            let fetch = |ygoprodeck_id: i32, size: CardImageSize| {
+                fetched.lock().unwrap().push(ygoprodeck_id);
+                async move { Ok(Some(fake_image(ygoprodeck_id, size))) }
+            };
+            job.run(&db, &cache, &options, &fetch)
+                .await
+                .expect("prefetch");
+            assert_eq!(*fetched.lock().unwrap(), vec![500]);
+            assert_eq!(job.progress().cached, 1);
+            assert_eq!(read_checkpoint(&cache).await, None);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_runs_one_job_at_a_time() {
+        with_db_pool(async move |db| {
+            let dir = test_dir("prefetch-single");
+            {
+                let client = db.get().await.expect("db");
+                save_cards(&client, &[100]).await;
+            }
+
+            let release = Arc::new(tokio::sync::Notify::new());
+            let fetch = {
+                let release = release.clone();
+                move |ygoprodeck_id: i32, size: CardImageSize| {
+                    let release = release.clone();
+                    async move {
+                        release.notified().await;
+                        Ok(Some(fake_image(ygoprodeck_id, size)))
+                    }
+                }
+            };
+
+            let job = ImagePrefetch::default();
+            let options = PrefetchOptions {
+                sizes: vec![CardImageSize::Small],
+                ..Default::default()
+            };
+            let pool = (*db).clone();
+            assert!(job.start_with(
+                pool.clone(),
+                test_cache(dir.path()),
+                options.clone(),
+                fetch.clone()
+            ));
+            assert_eq!(job.progress().status, PrefetchStatus::Running);
+            assert!(!job.start_with(pool, test_cache(dir.path()), options, fetch));
+
+            release.notify_one();
+            while job.progress().status == PrefetchStatus::Running {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+
+            let progress = job.progress();
+            assert_eq!(progress.status, PrefetchStatus::Finished);
+            assert_eq!(progress.downloaded, 1);
+            assert!(progress.finished_at.is_some());
+        })
+        .await;
+    }
+
+    #[test]
+    fn test_get_card_id_from_key() {
+        let key = get_card_image_key(42, 1, &CardImageSize::Full, Some(20), ImageFormat::Webp);
+        assert_eq!(get_card_id_from_key(&key), Some(42));
+        assert_eq!(get_card_id_from_key("card/42.jpg.sha256"), Some(42));
+        assert_eq!(get_card_id_from_key("card/420.jpg"), Some(420));
+        assert_eq!(get_card_id_from_key(&get_card_upload_key(42)), Some(42));
+        assert_eq!(get_card_id_from_key("card/.jpg"), None);
+        assert_eq!(get_card_id_from_key("prefetch_checkpoint"), None);
+    }
+
+    #[tokio::test]
+    async fn test_delete_card_images() {
+        let dir = test_dir("delete-images");
+        let cache = test_cache(dir.path());
+        for key in ["card/4.jpg", "card/4_full_w20.webp", "card/42.jpg"] {
+            cache.save(key, b"image".to_vec()).await.unwrap();
+        }
+        let upload = get_card_upload_key(4);
+        cache.save(&upload, b"upload".to_vec()).await.unwrap();
+
+        // Uploaded images are kept when clearing the cache
+        delete_cached_card_images(&cache, 4).await.unwrap();
+        assert!(cache.storage().exists(&upload).await.unwrap());
+        assert!(!cache.storage().exists("card/4.jpg").await.unwrap());
+
+        delete_card_images(&cache, 4).await.unwrap();
+        assert!(!cache.storage().exists(&upload).await.unwrap());
+
+        let mut keys: Vec<_> = cache
+            .storage()
+            .list(CACHE_PREFIX)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|object| object.key)
+            .collect();
+        keys.sort();
+        assert_eq!(keys, vec!["card/42.jpg", "card/42.jpg.sha256"]);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_orphans() {
+        with_db_pool(async move |db| {
+            let dir = test_dir("sweep");
+            let cache = test_cache(dir.path());
+            let client = db.get().await.expect("db");
+            let ids = save_cards(&client, &[100, 200]).await;
+            card::delete_by_id(&client, ids[1]).await.expect("delete");
+
+            let kept =
+                get_card_image_key(ids[0], 0, &CardImageSize::Small, None, ImageFormat::Jpeg);
+            cache.save(&kept, b"image".to_vec()).await.unwrap();
+            for width in [None, Some(20)] {
+                let key =
+                    get_card_image_key(ids[1], 0, &CardImageSize::Small, width, ImageFormat::Jpeg);
+                cache.save(&key, b"image".to_vec()).await.unwrap();
+            }
+            cache
+                .storage()
+                .write("prefetch_checkpoint", b"1")
+                .await
+                .unwrap();
+
+            let result = sweep_orphans(&client, &cache).await.expect("sweep");
+            assert_eq!(result.cards, 1);
+            assert_eq!(result.images, 2);
+            assert_eq!(result.freed, 2 * 69);
+
+            let stats = cache.stats().await.unwrap();
+            assert_eq!((stats.images, stats.size), (1, 69));
+            assert!(cache.storage().exists(&kept).await.unwrap());
+            assert!(cache.storage().exists("prefetch_checkpoint").await.unwrap());
+        })
+        .await;
+    }
+}
@@ -1 +1,12 @@
+pub mod artwork;
+pub mod banlist;
 pub mod card;
+pub mod collection;
+pub mod deck;
+pub mod image;
+pub mod price;
+pub mod query;
+pub mod scan;
+pub mod set;
+pub mod ydk;
+pub mod ydke;
@@ -0,0 +1,151 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::result::Result;
+use tokio_postgres::{Client, Error, Row};
+
+use crate::database::QueryParams;
+use crate::models::ygo;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Filter {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub source: Option<ygo::PriceSource>,
+}
+
+/// Retrieves the price history of a card, oldest first
+pub async fn get_history(
+    client: &Client,
+    card_id: i32,
+    filter: Option<Filter>,
+) -> Result<Vec<ygo::CardPrice>, Error> {
+    let mut query = String::from("SELECT * FROM ygo_card_prices WHERE card_id = $1");
+    let mut params = QueryParams::new();
+    params.push(card_id);
+
+    if let Some(filter) = filter {
+        // Filter by date range (inclusive)
+        if let Some(from) = filter.from {
+            let idx = params.push(from);
+            query.push_str(&format!(" AND date >= ${idx}"));
+        }
+        if let Some(to) = filter.to {
+            let idx = params.push(to);
+            query.push_str(&format!(" AND date <= ${idx}"));
+        }
+
+        // Filter by source
+        if let Some(source) = filter.source {
+            let idx = params.push(source);
+            query.push_str(&format!(" AND source = ${idx}"));
+        }
+    }
+
+    query.push_str(" ORDER BY date ASC, source ASC");
+
+    let rows = client.query(&query, &params.as_refs()).await?;
+
+    rows.iter().map(|row| row.try_into()).collect()
+}
+
+/// Saves the prices of many cards at a date at once, replacing the ones already saved for the
+/// same cards and sources. Each card must have one price per source at most.
+pub async fn save_snapshots(
+    client: &Client,
+    date: NaiveDate,
+    prices: &[(i32, ygo::PriceSource, Decimal)],
+) -> Result<(), Error> {
+    let card_ids: Vec<i32> = prices.iter().map(|&(card_id, _, _)| card_id).collect();
+    let sources: Vec<ygo::PriceSource> = prices.iter().map(|&(_, source, _)| source).collect();
+    let prices: Vec<Decimal> = prices.iter().map(|&(_, _, price)| price).collect();
+
+    client
+        .execute(
+            r#"
+            INSERT INTO ygo_card_prices (card_id, source, date, price)
+            SELECT card_id, source, $1, price
+            FROM UNNEST($2::INTEGER[], $3::YGO_PRICE_SOURCE[], $4::NUMERIC[])
+                AS prices (card_id, source, price)
+            ON CONFLICT (card_id, source, date) DO UPDATE SET
+                price = EXCLUDED.price
+            "#,
+            &[&date, &card_ids, &sources, &prices],
+        )
+        .await?;
+
+    Ok(())
+}
+
+impl TryFrom<&Row> for ygo::CardPrice {
+    type Error = Error;
+
+    /// Converts a database row into a CardPrice struct
+    fn try_from(value: &Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            source: value.try_get("source")?,
+            date: value.try_get("date")?,
+            price: value.try_get("price")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{services::ygo::card::seed_cards, test_utils::with_db_pool};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, day).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_save_snapshot_and_get_history() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 1).await.expect("seed");
+
+            let snapshots = [
+                (date(1), "1.50", "1.20"),
+                (date(2), "1.75", "1.25"),
+                (date(3), "2.00", "1.30"),
+            ];
+            for (date, tcgplayer, cardmarket) in snapshots {
+                let prices = [
+                    (1, ygo::PriceSource::Tcgplayer, tcgplayer.parse().unwrap()),
+                    (1, ygo::PriceSource::Cardmarket, cardmarket.parse().unwrap()),
+                ];
+                save_snapshots(&client, date, &prices)
+                    .await
+                    .expect("snapshot");
+            }
+
+            // Saving a snapshot twice the same day replaces the price
+            let prices = [(1, ygo::PriceSource::Tcgplayer, "2.10".parse().unwrap())];
+            save_snapshots(&client, date(3), &prices)
+                .await
+                .expect("snapshot");
+
+            let history = get_history(&client, 1, None).await.expect("history");
+            assert_eq!(history.len(), 6);
+            assert_eq!(history[0].date, date(1));
+            assert_eq!(history[0].source, ygo::PriceSource::Tcgplayer);
+
+            let filter = Filter {
+                from: Some(date(2)),
+                to: Some(date(3)),
+                source: Some(ygo::PriceSource::Tcgplayer),
+            };
+            let history = get_history(&client, 1, Some(filter))
+                .await
+                .expect("history");
+            let prices: Vec<_> = history.iter().map(|p| p.price.to_string()).collect();
+            assert_eq!(prices, vec!["1.75", "2.10"]);
+
+            let none = get_history(&client, 144, None).await.expect("history");
+            assert!(none.is_empty());
+        })
+        .await;
+    }
+}
@@ -0,0 +1,598 @@
+use serde::de::{DeserializeOwned, IntoDeserializer, value::Error as ValueError};
+use serde::{Deserialize, Serialize};
+
+use crate::database::QueryParams;
+use crate::models::ygo;
+
+/// Error raised by a query that cannot be parsed, pointing to the failing character
+#[derive(thiserror::Error, Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[error("{message} at position {position}")]
+pub struct ParseError {
+    pub message: String,
+    /// Position of the failing character, starting at 0
+    pub position: usize,
+}
+
+impl ParseError {
+    fn new(message: impl Into<String>, position: usize) -> Self {
+        Self {
+            message: message.into(),
+            position,
+        }
+    }
+}
+
+/// Comparison operators of field terms (`:` is an alias of `=`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Comparison::Eq => "=",
+            Comparison::Ne => "<>",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+}
+
+/// Numeric monster stats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stat {
+    Atk,
+    Def,
+    Level,
+}
+
+impl Stat {
+    fn column(&self) -> &'static str {
+        match self {
+            Stat::Atk => "monster_atk",
+            Stat::Def => "monster_def",
+            Stat::Level => "monster_level",
+        }
+    }
+}
+
+/// A single search condition
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    /// Full-text search of a word, e.g. `destroy`
+    Text(String),
+    /// Full-text search of a phrase, e.g. `"destroy all"`
+    Phrase(String),
+    Name(String),
+    Description(String),
+    Kind(ygo::CardKind),
+    MonsterKind(ygo::MonsterKind),
+    Subtype(ygo::MonsterSubtype),
+    Pendulum,
+    Attribute(ygo::MonsterAttribute),
+    Race(ygo::MonsterRace),
+    Spell(ygo::SpellKind),
+    Trap(ygo::TrapKind),
+    Stat(Stat, Comparison, i16),
+}
+
+/// A parsed search query, e.g. `atk>=2500 attr:dark (race:dragon OR type:synchro) -"destroy"`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Term(Term),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+impl Expr {
+    /// Compiles the query into a SQL condition on `ygo_cards`
+    pub fn to_sql(&self, params: &mut QueryParams) -> String {
+        match self {
+            Expr::Term(term) => term_to_sql(term, params),
+            // Cards without a value for the field (e.g. ATK of spells) don't match the term,
+            // so they match its negation
+            Expr::Not(expr) => format!("({}) IS NOT TRUE", expr.to_sql(params)),
+            Expr::And(exprs) => join_sql(exprs, " AND ", params),
+            Expr::Or(exprs) => join_sql(exprs, " OR ", params),
+        }
+    }
+
+    /// Returns a SQL expression ranking cards against the full-text terms of the query.
+    /// Negated terms don't count, and every card is as relevant without full-text terms.
+    pub fn rank_sql(&self, params: &mut QueryParams) -> String {
+        let mut terms = Vec::new();
+        self.collect_text_terms(&mut terms);
+        if terms.is_empty() {
+            return "0::REAL".to_string();
+        }
+
+        let tsqueries: Vec<String> = terms
+            .into_iter()
+            .map(|term| tsquery_sql(term, params))
+            .collect();
+        format!("ts_rank(search_vector, {})", tsqueries.join(" || "))
+    }
+
+    fn collect_text_terms<'a>(&'a self, terms: &mut Vec<&'a Term>) {
+        match self {
+            Expr::Term(term @ (Term::Text(_) | Term::Phrase(_))) => terms.push(term),
+            Expr::Term(_) | Expr::Not(_) => {}
+            Expr::And(exprs) | Expr::Or(exprs) => {
+                exprs.iter().for_each(|expr| expr.collect_text_terms(terms))
+            }
+        }
+    }
+}
+
+fn join_sql(exprs: &[Expr], separator: &str, params: &mut QueryParams) -> String {
+    let conditions: Vec<String> = exprs.iter().map(|expr| expr.to_sql(params)).collect();
+    format!("({})", conditions.join(separator))
+}
+
+fn tsquery_sql(term: &Term, params: &mut QueryParams) -> String {
+    match term {
+        Term::Phrase(text) => format!(
+            "phraseto_tsquery('english', ${})",
+            params.push(text.clone())
+        ),
+        Term::Text(text) => format!("plainto_tsquery('english', ${})", params.push(text.clone())),
+        _ => unreachable!("only text terms are full-text searched"),
+    }
+}
+
+fn term_to_sql(term: &Term, params: &mut QueryParams) -> String {
+    match term {
+        Term::Text(_) | Term::Phrase(_) => {
+            format!("search_vector @@ {}", tsquery_sql(term, params))
+        }
+        Term::Name(name) => format!("name ILIKE ${}", params.push(format!("%{name}%"))),
+        Term::Description(description) => format!(
+            "description ILIKE ${}",
+            params.push(format!("%{description}%"))
+        ),
+        Term::Kind(kind) => format!("kind = ${}", params.push(kind.clone())),
+        Term::MonsterKind(kind) => format!("monster_kind = ${}", params.push(kind.clone())),
+        Term::Subtype(subtype) => {
+            format!(
+                "monster_subtypes @> ${}",
+                params.push(vec![subtype.clone()])
+            )
+        }
+        Term::Pendulum => "monster_pendulum_scale IS NOT NULL".to_string(),
+        Term::Attribute(attribute) => {
+            format!("monster_attribute = ${}", params.push(attribute.clone()))
+        }
+        Term::Race(race) => format!("monster_race = ${}", params.push(race.clone())),
+        Term::Spell(kind) => format!("spell_kind = ${}", params.push(kind.clone())),
+        Term::Trap(kind) => format!("trap_kind = ${}", params.push(kind.clone())),
+        Term::Stat(stat, comparison, value) => format!(
+            "{} {} ${}",
+            stat.column(),
+            comparison.as_sql(),
+            params.push(*value)
+        ),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Minus,
+    Op(Comparison),
+    Word(String),
+    Quoted(String),
+}
+
+/// Whether a character ends a bare word
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | '"' | ':' | '=' | '<' | '>')
+}
+
+/// Splits a query into tokens, along with their position
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens: Vec<(Token, usize)> = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let start = i;
+
+        let token = match c {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ':' | '=' => Token::Op(Comparison::Eq),
+            '!' if next == Some('=') => {
+                i += 1;
+                Token::Op(Comparison::Ne)
+            }
+            '<' | '>' => {
+                let or_equal = next == Some('=');
+                if or_equal {
+                    i += 1;
+                }
+                Token::Op(match (c, or_equal) {
+                    ('<', false) => Comparison::Lt,
+                    ('<', true) => Comparison::Le,
+                    ('>', false) => Comparison::Gt,
+                    _ => Comparison::Ge,
+                })
+            }
+            '"' => {
+                let Some(length) = chars[i + 1..].iter().position(|&c| c == '"') else {
+                    return Err(ParseError::new("unclosed quote", start));
+                };
+                let text: String = chars[i + 1..i + 1 + length].iter().collect();
+                i += length + 1;
+                Token::Quoted(text)
+            }
+            // A minus negates the next term, unless it's the sign of a value (e.g. `atk>-1`)
+            '-' if next.is_some_and(|next| !next.is_whitespace())
+                && !matches!(tokens.last(), Some((Token::Op(_), _))) =>
+            {
+                Token::Minus
+            }
+            _ => {
+                let length = chars[i..]
+                    .iter()
+                    .enumerate()
+                    .position(|(offset, &c)| {
+                        offset > 0
+                            && (is_delimiter(c)
+                                || (c == '!' && chars.get(i + offset + 1) == Some(&'=')))
+                    })
+                    .unwrap_or(chars.len() - i);
+                let word: String = chars[i..i + length].iter().collect();
+                i += length - 1;
+                Token::Word(word)
+            }
+        };
+
+        tokens.push((token, start));
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+/// Parses a search query.
+///
+/// Terms are separated by spaces and must all match, unless separated by `OR`.
+/// A term is negated by a leading `-` or `NOT`, and terms can be grouped with parentheses.
+/// Bare words and quoted phrases are searched in card names and effect texts, while field terms
+/// compare a field with a value, e.g. `atk>=2500`, `attr:dark`, `race!=dragon` or
+/// `name:"blue-eyes"`.
+pub fn parse(input: &str) -> Result<Expr, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        idx: 0,
+        end: input.chars().count(),
+    };
+
+    let expr = parser.parse_or(0)?;
+    if let Some((_, position)) = parser.peek() {
+        return Err(ParseError::new("unexpected ')'", *position));
+    }
+
+    Ok(expr)
+}
+
+/// Maximum nesting of groups and negations, which are parsed recursively
+const MAX_DEPTH: usize = 32;
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    idx: usize,
+    /// Position of the end of the query
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&(Token, usize)> {
+        self.tokens.get(self.idx)
+    }
+
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.idx).cloned();
+        self.idx += 1;
+        token
+    }
+
+    /// Whether the next token is the given keyword
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some((Token::Word(word), _)) if word.eq_ignore_ascii_case(keyword))
+            && !matches!(self.tokens.get(self.idx + 1), Some((Token::Op(_), _)))
+    }
+
+    fn parse_or(&mut self, depth: usize) -> Result<Expr, ParseError> {
+        let mut exprs = vec![self.parse_and(depth)?];
+        while self.peek_keyword("or") {
+            self.idx += 1;
+            exprs.push(self.parse_and(depth)?);
+        }
+
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::Or(exprs)
+        })
+    }
+
+    fn parse_and(&mut self, depth: usize) -> Result<Expr, ParseError> {
+        let mut exprs = vec![self.parse_unary(depth)?];
+        loop {
+            if self.peek_keyword("and") {
+                self.idx += 1;
+            } else if self.peek_keyword("or")
+                || matches!(self.peek(), None | Some((Token::RParen, _)))
+            {
+                break;
+            }
+            exprs.push(self.parse_unary(depth)?);
+        }
+
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::And(exprs)
+        })
+    }
+
+    fn parse_unary(&mut self, depth: usize) -> Result<Expr, ParseError> {
+        if depth > MAX_DEPTH {
+            let position = self.peek().map_or(self.end, |(_, position)| *position);
+            return Err(ParseError::new("query is nested too deeply", position));
+        }
+
+        if matches!(self.peek(), Some((Token::Minus, _))) || self.peek_keyword("not") {
+            self.idx += 1;
+            return Ok(Expr::Not(Box::new(self.parse_unary(depth + 1)?)));
+        }
+
+        self.parse_primary(depth)
+    }
+
+    fn parse_primary(&mut self, depth: usize) -> Result<Expr, ParseError> {
+        let Some((token, position)) = self.next() else {
+            return Err(ParseError::new("expected a search term", self.end));
+        };
+
+        match token {
+            Token::LParen => {
+                let expr = self.parse_or(depth + 1)?;
+                match self.next() {
+                    Some((Token::RParen, _)) => Ok(expr),
+                    _ => Err(ParseError::new("expected ')'", self.end)),
+                }
+            }
+            Token::Word(word) => match self.peek() {
+                Some((Token::Op(comparison), op_position)) => {
+                    let (comparison, op_position) = (*comparison, *op_position);
+                    self.idx += 1;
+                    let (value, value_position) = match self.next() {
+                        Some((Token::Word(value) | Token::Quoted(value), value_position)) => {
+                            (value, value_position)
+                        }
+                        Some((_, value_position)) => {
+                            return Err(ParseError::new(
+                                format!("expected a value for '{word}'"),
+                                value_position,
+                            ));
+                        }
+                        None => {
+                            return Err(ParseError::new(
+                                format!("expected a value for '{word}'"),
+                                self.end,
+                            ));
+                        }
+                    };
+                    field_term(
+                        (&word, position),
+                        (comparison, op_position),
+                        (&value, value_position),
+                    )
+                }
+                _ => Ok(Expr::Term(Term::Text(word))),
+            },
+            Token::Quoted(text) if text.trim().is_empty() => {
+                Err(ParseError::new("expected text between quotes", position))
+            }
+            Token::Quoted(text) => Ok(Expr::Term(Term::Phrase(text))),
+            Token::RParen => Err(ParseError::new("unexpected ')'", position)),
+            Token::Minus | Token::Op(_) => Err(ParseError::new("expected a search term", position)),
+        }
+    }
+}
+
+/// Parses a value as one of the variants of an enum, e.g. `quick-play` as `SpellKind::QuickPlay`
+fn parse_variant<T: DeserializeOwned>(value: &str) -> Option<T> {
+    let value = value.to_lowercase().replace('-', "_");
+    T::deserialize(IntoDeserializer::<ValueError>::into_deserializer(value)).ok()
+}
+
+/// Builds the condition of a field term, e.g. `atk>=2500`
+fn field_term(
+    (field, field_position): (&str, usize),
+    (comparison, op_position): (Comparison, usize),
+    (value, value_position): (&str, usize),
+) -> Result<Expr, ParseError> {
+    let invalid_value = || {
+        ParseError::new(
+            format!("invalid value '{value}' for '{field}'"),
+            value_position,
+        )
+    };
+
+    let stat = match field.to_lowercase().as_str() {
+        "atk" => Some(Stat::Atk),
+        "def" => Some(Stat::Def),
+        "level" | "lvl" | "lv" => Some(Stat::Level),
+        _ => None,
+    };
+    if let Some(stat) = stat {
+        let value: i16 = value.parse().map_err(|_| invalid_value())?;
+        return Ok(Expr::Term(Term::Stat(stat, comparison, value)));
+    }
+
+    let term = match field.to_lowercase().as_str() {
+        "name" | "n" => Term::Name(value.to_string()),
+        "desc" | "text" | "o" => Term::Description(value.to_string()),
+        "kind" | "k" => Term::Kind(parse_variant(value).ok_or_else(invalid_value)?),
+        "type" | "t" if value.eq_ignore_ascii_case("pendulum") => Term::Pendulum,
+        "type" | "t" => parse_variant(value)
+            .map(Term::MonsterKind)
+            .or_else(|| parse_variant(value).map(Term::Subtype))
+            .ok_or_else(invalid_value)?,
+        "attr" | "attribute" | "a" => {
+            Term::Attribute(parse_variant(value).ok_or_else(invalid_value)?)
+        }
+        "race" | "r" => Term::Race(parse_variant(value).ok_or_else(invalid_value)?),
+        "spell" => Term::Spell(parse_variant(value).ok_or_else(invalid_value)?),
+        "trap" => Term::Trap(parse_variant(value).ok_or_else(invalid_value)?),
+        _ => {
+            return Err(ParseError::new(
+                format!("unknown field '{field}'"),
+                field_position,
+            ));
+        }
+    };
+
+    match comparison {
+        Comparison::Eq => Ok(Expr::Term(term)),
+        Comparison::Ne => Ok(Expr::Not(Box::new(Expr::Term(term)))),
+        _ => Err(ParseError::new(
+            format!("'{field}' can only be compared with ':', '=' or '!='"),
+            op_position,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(word: &str) -> Expr {
+        Expr::Term(Term::Text(word.to_string()))
+    }
+
+    #[test]
+    fn test_parse_terms() {
+        assert_eq!(
+            parse(r#"atk>=2500 attr:dark race:dragon type:synchro "destroy""#),
+            Ok(Expr::And(vec![
+                Expr::Term(Term::Stat(Stat::Atk, Comparison::Ge, 2500)),
+                Expr::Term(Term::Attribute(ygo::MonsterAttribute::Dark)),
+                Expr::Term(Term::Race(ygo::MonsterRace::Dragon)),
+                Expr::Term(Term::MonsterKind(ygo::MonsterKind::Synchro)),
+                Expr::Term(Term::Phrase("destroy".to_string())),
+            ]))
+        );
+        assert_eq!(
+            parse("TYPE:Tuner spell:quick-play name:\"blue-eyes\" def < -1"),
+            Ok(Expr::And(vec![
+                Expr::Term(Term::Subtype(ygo::MonsterSubtype::Tuner)),
+                Expr::Term(Term::Spell(ygo::SpellKind::QuickPlay)),
+                Expr::Term(Term::Name("blue-eyes".to_string())),
+                Expr::Term(Term::Stat(Stat::Def, Comparison::Lt, -1)),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_operators() {
+        // AND binds tighter than OR
+        assert_eq!(
+            parse("a OR b and c"),
+            Ok(Expr::Or(vec![
+                text("a"),
+                Expr::And(vec![text("b"), text("c")])
+            ]))
+        );
+        assert_eq!(
+            parse("-(a or b) NOT blue-eyes race!=zombie"),
+            Ok(Expr::And(vec![
+                Expr::Not(Box::new(Expr::Or(vec![text("a"), text("b")]))),
+                Expr::Not(Box::new(text("blue-eyes"))),
+                Expr::Not(Box::new(Expr::Term(Term::Race(ygo::MonsterRace::Zombie)))),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |message: &str, position| Err(ParseError::new(message, position));
+
+        assert_eq!(parse(""), error("expected a search term", 0));
+        assert_eq!(parse("atk>=2500 (dragon"), error("expected ')'", 17));
+        assert_eq!(parse("dragon)"), error("unexpected ')'", 6));
+        assert_eq!(parse("dragon OR"), error("expected a search term", 9));
+        assert_eq!(parse("name:\"blue"), error("unclosed quote", 5));
+        assert_eq!(parse("foo:bar"), error("unknown field 'foo'", 0));
+        assert_eq!(
+            parse("atk>=high"),
+            error("invalid value 'high' for 'atk'", 5)
+        );
+        assert_eq!(
+            parse("attr:sound"),
+            error("invalid value 'sound' for 'attr'", 5)
+        );
+        assert_eq!(
+            parse("dragon attr>dark"),
+            error("'attr' can only be compared with ':', '=' or '!='", 11)
+        );
+        assert_eq!(parse("level:"), error("expected a value for 'level'", 6));
+        assert_eq!(parse("\"  \""), error("expected text between quotes", 0));
+
+        // Deep nesting is rejected before it can overflow the stack
+        assert!(parse(&format!("{}dragon{}", "(".repeat(32), ")".repeat(32))).is_ok());
+        assert_eq!(
+            parse(&format!("{}dragon{}", "(".repeat(33), ")".repeat(33))),
+            error("query is nested too deeply", 33)
+        );
+        assert_eq!(
+            parse(&"(".repeat(1000)),
+            error("query is nested too deeply", 33)
+        );
+        assert_eq!(
+            parse(&format!("{}dragon", "-".repeat(1000))),
+            error("query is nested too deeply", 33)
+        );
+        assert_eq!(
+            parse(&"NOT ".repeat(1000)),
+            error("query is nested too deeply", 132)
+        );
+    }
+
+    #[test]
+    fn test_to_sql() {
+        let expr = parse("atk>=2500 -(attr:dark OR \"destroy\")").unwrap();
+        let mut params = QueryParams::new();
+        assert_eq!(
+            expr.to_sql(&mut params),
+            "(monster_atk >= $1 AND ((monster_attribute = $2 OR search_vector @@ phraseto_tsquery('english', $3))) IS NOT TRUE)"
+        );
+
+        // Negated terms don't count in the ranking
+        assert_eq!(expr.rank_sql(&mut params), "0::REAL");
+        let expr = parse("dragon \"white dragon\"").unwrap();
+        assert_eq!(
+            expr.rank_sql(&mut params),
+            "ts_rank(search_vector, plainto_tsquery('english', $4) || phraseto_tsquery('english', $5))"
+        );
+    }
+}
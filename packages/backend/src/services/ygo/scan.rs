@@ -0,0 +1,382 @@
+use std::collections::HashSet;
+use std::result::Result;
+
+use tokio_postgres::{Client, Error, Row};
+
+use crate::database::TzTimestamp;
+use crate::models::ygo;
+use crate::services::image_cache::{self, ImageCache};
+
+/// Prefix of the keys of collection scans. Like uploaded card images, they are kept out of the
+/// cache, so that they are never evicted.
+const SCAN_PREFIX: &str = "upload/scan/";
+
+/// Utility to get the key of a collection scan's image in the image storage
+pub fn get_scan_key(item_id: i32, scan_id: i32) -> String {
+    format!("{SCAN_PREFIX}{item_id}/{scan_id}")
+}
+
+/// Utility to get the ID of the scan an image key belongs to, sidecar files included
+pub fn get_scan_id_from_key(key: &str) -> Option<i32> {
+    let (_, name) = key.strip_prefix(SCAN_PREFIX)?.split_once('/')?;
+    let end = name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(name.len());
+    name[..end].parse().ok()
+}
+
+/// Lists the scans of a collection item, by side then in upload order
+pub async fn get_by_item_id(
+    client: &Client,
+    item_id: i32,
+) -> Result<Vec<ygo::CollectionScan>, Error> {
+    let rows = client
+        .query(
+            "SELECT * FROM ygo_collection_scans WHERE item_id = $1 ORDER BY side, id",
+            &[&item_id],
+        )
+        .await?;
+
+    rows.iter().map(|row| row.try_into()).collect()
+}
+
+/// Retrieves a scan of a collection item by ID
+pub async fn get_by_id(
+    client: &Client,
+    item_id: i32,
+    id: i32,
+) -> Result<Option<ygo::CollectionScan>, Error> {
+    let row = client
+        .query_opt(
+            "SELECT * FROM ygo_collection_scans WHERE item_id = $1 AND id = $2",
+            &[&item_id, &id],
+        )
+        .await?;
+
+    row.as_ref().map(|row| row.try_into()).transpose()
+}
+
+/// Insert a new scan along with its image, and return the created record.
+///
+/// The image is saved once the scan is recorded, so that the orphan sweep never takes it for the
+/// image of a deleted scan. The record is deleted if the image can't be saved.
+pub async fn save_new(
+    client: &Client,
+    cache: &ImageCache,
+    new_scan: &ygo::NewCollectionScan,
+    image: Vec<u8>,
+) -> anyhow::Result<ygo::CollectionScan> {
+    let d = &new_scan.data;
+
+    let row = client
+        .query_one(
+            r#"
+            INSERT INTO ygo_collection_scans (
+                item_id,
+                side,
+                capture_date,
+                width,
+                height,
+                size
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6
+            ) RETURNING *
+            "#,
+            &[
+                &d.item_id,
+                &d.side,
+                &d.capture_date,
+                &d.width,
+                &d.height,
+                &d.size,
+            ],
+        )
+        .await?;
+    let scan: ygo::CollectionScan = (&row).try_into()?;
+
+    let key = get_scan_key(scan.data.item_id, scan.id);
+    if let Err(err) = cache.save(&key, image).await {
+        client
+            .execute(
+                "DELETE FROM ygo_collection_scans WHERE id = $1",
+                &[&scan.id],
+            )
+            .await?;
+        return Err(err.into());
+    }
+
+    Ok(scan)
+}
+
+/// Deletes a scan of a collection item by ID, along with its image.
+/// Returns true if a scan was deleted, false otherwise.
+///
+/// The image is deleted once the scan is, and left for the orphan sweep if that fails.
+pub async fn delete_by_id(
+    client: &Client,
+    cache: &ImageCache,
+    item_id: i32,
+    id: i32,
+) -> anyhow::Result<bool> {
+    let affected = client
+        .execute(
+            "DELETE FROM ygo_collection_scans WHERE item_id = $1 AND id = $2",
+            &[&item_id, &id],
+        )
+        .await?;
+    if affected == 0 {
+        return Ok(false);
+    }
+
+    cache.delete(&get_scan_key(item_id, id)).await?;
+    Ok(true)
+}
+
+/// Deletes the images of every scan of a collection item.
+/// Their records are deleted along with the item.
+pub async fn delete_item_scan_images(cache: &ImageCache, item_id: i32) -> std::io::Result<()> {
+    let objects = cache
+        .storage()
+        .list(&format!("{SCAN_PREFIX}{item_id}/"))
+        .await?;
+    let keys: HashSet<_> = objects
+        .iter()
+        .map(|object| image_cache::get_image_key(&object.key))
+        .collect();
+
+    for key in keys {
+        cache.delete(key).await?;
+    }
+
+    Ok(())
+}
+
+/// Deletes the images of scans that no longer exist, such as those of deleted collection items.
+/// Returns the number of images deleted and the bytes freed, sidecar files included.
+pub async fn sweep_orphans(client: &Client, cache: &ImageCache) -> anyhow::Result<(u64, u64)> {
+    let objects = cache.storage().list(SCAN_PREFIX).await?;
+
+    let scan_ids: Vec<i32> = objects
+        .iter()
+        .filter_map(|object| get_scan_id_from_key(&object.key))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let existing: HashSet<i32> = client
+        .query(
+            "SELECT id FROM ygo_collection_scans WHERE id = ANY($1)",
+            &[&scan_ids],
+        )
+        .await?
+        .iter()
+        .map(|row| row.try_get(0))
+        .collect::<Result<_, _>>()?;
+
+    let mut keys = HashSet::new();
+    let mut freed = 0;
+    for object in &objects {
+        let Some(scan_id) = get_scan_id_from_key(&object.key) else {
+            continue;
+        };
+        if existing.contains(&scan_id) {
+            continue;
+        }
+
+        keys.insert(image_cache::get_image_key(&object.key));
+        freed += object.size;
+    }
+
+    for key in &keys {
+        cache.delete(key).await?;
+    }
+
+    Ok((keys.len() as u64, freed))
+}
+
+impl TryFrom<&Row> for ygo::CollectionScan {
+    type Error = Error;
+
+    /// Converts a database row into a CollectionScan struct
+    fn try_from(value: &Row) -> Result<Self, Self::Error> {
+        let created_at: TzTimestamp = value.try_get("created_at")?;
+
+        Ok(Self {
+            id: value.try_get("id")?,
+            created_at: created_at.0,
+            data: value.try_into()?,
+        })
+    }
+}
+
+impl TryFrom<&Row> for ygo::CollectionScanData {
+    type Error = Error;
+
+    fn try_from(value: &Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            item_id: value.try_get("item_id")?,
+            side: value.try_get("side")?,
+            capture_date: value.try_get("capture_date")?,
+            width: value.try_get("width")?,
+            height: value.try_get("height")?,
+            size: value.try_get("size")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::services::image_storage::FilesystemStorage;
+    use crate::services::ygo::{card::seed_cards, collection};
+    use crate::test_utils::{test_dir, with_db_pool};
+
+    async fn make_item(client: &Client) -> i32 {
+        seed_cards(client, 1).await.expect("seed");
+        let new = ygo::NewCollectionItem {
+            data: ygo::CollectionItemData {
+                card_id: 1,
+                quantity: 1,
+                ..Default::default()
+            },
+        };
+        collection::save_new(client, &new).await.expect("item").id
+    }
+
+    fn make_scan(item_id: i32, side: ygo::ScanSide) -> ygo::NewCollectionScan {
+        ygo::NewCollectionScan {
+            data: ygo::CollectionScanData {
+                item_id,
+                side,
+                capture_date: chrono::NaiveDate::from_ymd_opt(2026, 10, 1),
+                width: 64,
+                height: 96,
+                size: 5,
+            },
+        }
+    }
+
+    #[test]
+    fn test_get_scan_id_from_key() {
+        assert_eq!(get_scan_key(12, 345), "upload/scan/12/345");
+        assert_eq!(get_scan_id_from_key("upload/scan/12/345"), Some(345));
+        assert_eq!(get_scan_id_from_key("upload/scan/12/345.sha256"), Some(345));
+        assert_eq!(get_scan_id_from_key("upload/scan/12"), None);
+        assert_eq!(get_scan_id_from_key("upload/card/12"), None);
+    }
+
+    #[tokio::test]
+    async fn test_save_new_get_and_delete() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            let dir = test_dir("scans");
+            let cache = ImageCache::new(Arc::new(FilesystemStorage::new(dir.path())), None);
+            let item_id = make_item(&client).await;
+
+            let back = save_new(
+                &client,
+                &cache,
+                &make_scan(item_id, ygo::ScanSide::Back),
+                b"back".to_vec(),
+            )
+            .await
+            .expect("insert");
+            let front = save_new(
+                &client,
+                &cache,
+                &make_scan(item_id, ygo::ScanSide::Front),
+                b"front".to_vec(),
+            )
+            .await
+            .expect("insert");
+            assert_eq!(front.data, make_scan(item_id, ygo::ScanSide::Front).data);
+
+            // Front scans come first
+            let scans = get_by_item_id(&client, item_id).await.expect("list");
+            assert_eq!(scans, vec![front.clone(), back.clone()]);
+
+            let image = cache.read(&get_scan_key(item_id, front.id)).await.unwrap();
+            assert_eq!(image.unwrap().data, b"front");
+
+            // Scans belong to their item
+            assert_eq!(
+                get_by_id(&client, item_id + 1, front.id).await.unwrap(),
+                None
+            );
+            assert!(
+                !delete_by_id(&client, &cache, item_id + 1, front.id)
+                    .await
+                    .unwrap()
+            );
+
+            assert!(
+                delete_by_id(&client, &cache, item_id, front.id)
+                    .await
+                    .unwrap()
+            );
+            assert_eq!(get_by_id(&client, item_id, front.id).await.unwrap(), None);
+            let image = cache.read(&get_scan_key(item_id, front.id)).await.unwrap();
+            assert!(image.is_none());
+
+            // Images of deleted items are left for the orphan sweep
+            collection::delete_by_id(&client, item_id).await.unwrap();
+            assert!(get_by_item_id(&client, item_id).await.unwrap().is_empty());
+            let (images, freed) = sweep_orphans(&client, &cache).await.unwrap();
+            assert_eq!((images, freed), (1, 68));
+            let image = cache.read(&get_scan_key(item_id, back.id)).await.unwrap();
+            assert!(image.is_none());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_save_new_without_image() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+
+            // Images can't be written under a file
+            let dir = test_dir("scans-failing");
+            let root = dir.path().join("file");
+            tokio::fs::write(&root, b"").await.unwrap();
+            let cache = ImageCache::new(Arc::new(FilesystemStorage::new(root)), None);
+            let item_id = make_item(&client).await;
+
+            let result = save_new(
+                &client,
+                &cache,
+                &make_scan(item_id, ygo::ScanSide::Front),
+                b"front".to_vec(),
+            )
+            .await;
+            assert!(result.is_err());
+            assert!(get_by_item_id(&client, item_id).await.unwrap().is_empty());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_delete_item_scan_images() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            let dir = test_dir("item-scans");
+            let cache = ImageCache::new(Arc::new(FilesystemStorage::new(dir.path())), None);
+            let item_id = make_item(&client).await;
+
+            let scan = save_new(
+                &client,
+                &cache,
+                &make_scan(item_id, ygo::ScanSide::Slab),
+                b"slab".to_vec(),
+            )
+            .await
+            .expect("insert");
+
+            delete_item_scan_images(&cache, item_id).await.unwrap();
+            let image = cache.read(&get_scan_key(item_id, scan.id)).await.unwrap();
+            assert!(image.is_none());
+            assert!(cache.storage().list(SCAN_PREFIX).await.unwrap().is_empty());
+        })
+        .await;
+    }
+}
@@ -0,0 +1,421 @@
+use serde::Deserialize;
+use std::result::Result;
+use tokio_postgres::{Client, Error, Row};
+
+use crate::database::QueryParams;
+use crate::models::ygo;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Filter {
+    pub name: Option<String>,
+    pub code: Option<String>,
+}
+
+/// Retrieves all sets, most recent releases first
+pub async fn get_all(client: &Client, filter: Option<Filter>) -> Result<Vec<ygo::CardSet>, Error> {
+    let mut query = String::from("SELECT * FROM ygo_sets");
+    let mut params = QueryParams::new();
+    let mut where_queries: Vec<String> = Vec::new();
+
+    if let Some(filter) = filter {
+        // Filter by name
+        if let Some(name) = filter.name {
+            let idx = params.push(format!("%{}%", name));
+            where_queries.push(format!("name ILIKE ${idx}"));
+        }
+
+        // Filter by set code
+        if let Some(code) = filter.code {
+            let idx = params.push(code);
+            where_queries.push(format!("code ILIKE ${idx}"));
+        }
+    }
+
+    if !where_queries.is_empty() {
+        query.push_str(" WHERE ");
+        query.push_str(&where_queries.join(" AND "));
+    }
+
+    query.push_str(" ORDER BY release_date DESC NULLS LAST, name ASC");
+
+    let rows = client.query(&query, &params.as_refs()).await?;
+
+    rows.iter().map(|row| row.try_into()).collect()
+}
+
+/// Retrieves a set by ID
+pub async fn get_by_id(client: &Client, id: i32) -> Result<Option<ygo::CardSet>, Error> {
+    let query = "SELECT * FROM ygo_sets WHERE id = $1";
+    let row = &client.query_opt(query, &[&id]).await?;
+
+    row.as_ref().map(|r| r.try_into()).transpose()
+}
+
+/// Inserts a set, or updates the existing set with the same name.
+/// Missing release dates and card counts do not override known ones.
+pub async fn upsert(client: &Client, data: &ygo::CardSetData) -> Result<ygo::CardSet, Error> {
+    let row = client
+        .query_one(
+            r#"
+            INSERT INTO ygo_sets (
+                name,
+                code,
+                release_date,
+                card_count
+            ) VALUES (
+                $1, $2, $3, $4
+            )
+            ON CONFLICT (name) DO UPDATE SET
+                code = EXCLUDED.code,
+                release_date = COALESCE(EXCLUDED.release_date, ygo_sets.release_date),
+                card_count = COALESCE(EXCLUDED.card_count, ygo_sets.card_count),
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING *
+            "#,
+            &[&data.name, &data.code, &data.release_date, &data.card_count],
+        )
+        .await?;
+
+    (&row).try_into()
+}
+
+/// Inserts many sets at once, like `upsert`, but keeps the code of existing sets
+/// since codes derived from card prints are less reliable. Sets must not share a name.
+pub async fn upsert_all(
+    client: &Client,
+    sets: &[ygo::CardSetData],
+) -> Result<Vec<ygo::CardSet>, Error> {
+    let names: Vec<&str> = sets.iter().map(|set| set.name.as_str()).collect();
+    let codes: Vec<&str> = sets.iter().map(|set| set.code.as_str()).collect();
+    let release_dates: Vec<_> = sets.iter().map(|set| set.release_date).collect();
+    let card_counts: Vec<_> = sets.iter().map(|set| set.card_count).collect();
+
+    let rows = client
+        .query(
+            r#"
+            INSERT INTO ygo_sets (
+                name,
+                code,
+                release_date,
+                card_count
+            )
+            SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[], $3::DATE[], $4::INTEGER[])
+            ON CONFLICT (name) DO UPDATE SET
+                release_date = COALESCE(EXCLUDED.release_date, ygo_sets.release_date),
+                card_count = COALESCE(EXCLUDED.card_count, ygo_sets.card_count),
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING *
+            "#,
+            &[&names, &codes, &release_dates, &card_counts],
+        )
+        .await?;
+
+    rows.iter().map(|row| row.try_into()).collect()
+}
+
+/// Retrieves the printings of a card, oldest releases first
+pub async fn get_prints_by_card_id(
+    client: &Client,
+    card_id: i32,
+) -> Result<Vec<ygo::CardPrint>, Error> {
+    let query = r#"
+        SELECT
+            p.*,
+            s.name AS set_name,
+            s.code AS set_code,
+            s.release_date AS set_release_date,
+            s.card_count AS set_card_count
+        FROM ygo_card_prints p
+        JOIN ygo_sets s ON s.id = p.set_id
+        WHERE p.card_id = $1
+        ORDER BY s.release_date ASC NULLS LAST, p.code ASC, p.id ASC
+    "#;
+    let rows = client.query(query, &[&card_id]).await?;
+
+    rows.iter().map(|row| row.try_into()).collect()
+}
+
+/// Retrieves a card printing by ID
+pub async fn get_print_by_id(client: &Client, id: i32) -> Result<Option<ygo::CardPrint>, Error> {
+    let query = r#"
+        SELECT
+            p.*,
+            s.name AS set_name,
+            s.code AS set_code,
+            s.release_date AS set_release_date,
+            s.card_count AS set_card_count
+        FROM ygo_card_prints p
+        JOIN ygo_sets s ON s.id = p.set_id
+        WHERE p.id = $1
+    "#;
+    let row = &client.query_opt(query, &[&id]).await?;
+
+    row.as_ref().map(|r| r.try_into()).transpose()
+}
+
+/// Inserts card printings that are not already known, and updates the rarity code of the others.
+/// Printings must not share a card, set, code and rarity.
+pub async fn upsert_prints(client: &Client, prints: &[ygo::NewCardPrint]) -> Result<(), Error> {
+    let card_ids: Vec<i32> = prints.iter().map(|print| print.card_id).collect();
+    let set_ids: Vec<i32> = prints.iter().map(|print| print.set_id).collect();
+    let codes: Vec<&str> = prints.iter().map(|print| print.code.as_str()).collect();
+    let rarities: Vec<&str> = prints.iter().map(|print| print.rarity.as_str()).collect();
+    let rarity_codes: Vec<_> = prints
+        .iter()
+        .map(|print| print.rarity_code.as_deref())
+        .collect();
+
+    client
+        .execute(
+            r#"
+            INSERT INTO ygo_card_prints (
+                card_id,
+                set_id,
+                code,
+                rarity,
+                rarity_code
+            )
+            SELECT * FROM UNNEST($1::INTEGER[], $2::INTEGER[], $3::TEXT[], $4::TEXT[], $5::TEXT[])
+            ON CONFLICT (card_id, set_id, code, rarity) DO UPDATE SET
+                rarity_code = EXCLUDED.rarity_code
+            "#,
+            &[&card_ids, &set_ids, &codes, &rarities, &rarity_codes],
+        )
+        .await?;
+
+    Ok(())
+}
+
+impl TryFrom<&Row> for ygo::CardSet {
+    type Error = Error;
+
+    /// Converts a database row into a CardSet struct
+    fn try_from(value: &Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.try_get("id")?,
+            data: ygo::CardSetData {
+                name: value.try_get("name")?,
+                code: value.try_get("code")?,
+                release_date: value.try_get("release_date")?,
+                card_count: value.try_get("card_count")?,
+            },
+        })
+    }
+}
+
+impl TryFrom<&Row> for ygo::CardPrint {
+    type Error = Error;
+
+    /// Converts a database row, joined with its set's columns, into a CardPrint struct
+    fn try_from(value: &Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.try_get("id")?,
+            card_id: value.try_get("card_id")?,
+            code: value.try_get("code")?,
+            rarity: value.try_get("rarity")?,
+            rarity_code: value.try_get("rarity_code")?,
+            set: ygo::CardSet {
+                id: value.try_get("set_id")?,
+                data: ygo::CardSetData {
+                    name: value.try_get("set_name")?,
+                    code: value.try_get("set_code")?,
+                    release_date: value.try_get("set_release_date")?,
+                    card_count: value.try_get("set_card_count")?,
+                },
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::{services::ygo::card::seed_cards, test_utils::with_db_pool};
+
+    fn make_set(name: &str, code: &str, release_date: Option<NaiveDate>) -> ygo::CardSetData {
+        ygo::CardSetData {
+            name: name.to_string(),
+            code: code.to_string(),
+            release_date,
+            card_count: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upsert_inserts_then_updates_by_name() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+
+            let date = NaiveDate::from_ymd_opt(2002, 3, 8);
+            let created = upsert(&client, &make_set("Legend of Blue Eyes", "LOB", date))
+                .await
+                .expect("insert");
+            assert_eq!(created.data.release_date, date);
+
+            // Unknown release dates do not erase the known one
+            let updated = upsert(&client, &make_set("Legend of Blue Eyes", "LOB", None))
+                .await
+                .expect("update");
+            assert_eq!(updated.id, created.id);
+            assert_eq!(updated.data.release_date, date);
+
+            let fetched = get_by_id(&client, created.id).await.expect("fetch");
+            assert_eq!(fetched, Some(updated));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_upsert_all_keeps_existing_codes() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+
+            let date = NaiveDate::from_ymd_opt(2002, 3, 8);
+            let created = upsert(&client, &make_set("Legend of Blue Eyes", "LOB", None))
+                .await
+                .expect("insert");
+
+            let sets = upsert_all(
+                &client,
+                &[
+                    make_set("Legend of Blue Eyes", "LOB-EN", date),
+                    make_set("Metal Raiders", "MRD-EN", None),
+                ],
+            )
+            .await
+            .expect("upsert all");
+            assert_eq!(sets.len(), 2);
+
+            let existing = sets
+                .iter()
+                .find(|set| set.id == created.id)
+                .expect("existing");
+            assert_eq!(existing.data.code, "LOB");
+            assert_eq!(existing.data.release_date, date);
+
+            let inserted = sets
+                .iter()
+                .find(|set| set.id != created.id)
+                .expect("inserted");
+            assert_eq!(inserted.data.code, "MRD-EN");
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_get_all_sorts_and_filters() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+
+            let lob = make_set(
+                "Legend of Blue Eyes",
+                "LOB",
+                NaiveDate::from_ymd_opt(2002, 3, 8),
+            );
+            let mrd = make_set("Metal Raiders", "MRD", NaiveDate::from_ymd_opt(2002, 6, 26));
+            let promo = make_set("Promo", "PRM", None);
+            for set in [&lob, &mrd, &promo] {
+                upsert(&client, set).await.expect("insert");
+            }
+
+            let sets = get_all(&client, None).await.expect("all");
+            let names: Vec<_> = sets.iter().map(|s| s.data.name.as_str()).collect();
+            assert_eq!(names, vec!["Metal Raiders", "Legend of Blue Eyes", "Promo"]);
+
+            let filter = Filter {
+                name: Some("blue".into()),
+                ..Default::default()
+            };
+            let sets = get_all(&client, Some(filter)).await.expect("filtered");
+            assert_eq!(sets.len(), 1);
+            assert_eq!(sets[0].data.code, "LOB");
+
+            let filter = Filter {
+                code: Some("mrd".into()),
+                ..Default::default()
+            };
+            let sets = get_all(&client, Some(filter)).await.expect("filtered");
+            assert_eq!(sets.len(), 1);
+            assert_eq!(sets[0].data.name, "Metal Raiders");
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_upsert_prints_and_get_prints_by_card_id() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            seed_cards(&client, 2).await.expect("seed");
+
+            let lob = upsert(
+                &client,
+                &make_set(
+                    "Legend of Blue Eyes",
+                    "LOB",
+                    NaiveDate::from_ymd_opt(2002, 3, 8),
+                ),
+            )
+            .await
+            .expect("set");
+            let sdk = upsert(
+                &client,
+                &make_set(
+                    "Starter Deck: Kaiba",
+                    "SDK",
+                    NaiveDate::from_ymd_opt(2002, 3, 29),
+                ),
+            )
+            .await
+            .expect("set");
+
+            let prints = [
+                (1, &sdk, "SDK-001", "Ultra Rare"),
+                (1, &lob, "LOB-001", "Ultra Rare"),
+                (2, &lob, "LOB-005", "Ultra Rare"),
+            ];
+            let prints: Vec<_> = prints
+                .into_iter()
+                .map(|(card_id, set, code, rarity)| ygo::NewCardPrint {
+                    card_id,
+                    set_id: set.id,
+                    code: code.to_string(),
+                    rarity: rarity.to_string(),
+                    rarity_code: None,
+                })
+                .collect();
+            upsert_prints(&client, &prints).await.expect("prints");
+            let first = get_prints_by_card_id(&client, 1).await.expect("prints");
+
+            // Upserting twice keeps a single print, with the latest rarity code
+            let prints: Vec<_> = prints
+                .into_iter()
+                .map(|print| ygo::NewCardPrint {
+                    rarity_code: Some("(UR)".to_string()),
+                    ..print
+                })
+                .collect();
+            upsert_prints(&client, &prints).await.expect("prints");
+
+            let prints = get_prints_by_card_id(&client, 1).await.expect("prints");
+            assert_eq!(
+                prints.iter().map(|p| p.id).collect::<Vec<_>>(),
+                first.iter().map(|p| p.id).collect::<Vec<_>>()
+            );
+            assert_eq!(prints[0].rarity_code.as_deref(), Some("(UR)"));
+            let codes: Vec<_> = prints.iter().map(|p| p.code.as_str()).collect();
+            assert_eq!(codes, vec!["LOB-001", "SDK-001"]);
+            assert_eq!(prints[0].set, lob);
+            assert_eq!(prints[1].rarity, "Ultra Rare");
+
+            let none = get_prints_by_card_id(&client, 144).await.expect("prints");
+            assert!(none.is_empty());
+
+            let print = get_print_by_id(&client, prints[0].id).await.expect("print");
+            assert_eq!(print.as_ref(), Some(&prints[0]));
+        })
+        .await;
+    }
+}
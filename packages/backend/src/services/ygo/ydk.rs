@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use std::result::Result;
+use tokio_postgres::{Client, Error};
+
+use crate::models::ygo;
+use crate::services::ygo as service;
+
+/// A deck in the YDK format: card passwords listed by zone, one entry per copy
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ydk {
+    pub main: Vec<String>,
+    pub extra: Vec<String>,
+    pub side: Vec<String>,
+}
+
+impl Ydk {
+    fn zone_mut(&mut self, zone: &ygo::DeckZone) -> &mut Vec<String> {
+        match zone {
+            ygo::DeckZone::Main => &mut self.main,
+            ygo::DeckZone::Extra => &mut self.extra,
+            ygo::DeckZone::Side => &mut self.side,
+        }
+    }
+}
+
+/// A YDK file resolved into a deck
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedYdk {
+    pub deck: ygo::DeckData,
+    /// Passwords that don't match any card, which were left out of the deck
+    pub unknown_passwords: Vec<String>,
+}
+
+/// Returns the zone a section header opens, if the line is one
+fn section(line: &str) -> Option<ygo::DeckZone> {
+    match line.to_ascii_lowercase().as_str() {
+        "#main" => Some(ygo::DeckZone::Main),
+        "#extra" => Some(ygo::DeckZone::Extra),
+        "!side" => Some(ygo::DeckZone::Side),
+        _ => None,
+    }
+}
+
+/// Parses the content of a YDK file.
+/// Other lines starting with `#` or `!` are comments (e.g. `#created by ...`).
+pub fn parse(content: &str) -> Result<Ydk, String> {
+    let mut ydk = Ydk::default();
+    let mut zone = None;
+
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(new_zone) = section(line) {
+            zone = Some(new_zone);
+            continue;
+        }
+        if line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+
+        let Some(zone) = &zone else {
+            return Err(format!("line {}: card outside of a section", idx + 1));
+        };
+        // Passwords are stored without leading zeros
+        let password: u32 = line
+            .parse()
+            .map_err(|_| format!("line {}: invalid password '{line}'", idx + 1))?;
+        ydk.zone_mut(zone).push(password.to_string());
+    }
+
+    Ok(ydk)
+}
+
+/// Writes a deck in the YDK format
+pub fn write(ydk: &Ydk) -> String {
+    let mut content = String::from("#created by cardfolio\n");
+
+    for (header, passwords) in [
+        ("#main", &ydk.main),
+        ("#extra", &ydk.extra),
+        ("!side", &ydk.side),
+    ] {
+        content.push_str(header);
+        content.push('\n');
+        for password in passwords {
+            content.push_str(password);
+            content.push('\n');
+        }
+    }
+
+    content
+}
+
+/// Resolves the passwords of a YDK file into a deck of the given name.
+/// Unknown passwords are reported once each, in order of first appearance.
+pub async fn resolve(client: &Client, ydk: &Ydk, name: String) -> Result<ResolvedYdk, Error> {
+    let mut resolved = ResolvedYdk {
+        deck: ygo::DeckData {
+            name,
+            ..Default::default()
+        },
+        unknown_passwords: Vec::new(),
+    };
+    let mut card_ids: HashMap<&str, Option<i32>> = HashMap::new();
+
+    for (zone, passwords) in [
+        (ygo::DeckZone::Main, &ydk.main),
+        (ygo::DeckZone::Extra, &ydk.extra),
+        (ygo::DeckZone::Side, &ydk.side),
+    ] {
+        for password in passwords {
+            let card_id = match card_ids.get(password.as_str()) {
+                Some(card_id) => *card_id,
+                None => {
+                    let card = service::card::get_by_password(client, password).await?;
+                    let card_id = card.map(|card| card.id);
+                    if card_id.is_none() {
+                        resolved.unknown_passwords.push(password.clone());
+                    }
+                    card_ids.insert(password, card_id);
+                    card_id
+                }
+            };
+
+            if let Some(card_id) = card_id {
+                resolved.deck.zone_mut(&zone).push(card_id);
+            }
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Converts a deck to the YDK format.
+/// Cards without a password cannot be listed and are left out.
+pub async fn export(client: &Client, deck: &ygo::DeckData) -> Result<Ydk, Error> {
+    let card_ids: Vec<i32> = deck.card_ids().collect();
+    let passwords: HashMap<i32, String> = service::card::get_by_ids(client, &card_ids)
+        .await?
+        .into_iter()
+        .filter_map(|card| card.data.password.map(|password| (card.id, password)))
+        .collect();
+
+    let mut ydk = Ydk::default();
+    for zone in ygo::DeckZone::ALL {
+        *ydk.zone_mut(&zone) = deck
+            .zone(&zone)
+            .iter()
+            .filter_map(|card_id| passwords.get(card_id).cloned())
+            .collect();
+    }
+
+    Ok(ydk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::with_db_pool;
+
+    const YDK: &str = "#created by Player\r\n#main\r\n89631139\r\n89631139\r\n46986414\r\n#extra\r\n\r\n!side\r\n05318639\r\n";
+
+    fn to_strings(passwords: &[&str]) -> Vec<String> {
+        passwords.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let ydk = parse(YDK).expect("parse");
+        assert_eq!(ydk.main, to_strings(&["89631139", "89631139", "46986414"]));
+        assert!(ydk.extra.is_empty());
+        // Leading zeros are dropped
+        assert_eq!(ydk.side, to_strings(&["5318639"]));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("#main\n89631139\nBlue-Eyes\n"),
+            Err("line 3: invalid password 'Blue-Eyes'".to_string())
+        );
+        assert_eq!(
+            parse("89631139\n#main\n"),
+            Err("line 1: card outside of a section".to_string())
+        );
+    }
+
+    #[test]
+    fn test_write_then_parse() {
+        let ydk = Ydk {
+            main: to_strings(&["89631139", "46986414"]),
+            extra: to_strings(&["23995346"]),
+            side: vec![],
+        };
+        let content = write(&ydk);
+        assert_eq!(
+            content,
+            "#created by cardfolio\n#main\n89631139\n46986414\n#extra\n23995346\n!side\n"
+        );
+        assert_eq!(parse(&content), Ok(ydk));
+    }
+
+    async fn create_card(client: &Client, name: &str, password: Option<&str>) -> i32 {
+        let new = ygo::NewCard {
+            data: ygo::CardData {
+                name: name.to_string(),
+                password: password.map(|p| p.to_string()),
+                ..Default::default()
+            },
+        };
+        service::card::save_new(client, &new)
+            .await
+            .expect("card")
+            .id
+    }
+
+    #[tokio::test]
+    async fn test_resolve_reports_unknown_passwords() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            let blue_eyes = create_card(&client, "Blue-Eyes", Some("89631139")).await;
+
+            let ydk = Ydk {
+                main: to_strings(&["89631139", "1", "89631139", "1"]),
+                extra: vec![],
+                side: to_strings(&["2", "89631139"]),
+            };
+            let resolved = resolve(&client, &ydk, "Kaiba".to_string())
+                .await
+                .expect("resolve");
+            assert_eq!(resolved.deck.name, "Kaiba");
+            assert_eq!(resolved.deck.main, vec![blue_eyes, blue_eyes]);
+            assert_eq!(resolved.deck.side, vec![blue_eyes]);
+            assert_eq!(resolved.unknown_passwords, to_strings(&["1", "2"]));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_export_skips_cards_without_password() {
+        with_db_pool(async move |db| {
+            let client = db.get().await.expect("db");
+            let blue_eyes = create_card(&client, "Blue-Eyes", Some("89631139")).await;
+            let token = create_card(&client, "Token", None).await;
+
+            let deck = ygo::DeckData {
+                main: vec![blue_eyes, token, blue_eyes],
+                side: vec![blue_eyes],
+                ..Default::default()
+            };
+            let ydk = export(&client, &deck).await.expect("export");
+            assert_eq!(ydk.main, to_strings(&["89631139", "89631139"]));
+            assert!(ydk.extra.is_empty());
+            assert_eq!(ydk.side, to_strings(&["89631139"]));
+        })
+        .await;
+    }
+}
@@ -0,0 +1,110 @@
+use base64::{Engine as _, engine::general_purpose};
+
+use super::ydk::Ydk;
+
+/// Scheme of the deck URLs shared by Duelingbook, EDOPro, etc.
+pub const SCHEME: &str = "ydke://";
+
+/// Decodes a `ydke://` URL. Each zone (main, extra, side) is a segment ended by `!`,
+/// holding the base64 of its passwords as little-endian u32.
+pub fn decode(url: &str) -> Result<Ydk, String> {
+    let segments = url
+        .trim()
+        .strip_prefix(SCHEME)
+        .ok_or(format!("URL must start with {SCHEME}"))?;
+
+    let mut zones: Vec<Vec<String>> = segments
+        .split('!')
+        .map(decode_zone)
+        .collect::<Result<_, _>>()?;
+
+    // The URL ends with a separator, leaving an empty last segment
+    if zones.len() == 4 && zones[3].is_empty() {
+        zones.pop();
+    }
+    let [main, extra, side]: [Vec<String>; 3] = zones
+        .try_into()
+        .map_err(|_| "URL must have 3 zones (main, extra and side)".to_string())?;
+
+    Ok(Ydk { main, extra, side })
+}
+
+fn decode_zone(segment: &str) -> Result<Vec<String>, String> {
+    let bytes = general_purpose::STANDARD
+        .decode(segment)
+        .map_err(|e| format!("Cannot decode zone: {e}"))?;
+    if bytes.len() % 4 != 0 {
+        return Err("Cannot decode zone: passwords must be 4 bytes long".to_string());
+    }
+
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]).to_string())
+        .collect())
+}
+
+/// Encodes a deck as a `ydke://` URL.
+/// Passwords that aren't numbers cannot be encoded and are left out.
+pub fn encode(ydk: &Ydk) -> String {
+    let mut url = String::from(SCHEME);
+
+    for passwords in [&ydk.main, &ydk.extra, &ydk.side] {
+        let bytes: Vec<u8> = passwords
+            .iter()
+            .filter_map(|password| password.parse::<u32>().ok())
+            .flat_map(u32::to_le_bytes)
+            .collect();
+        url.push_str(&general_purpose::STANDARD.encode(bytes));
+        url.push('!');
+    }
+
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_strings(passwords: &[&str]) -> Vec<String> {
+        passwords.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_encode_then_decode() {
+        let ydk = Ydk {
+            main: to_strings(&["89631139", "89631139", "46986414"]),
+            extra: to_strings(&["23995346"]),
+            side: vec![],
+        };
+
+        let url = encode(&ydk);
+        assert_eq!(url, "ydke://o6lXBaOpVwWu9MwC!0iNuAQ==!!");
+        assert_eq!(decode(&url), Ok(ydk));
+    }
+
+    #[test]
+    fn test_encode_skips_invalid_passwords() {
+        let ydk = Ydk {
+            main: to_strings(&["89631139", "Token"]),
+            ..Default::default()
+        };
+        assert_eq!(encode(&ydk), "ydke://o6lXBQ==!!!");
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(
+            decode("https://example.com"),
+            Err("URL must start with ydke://".to_string())
+        );
+        assert_eq!(
+            decode("ydke://o6lXBQ==!"),
+            Err("URL must have 3 zones (main, extra and side)".to_string())
+        );
+        assert_eq!(
+            decode("ydke://o6lX!!!"),
+            Err("Cannot decode zone: passwords must be 4 bytes long".to_string())
+        );
+        assert!(decode("ydke://@@@@!!!").is_err());
+    }
+}
@@ -2,12 +2,22 @@ use std::{env, ops::Deref, panic, sync::Arc};
 
 use tracing::level_filters::LevelFilter;
 
+use crate::services::image_cache::ImageCache;
+use crate::services::image_storage::ImageStorageConfig;
 use crate::{database, migrations, prelude::*};
 
 use futures_util::FutureExt;
 pub use http_body_util::BodyExt;
 pub use tower::ServiceExt; // Import for .oneshot() // Import for .catch_unwind()
 
+/// Utility to create an empty directory for a test, deleted along with its content once dropped
+pub fn test_dir(name: &str) -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix(&format!("cardfolio-{name}-"))
+        .tempdir()
+        .expect("Failed to create test directory")
+}
+
 /// Utility to create a database connection pool for testing
 pub async fn with_db_pool<Fn, Fut>(f: Fn)
 where
@@ -71,12 +81,24 @@ where
             db_pool_size: 1,
             frontend_dir: "../frontend/dist/".to_string(),
             content_dir: "../../run/test/content/".to_string(),
+            image_storage: ImageStorageConfig::Filesystem,
+            image_cache_max_size: None,
         };
 
         let db = db_pool.deref().clone();
+        let images = ImageCache::new(
+            config.image_storage.build(config.get_content_path()),
+            config.image_cache_max_size,
+        );
 
         // Run the wrapped function
-        let state = Arc::new(AppState { config, db });
+        let state = Arc::new(AppState {
+            config,
+            db,
+            images,
+            prefetch: Default::default(),
+            transcoder: Default::default(),
+        });
         f(state).await
     })
     .await